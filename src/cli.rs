@@ -0,0 +1,2356 @@
+//! The `clap` based command line interface of the `git-nomad` binary, which translates arguments,
+//! environment variables, and git config into a [`Workflow`].
+//!
+//! This lives in the binary rather than the library so that embedders don't depend on `clap`.
+
+use std::{borrow::Cow, collections::HashSet, ffi::OsString, path::Path, time::Duration};
+
+use clap::{
+    builder::PossibleValue, crate_authors, crate_description, crate_name, crate_version,
+    parser::ValueSource, value_parser, Arg, ArgAction, ArgMatches, Command, ValueHint,
+};
+use git_version::git_version;
+
+use git_nomad::{
+    git_backend::GitBackend,
+    git_binary::{ConfigEntry, ConfigScope, GitBinary},
+    ls_format::LsFormat,
+    renderer::Renderer,
+    types::{Branch, Host, Remote, User},
+    verbosity::Verbosity,
+    workflow::{
+        BranchPatterns, Filter, InitSetting, LsPrinter, Setting, SettingSource, SyncExtras,
+        Workflow,
+    },
+    CONFIG_CLONE_ID, CONFIG_EXCLUDE, CONFIG_HOST, CONFIG_INCLUDE, CONFIG_REMOTE, CONFIG_SYNC_STASH,
+    CONFIG_SYNC_TAGS, CONFIG_USER,
+};
+
+const DEFAULT_REMOTE: Remote<'static> = Remote(Cow::Borrowed("origin"));
+const ENV_USER: &str = "GIT_NOMAD_USER";
+const ENV_HOST: &str = "GIT_NOMAD_HOST";
+const ENV_REMOTE: &str = "GIT_NOMAD_REMOTE";
+
+const BUILD_VERSION: Option<&str> = option_env!("GIT_NOMAD_BUILD_VERSION");
+
+// This value is only conditionally used if `git_version!` cannot find any other version.
+const _CARGO_VERSION: &str = crate_version!();
+const GIT_VERSION: &str = git_version!(
+    prefix = "git:",
+    args = ["--tags", "--always", "--dirty=-modified"],
+    fallback = _CARGO_VERSION,
+);
+
+fn version() -> &'static str {
+    BUILD_VERSION.unwrap_or(GIT_VERSION)
+}
+
+/// Run `git-nomad` as if invoked from `cwd` with the command line `args`, where the first
+/// argument is the name of the binary.
+///
+/// Invalid arguments (and `--help`) print a message and exit the process, like any `clap` based
+/// binary. Other failures are returned, see [`git_nomad::error::exit_code`] for how they map to exit
+/// codes.
+pub fn nomad(
+    renderer: &mut impl Renderer,
+    args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+    cwd: &Path,
+) -> anyhow::Result<()> {
+    let default_user = whoami::fallible::username().ok().map(User::from);
+    let default_host = whoami::fallible::hostname().ok().map(Host::from);
+
+    let mut matches = cli(default_user, default_host, args).unwrap_or_else(|e| e.exit());
+    let verbosity = specified_verbosity(&mut matches);
+
+    if verbosity.is_some_and(|v| v.display_version) {
+        renderer.writer(|w| {
+            writeln!(w)?;
+            writeln!(w, "Version: {}", version())?;
+            Ok(())
+        })?;
+    }
+
+    let git = GitBinary::new(
+        renderer,
+        verbosity,
+        Cow::from(specified_git(&mut matches)),
+        cwd,
+    )?;
+    #[cfg(feature = "gix")]
+    let git = git_nomad::git_gix::GixBackend::new(git)?;
+    let workflow = specified_workflow(renderer, &mut matches, &git)?;
+
+    if verbosity.is_some_and(|v| v.display_workflow) {
+        renderer.writer(|w| {
+            writeln!(w)?;
+            writeln!(w, "Workflow: {:?}", workflow)?;
+            Ok(())
+        })?;
+    }
+
+    workflow.execute(renderer, &git)
+}
+
+fn maybe_apply_default(arg: Arg, optional_default: Option<String>) -> Arg {
+    if let Some(default) = optional_default {
+        arg.default_value(default)
+    } else {
+        arg
+    }
+}
+
+#[cfg(test)]
+mod test_maybe_apply_default {
+    use clap::{builder::OsStr, Arg};
+
+    use super::maybe_apply_default;
+
+    #[test]
+    fn apply_some() {
+        let arg = maybe_apply_default(Arg::new("test"), Some("default".into()));
+        assert_eq!(arg.get_default_values(), &["default"]);
+    }
+
+    #[test]
+    fn apply_none() {
+        let arg = maybe_apply_default(Arg::new("test"), None);
+        assert_eq!(arg.get_default_values(), &[] as &[OsStr]);
+    }
+}
+
+/// Use [`clap`] to implement the intended command line interface.
+fn cli(
+    default_user: Option<User>,
+    default_host: Option<Host>,
+    args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+) -> clap::error::Result<ArgMatches> {
+    Command::new(crate_name!())
+        .arg_required_else_help(true)
+        .version(version())
+        .author(crate_authors!())
+        .about(crate_description!())
+        .arg(
+            Arg::new("git")
+                .global(true)
+                .long("git")
+                .help("Git binary to use")
+                .value_parser(value_parser!(String))
+                .value_hint(ValueHint::CommandName)
+                .default_value("git"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .global(true)
+                .short('q')
+                .long("quiet")
+                .help("Suppress all output")
+                .value_parser(value_parser!(bool))
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dry_run")
+                .global(true)
+                .short('n')
+                .long("dry-run")
+                .help("Print what would change without modifying the local clone or the remote")
+                .value_parser(value_parser!(bool))
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("verbose")
+                .global(true)
+                .short('v')
+                .long("verbose")
+                .help("Verbose output, repeat up to 2 times for increasing verbosity")
+                .value_parser(value_parser!(u8))
+                .action(ArgAction::Count),
+        )
+        .arg(
+            maybe_apply_default(
+                Arg::new("user")
+                    .global(true)
+                    .short('U')
+                    .long("user")
+                    .help("User name, shared by multiple clones, unique per remote")
+                    .value_parser(value_parser!(String))
+                    .value_hint(ValueHint::Username)
+                    .env(ENV_USER),
+                default_user.map(|u| u.0.into_owned()),
+            )
+        )
+        .arg(
+            maybe_apply_default(
+                Arg::new("host")
+                    .global(true)
+                    .short('H')
+                    .long("host")
+                    .value_parser(value_parser!(String))
+                    .value_hint(ValueHint::Hostname)
+                    .env(ENV_HOST)
+                    .help("Host name, unique per clone"),
+                default_host.map(|h| h.0.into_owned()),
+            )
+        )
+        .arg(
+            Arg::new("remote")
+                .global(true)
+                .short('R')
+                .long("remote")
+                .help("Git remote to operate against (can be specified multiple times)")
+                .value_parser(value_parser!(String))
+                .value_hint(ValueHint::Other)
+                .action(ArgAction::Append)
+                .env(ENV_REMOTE)
+                .default_value(DEFAULT_REMOTE.0.as_ref())
+        )
+        .subcommand(
+            Command::new("init")
+                .about("Confirm or override the user, host, and remotes, then save them in git config")
+                .arg(config_global_arg())
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .help("Save the resolved values without asking")
+                        .value_parser(value_parser!(bool))
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Inspect and change nomad settings in git config")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("show")
+                        .about("Print every setting with its value and where the value came from"),
+                )
+                .subcommand(
+                    Command::new("get")
+                        .about("Print every value of nomad.<KEY>")
+                        .arg(config_key_arg()),
+                )
+                .subcommand(
+                    Command::new("set")
+                        .about("Replace every value of nomad.<KEY>")
+                        .arg(config_key_arg())
+                        .arg(
+                            Arg::new("value")
+                                .help("Value to set (can be specified multiple times for keys like remote)")
+                                .required(true)
+                                .num_args(1..)
+                                .value_parser(value_parser!(String)),
+                        )
+                        .arg(config_global_arg()),
+                )
+                .subcommand(
+                    Command::new("unset")
+                        .about("Remove every value of nomad.<KEY>")
+                        .arg(config_key_arg())
+                        .arg(config_global_arg()),
+                ),
+        )
+        .subcommand(
+            Command::new("sync")
+                .about("Sync local branches to remote")
+                .args(branch_pattern_args())
+                .arg(
+                    Arg::new("stash")
+                        .long("stash")
+                        .help("Also sync git stash entries (defaults to nomad.syncStash)")
+                        .value_parser(value_parser!(bool))
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("tags")
+                        .long("tags")
+                        .help("Also sync tags (defaults to nomad.syncTags)")
+                        .value_parser(value_parser!(bool))
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("include_worktree")
+                        .long("include-worktree")
+                        .help("Also sync a snapshot of uncommitted changes for the current branch, without touching HEAD or the index")
+                        .value_parser(value_parser!(bool))
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("push")
+                .about("Push specific local branches to remote without fetching or pruning (used by hooks)")
+                .arg(
+                    Arg::new("branch")
+                    .short('b')
+                    .long("branch")
+                    .help("Local branch to push (can be specified multiple times)")
+                    .required(true)
+                    .value_parser(value_parser!(String))
+                    .action(ArgAction::Append)
+                )
+                .args(branch_pattern_args()),
+        )
+        .subcommand(
+            Command::new("hooks")
+                .about("Manage git hooks that push branches in the background as they change")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("install")
+                        .about("Install post-commit, post-checkout, and reference-transaction hooks"),
+                )
+                .subcommand(
+                    Command::new("uninstall")
+                        .about("Remove hooks installed by nomad, restoring the hooks they chained to"),
+                ),
+        )
+        .subcommand(
+            Command::new("ls")
+                .about("List nomad managed refs")
+                .arg(
+                    Arg::new("fetch")
+                        .short('F')
+                        .long("fetch")
+                        .help("Fetch refs from remote before listing")
+                        .value_parser(value_parser!(bool))
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("print")
+                        .long("print")
+                        .help("Format for listing nomad managed refs")
+                        .value_parser([
+                            PossibleValue::new("grouped")
+                                .help("Print ref name and commit ID grouped by host"),
+                            PossibleValue::new("ref").help("Print only the ref name"),
+                            PossibleValue::new("commit").help("Print only the commit ID"),
+                            PossibleValue::new("json")
+                                .help("Print a JSON array with an object for every ref"),
+                            PossibleValue::new("jsonl")
+                                .help("Print a JSON object for every ref, one per line"),
+                        ])
+                        .default_value("grouped"),
+                )
+                .arg(
+                    Arg::new("long")
+                    .short('l')
+                    .long("long")
+                    .help("Print the abbreviated commit ID, age, and subject of every ref grouped by host")
+                    .conflicts_with("print")
+                    .value_parser(value_parser!(bool))
+                    .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("format")
+                    .long("format")
+                    .value_name("TEMPLATE")
+                    .help("Print every ref by filling in a template, like `%(host) %(branch)`")
+                    .long_help(concat!(
+                        "Print every ref by filling in a template, one line per ref.\n\n",
+                        "Supported placeholders are %(user), %(host), %(branch), %(refname), ",
+                        "%(objectname), %(objectname:short), %(subject), and ",
+                        "%(committerdate:relative). Use %% for a literal %.",
+                    ))
+                    .conflicts_with_all(["print", "long"])
+                    .value_parser(LsFormat::parse),
+                )
+                .arg(
+                    Arg::new("head")
+                    .long("head")
+                    .help("Only display refs for the current branch")
+                    .value_parser(value_parser!(bool))
+                    .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("branch")
+                    .short('b')
+                    .long("branch")
+                    .help("Only display refs for the named branch (can be specified multiple times)")
+                    .value_parser(value_parser!(String))
+                    .action(ArgAction::Append)
+                )
+                .arg(
+                    Arg::new("print_self")
+                    .long("print-self")
+                    .help("Print refs for the current host")
+                    .value_parser(value_parser!(bool))
+                    .action(ArgAction::SetTrue)
+                ),
+        )
+        .subcommand(
+            Command::new("purge")
+                .about("Delete nomad refs locally and on the remote")
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .help("Delete refs for all hosts")
+                        .value_parser(value_parser!(bool))
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("older_than")
+                        .long("older-than")
+                        .value_name("AGE")
+                        .help("Delete refs for any other host (or any host at all with --all) whose newest ref was committed longer than AGE ago, like 30d (units: s, m, h, d, w)")
+                        .value_parser(parse_age),
+                ),
+        )
+        .subcommand(
+            Command::new("status")
+                .about("Compare copies of the same branch across hosts")
+                .arg(
+                    Arg::new("fetch")
+                        .short('F')
+                        .long("fetch")
+                        .help("Fetch refs from remote before comparing")
+                        .value_parser(value_parser!(bool))
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("branch")
+                    .short('b')
+                    .long("branch")
+                    .help("Only compare the named branch (can be specified multiple times)")
+                    .value_parser(value_parser!(String))
+                    .action(ArgAction::Append)
+                ),
+        )
+        .subcommand(
+            Command::new("stash")
+                .about("Use git stash entries synced from other hosts")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("apply")
+                        .about("Apply a stash entry from another host to the working tree")
+                        .arg(
+                            Arg::new("host")
+                                .help("Host that synced the stash entry")
+                                .required(true)
+                                .value_parser(value_parser!(String)),
+                        )
+                        .arg(
+                            Arg::new("index")
+                                .help("Which stash entry, like the 0 in stash@{0}")
+                                .default_value("0")
+                                .value_parser(value_parser!(usize)),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("worktree")
+                .about("Use working tree snapshots synced from other hosts")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("apply")
+                        .about("Apply uncommitted changes from another host to the working tree")
+                        .arg(
+                            Arg::new("host")
+                                .help("Host that synced the working tree snapshot")
+                                .required(true)
+                                .value_parser(value_parser!(String)),
+                        )
+                        .arg(
+                            Arg::new("branch")
+                                .help("Branch that was checked out on that host (defaults to the current branch)")
+                                .value_parser(value_parser!(String)),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("checkout")
+                .about("Check out a branch from another host")
+                .override_usage("git-nomad checkout [OPTIONS] [HOST] <BRANCH>")
+                .arg(
+                    Arg::new("host_and_branch")
+                        .help("Host to check out from (optional if only one other host has the branch) and the branch name")
+                        .value_names(["HOST", "BRANCH"])
+                        .num_args(1..=2)
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("fetch")
+                        .short('F')
+                        .long("fetch")
+                        .help("Fetch refs from remote before checking out")
+                        .value_parser(value_parser!(bool))
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("force")
+                        .short('f')
+                        .long("force")
+                        .help("Overwrite the local branch even if it has diverged")
+                        .value_parser(value_parser!(bool))
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .try_get_matches_from(args)
+}
+
+/// The key within `nomad.*` for `config` subcommands.
+fn config_key_arg() -> Arg {
+    Arg::new("key")
+        .help("Setting name like host, with or without the nomad. prefix")
+        .value_name("KEY")
+        .required(true)
+        .value_parser(|key: &str| -> Result<String, String> {
+            let key = key.strip_prefix("nomad.").unwrap_or(key);
+            if key.is_empty() {
+                Err("expected a setting name like host".to_string())
+            } else {
+                Ok(key.to_string())
+            }
+        })
+}
+
+/// Option to write global git config, shared by every subcommand that writes settings.
+fn config_global_arg() -> Arg {
+    Arg::new("global")
+        .long("global")
+        .help("Save in global git config instead of this repository's")
+        .value_parser(value_parser!(bool))
+        .action(ArgAction::SetTrue)
+}
+
+/// Options for choosing which local branches get pushed, shared by every subcommand that pushes.
+fn branch_pattern_args() -> [Arg; 2] {
+    [
+        Arg::new("include")
+            .long("include")
+            .help("Only push branches matching the glob pattern (can be specified multiple times, overrides nomad.include)")
+            .value_name("PATTERN")
+            .value_parser(value_parser!(String))
+            .action(ArgAction::Append),
+        Arg::new("exclude")
+            .long("exclude")
+            .help("Do not push branches matching the glob pattern (can be specified multiple times, overrides nomad.exclude)")
+            .value_name("PATTERN")
+            .value_parser(value_parser!(String))
+            .action(ArgAction::Append),
+    ]
+}
+
+/// Parse a human friendly age like `30d` into a [`Duration`].
+fn parse_age(value: &str) -> Result<Duration, String> {
+    let unit_start = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(unit_start);
+
+    let seconds_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err("expected a unit of s, m, h, d, or w, like 30d".to_string()),
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(seconds_per_unit))
+        .map(Duration::from_secs)
+        .ok_or_else(|| "expected a number followed by a unit, like 30d".to_string())
+}
+
+/// The [`Verbosity`] intended by the user via the CLI.
+fn specified_verbosity(matches: &mut ArgMatches) -> Option<Verbosity> {
+    if matches.remove_one::<bool>("quiet").expect("has default") {
+        None
+    } else {
+        match matches.remove_one::<u8>("verbose").expect("has default") {
+            0 => Some(Verbosity::default()),
+            1 => Some(Verbosity::verbose()),
+            _ => Some(Verbosity::max()),
+        }
+    }
+}
+
+/// The [`GitBinary`] intended by the user via the CLI.
+///
+/// # Panics
+///
+/// If [`clap`] does not prevent certain assumed invalid states.
+fn specified_git(matches: &mut ArgMatches) -> String {
+    matches.remove_one("git").expect("default value")
+}
+
+/// The nomad workflow the user intends to execute via the CLI.
+///
+/// # Panics
+///
+/// If [`clap`] does not prevent certain assumed invalid states.
+fn specified_workflow<'a>(
+    renderer: &mut impl Renderer,
+    matches: &'a mut ArgMatches,
+    git: &impl GitBackend,
+) -> anyhow::Result<Workflow<'a>> {
+    let user_source = matches.value_source("user");
+    let host_source = matches.value_source("host");
+    let remote_source = matches.value_source("remote");
+    let is_explicit = |source: Option<ValueSource>| {
+        matches!(
+            source,
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        )
+    };
+
+    let user = resolve(matches, "user", || {
+        git.get_config(renderer, CONFIG_USER)
+            .map(|opt| opt.map(User::from))
+    })?;
+
+    let host = resolve(matches, "host", || {
+        git.get_config(renderer, CONFIG_HOST)
+            .map(|opt| opt.map(Host::from))
+    })?;
+
+    let remotes = resolve_remotes(renderer, matches, git)?;
+
+    let dry_run = matches.remove_one::<bool>("dry_run").expect("has default");
+
+    let (subcommand, matches) = matches
+        .remove_subcommand()
+        .expect("subcommand is mandatory");
+
+    return match (subcommand.as_str(), matches) {
+        ("init", mut matches) => {
+            let yes = matches.remove_one::<bool>("yes").expect("has default");
+
+            Ok(Workflow::Init {
+                user: InitSetting {
+                    value: user,
+                    ask: !yes && !is_explicit(user_source),
+                },
+                host: InitSetting {
+                    value: host,
+                    ask: !yes && !is_explicit(host_source),
+                },
+                remotes: InitSetting {
+                    value: remotes,
+                    ask: !yes && !is_explicit(remote_source),
+                },
+                scope: if matches.remove_one::<bool>("global").expect("has default") {
+                    ConfigScope::Global
+                } else {
+                    ConfigScope::Local
+                },
+                dry_run,
+            })
+        }
+
+        ("config", mut matches) => {
+            let (subcommand, mut matches) = matches
+                .remove_subcommand()
+                .expect("subcommand is mandatory");
+
+            let scope = |matches: &mut ArgMatches| {
+                if matches.remove_one::<bool>("global").expect("has default") {
+                    ConfigScope::Global
+                } else {
+                    ConfigScope::Local
+                }
+            };
+
+            match subcommand.as_str() {
+                "show" => {
+                    let mut entries = |key| git.get_config_entries(renderer, None, key);
+
+                    Ok(Workflow::ConfigShow {
+                        settings: vec![
+                            setting(
+                                CONFIG_USER,
+                                vec![user.0.into_owned()],
+                                user_source,
+                                ENV_USER,
+                                entries(CONFIG_USER)?,
+                                "default from the operating system",
+                            ),
+                            setting(
+                                CONFIG_HOST,
+                                vec![host.0.into_owned()],
+                                host_source,
+                                ENV_HOST,
+                                entries(CONFIG_HOST)?,
+                                "default from the operating system",
+                            ),
+                            setting(
+                                CONFIG_REMOTE,
+                                remotes.into_iter().map(|r| r.0.into_owned()).collect(),
+                                remote_source,
+                                ENV_REMOTE,
+                                entries(CONFIG_REMOTE)?,
+                                "default",
+                            ),
+                            config_only_setting(
+                                CONFIG_INCLUDE,
+                                entries(CONFIG_INCLUDE)?,
+                                "default",
+                            ),
+                            config_only_setting(
+                                CONFIG_EXCLUDE,
+                                entries(CONFIG_EXCLUDE)?,
+                                "default",
+                            ),
+                            config_only_setting(
+                                CONFIG_SYNC_STASH,
+                                entries(CONFIG_SYNC_STASH)?,
+                                "default",
+                            ),
+                            config_only_setting(
+                                CONFIG_SYNC_TAGS,
+                                entries(CONFIG_SYNC_TAGS)?,
+                                "default",
+                            ),
+                            config_only_setting(
+                                CONFIG_CLONE_ID,
+                                entries(CONFIG_CLONE_ID)?,
+                                "generated on first sync",
+                            ),
+                        ],
+                    })
+                }
+                "get" => Ok(Workflow::ConfigGet {
+                    key: matches.remove_one("key").expect("required argument"),
+                }),
+                "set" => Ok(Workflow::ConfigSet {
+                    key: matches.remove_one("key").expect("required argument"),
+                    values: matches
+                        .remove_many("value")
+                        .expect("required argument")
+                        .collect(),
+                    scope: scope(&mut matches),
+                    dry_run,
+                }),
+                "unset" => Ok(Workflow::ConfigUnset {
+                    key: matches.remove_one("key").expect("required argument"),
+                    scope: scope(&mut matches),
+                    dry_run,
+                }),
+                _ => unreachable!("unknown config subcommand"),
+            }
+        }
+
+        ("sync", mut matches) => Ok(Workflow::Sync {
+            user,
+            host,
+            remotes,
+            branch_patterns: specified_branch_patterns(renderer, &mut matches, git)?,
+            extras: specified_sync_extras(renderer, &mut matches, git)?,
+            dry_run,
+        }),
+
+        ("push", mut matches) => Ok(Workflow::Push {
+            user,
+            host,
+            remotes,
+            branch_patterns: specified_branch_patterns(renderer, &mut matches, git)?,
+            branches: matches
+                .remove_many::<String>("branch")
+                .expect("required argument")
+                .map(Branch::from)
+                .collect(),
+            dry_run,
+        }),
+
+        ("hooks", mut matches) => {
+            let (subcommand, _) = matches
+                .remove_subcommand()
+                .expect("subcommand is mandatory");
+
+            match subcommand.as_str() {
+                "install" => Ok(Workflow::InstallHooks { dry_run }),
+                "uninstall" => Ok(Workflow::UninstallHooks { dry_run }),
+                _ => unreachable!("unknown hooks subcommand"),
+            }
+        }
+
+        ("ls", mut matches) => Ok(Workflow::Ls {
+            printer: match matches
+                .remove_one::<String>("print")
+                .expect("has default")
+                .as_str()
+            {
+                _ if matches.remove_one::<bool>("long").expect("has default") => LsPrinter::Long,
+                _ if matches.contains_id("format") => LsPrinter::Format(
+                    matches
+                        .remove_one::<LsFormat>("format")
+                        .expect("checked for presence"),
+                ),
+                "grouped" => LsPrinter::Grouped,
+                "ref" => LsPrinter::Ref,
+                "commit" => LsPrinter::Commit,
+                "json" => LsPrinter::Json,
+                "jsonl" => LsPrinter::JsonLines,
+                _ => unreachable!("has possible values"),
+            },
+            user,
+            fetch_remotes: if matches.remove_one::<bool>("fetch").expect("has default") {
+                remotes
+            } else {
+                Vec::new()
+            },
+            host_filter: if matches
+                .remove_one::<bool>("print_self")
+                .expect("has default")
+            {
+                Filter::All
+            } else {
+                Filter::Deny([host].into())
+            },
+            branch_filter: {
+                let mut branch_set = HashSet::<Branch>::new();
+
+                if matches.remove_one::<bool>("head").expect("has default") {
+                    branch_set.insert(git.current_branch(renderer)?);
+                }
+
+                if let Some(branches) = matches.remove_many::<String>("branch") {
+                    branch_set.extend(branches.map(Branch::from));
+                }
+
+                if branch_set.is_empty() {
+                    Filter::All
+                } else {
+                    Filter::Allow(branch_set)
+                }
+            },
+        }),
+
+        ("purge", mut matches) => {
+            let older_than = matches.remove_one::<Duration>("older_than");
+            let host_filter = if matches.remove_one::<bool>("all").expect("default value") {
+                Filter::All
+            } else if older_than.is_some() {
+                // The current host is never inactive from its own point of view, even if it
+                // hasn't committed in a while.
+                Filter::Deny([host].into())
+            } else {
+                Filter::Allow(HashSet::from_iter([host]))
+            };
+
+            Ok(Workflow::Purge {
+                user,
+                remotes,
+                host_filter,
+                older_than,
+                dry_run,
+            })
+        }
+
+        ("status", mut matches) => Ok(Workflow::Status {
+            user,
+            fetch_remotes: if matches.remove_one::<bool>("fetch").expect("has default") {
+                remotes
+            } else {
+                Vec::new()
+            },
+            host_filter: Filter::Deny([host].into()),
+            branch_filter: match matches.remove_many::<String>("branch") {
+                Some(branches) => Filter::Allow(branches.map(Branch::from).collect()),
+                None => Filter::All,
+            },
+        }),
+
+        ("stash", mut matches) => match matches
+            .remove_subcommand()
+            .expect("subcommand is mandatory")
+        {
+            (subcommand, mut matches) if subcommand == "apply" => Ok(Workflow::StashApply {
+                user,
+                host: Host::from(
+                    matches
+                        .remove_one::<String>("host")
+                        .expect("required argument"),
+                ),
+                index: matches.remove_one("index").expect("has default"),
+                dry_run,
+            }),
+            _ => unreachable!("unknown stash subcommand"),
+        },
+
+        ("worktree", mut matches) => match matches
+            .remove_subcommand()
+            .expect("subcommand is mandatory")
+        {
+            (subcommand, mut matches) if subcommand == "apply" => Ok(Workflow::WorktreeApply {
+                user,
+                host: Host::from(
+                    matches
+                        .remove_one::<String>("host")
+                        .expect("required argument"),
+                ),
+                branch: match matches.remove_one::<String>("branch") {
+                    Some(branch) => Branch::from(branch),
+                    None => git.current_branch(renderer)?,
+                },
+                dry_run,
+            }),
+            _ => unreachable!("unknown worktree subcommand"),
+        },
+
+        ("checkout", mut matches) => {
+            let mut host_and_branch = matches
+                .remove_many::<String>("host_and_branch")
+                .expect("required argument")
+                .collect::<Vec<_>>();
+            let branch = Branch::from(host_and_branch.pop().expect("at least one value"));
+            let host_filter = match host_and_branch.pop() {
+                Some(source_host) => Filter::Allow([Host::from(source_host)].into()),
+                None => Filter::Deny([host].into()),
+            };
+
+            Ok(Workflow::Checkout {
+                user,
+                fetch_remotes: if matches.remove_one::<bool>("fetch").expect("has default") {
+                    remotes
+                } else {
+                    Vec::new()
+                },
+                host_filter,
+                branch,
+                force: matches.remove_one::<bool>("force").expect("has default"),
+                dry_run,
+            })
+        }
+
+        _ => unreachable!("unknown subcommand"),
+    };
+}
+
+/// Describe a setting that was resolved from the CLI, environment, `git config`, or a default.
+///
+/// Git uses the last value it encounters for single valued settings like the host, so that is the
+/// only config file that matters.
+fn setting(
+    name: &'static str,
+    values: Vec<String>,
+    value_source: Option<ValueSource>,
+    env: &'static str,
+    config_entries: Vec<ConfigEntry>,
+    default: &'static str,
+) -> Setting {
+    let config_entries = if name == CONFIG_REMOTE {
+        config_entries
+    } else {
+        config_entries.into_iter().last().into_iter().collect()
+    };
+
+    let source = match value_source {
+        Some(ValueSource::CommandLine) => SettingSource::CommandLine,
+        Some(ValueSource::EnvVariable) => SettingSource::Env(env),
+        _ if !config_entries.is_empty() => {
+            SettingSource::GitConfig(config_origins(&config_entries))
+        }
+        _ => SettingSource::Default(default),
+    };
+
+    Setting {
+        name,
+        values,
+        source,
+    }
+}
+
+/// Describe a setting that can only be specified in `git config`.
+fn config_only_setting(
+    name: &'static str,
+    config_entries: Vec<ConfigEntry>,
+    default: &'static str,
+) -> Setting {
+    if config_entries.is_empty() {
+        return Setting {
+            name,
+            values: Vec::new(),
+            source: SettingSource::Default(default),
+        };
+    }
+
+    Setting {
+        name,
+        source: SettingSource::GitConfig(config_origins(&config_entries)),
+        values: config_entries
+            .into_iter()
+            .map(|entry| entry.value)
+            .collect(),
+    }
+}
+
+/// Every distinct config file that `config_entries` came from, in order.
+fn config_origins(config_entries: &[ConfigEntry]) -> Vec<String> {
+    let mut origins = Vec::<String>::new();
+    for entry in config_entries {
+        if !origins.contains(&entry.origin) {
+            origins.push(entry.origin.clone());
+        }
+    }
+    origins
+}
+
+/// The [`BranchPatterns`] from the CLI, falling back to `git config` for each of the include and
+/// exclude lists independently.
+fn specified_branch_patterns(
+    renderer: &mut impl Renderer,
+    matches: &mut ArgMatches,
+    git: &impl GitBackend,
+) -> anyhow::Result<BranchPatterns> {
+    let mut patterns =
+        |arg_name: &str, config_key: &str| match matches.remove_many::<String>(arg_name) {
+            Some(patterns) => Ok(patterns.collect::<Vec<_>>()),
+            None => git.get_config_all(renderer, config_key),
+        };
+
+    let include = patterns("include", CONFIG_INCLUDE)?;
+    let exclude = patterns("exclude", CONFIG_EXCLUDE)?;
+    BranchPatterns::new(include, exclude)
+}
+
+/// The [`SyncExtras`] from the CLI, where flags that can also be enabled in `git config` can only
+/// be turned on from the CLI, not off.
+fn specified_sync_extras(
+    renderer: &mut impl Renderer,
+    matches: &mut ArgMatches,
+    git: &impl GitBackend,
+) -> anyhow::Result<SyncExtras> {
+    let mut flag = |arg_name: &str| matches.remove_one::<bool>(arg_name).expect("has default");
+    let stash = flag("stash");
+    let worktree = flag("include_worktree");
+    let tags = flag("tags");
+
+    Ok(SyncExtras {
+        stash: stash || git.get_config_bool(renderer, CONFIG_SYNC_STASH)?,
+        worktree,
+        tags: tags || git.get_config_bool(renderer, CONFIG_SYNC_TAGS)?,
+    })
+}
+
+/// Extract the remotes to operate against in order of preference:
+///
+/// 1. Passed in as direct CLI options, possibly repeated
+/// 2. Specified as an environment variable
+/// 3. Specified in `git config`, possibly multiple times
+/// 4. The default remote
+fn resolve_remotes(
+    renderer: &mut impl Renderer,
+    matches: &mut ArgMatches,
+    git: &impl GitBackend,
+) -> anyhow::Result<Vec<Remote<'static>>> {
+    let source = matches.value_source("remote").expect("default value");
+    let remotes = matches
+        .remove_many::<String>("remote")
+        .expect("default value")
+        .map(Remote::from)
+        .collect::<Vec<_>>();
+
+    match source {
+        ValueSource::CommandLine | ValueSource::EnvVariable => Ok(remotes),
+        _ => {
+            let configured = git.get_config_all(renderer, CONFIG_REMOTE)?;
+            if configured.is_empty() {
+                Ok(remotes)
+            } else {
+                Ok(configured.into_iter().map(Remote::from).collect())
+            }
+        }
+    }
+}
+
+/// Extract user arguments in order of preference:
+///
+/// 1. Passed in as direct CLI options
+/// 2. Specified as an environment variable
+/// 3. Specified in `git config`
+/// 4. A default from querying the operating system
+fn resolve<T: Clone + From<String>>(
+    matches: &mut ArgMatches,
+    arg_name: &str,
+    from_git_config: impl FnOnce() -> anyhow::Result<Option<T>>,
+) -> anyhow::Result<T> {
+    match (
+        matches.value_source(arg_name).expect("default value"),
+        matches
+            .remove_one::<String>(arg_name)
+            .expect("default value"),
+    ) {
+        (ValueSource::CommandLine | ValueSource::EnvVariable, value) => Ok(T::from(value)),
+        (_, value) => match from_git_config()? {
+            Some(git_value) => Ok(git_value),
+            None => Ok(T::from(value)),
+        },
+    }
+}
+
+/// CLI invocation tests
+#[cfg(test)]
+mod test_cli {
+    use std::{
+        borrow::Cow, collections::HashSet, io, io::Write, iter::FromIterator, path::Path,
+        process::Command, time::Duration,
+    };
+
+    use clap::{error::ErrorKind, ArgMatches};
+    use tempfile::{tempdir, TempDir};
+
+    use git_nomad::{
+        error::{exit_code, NomadError},
+        git_binary::{ConfigScope, GitBinary},
+        ls_format::LsFormat,
+        renderer::Renderer,
+        types::{Branch, Host, Remote, User},
+        verbosity::Verbosity,
+        workflow::{
+            BranchPatterns, Filter, InitSetting, LsPrinter, Setting, SettingSource, SyncExtras,
+            Workflow,
+        },
+        CONFIG_CLONE_ID, CONFIG_EXCLUDE, CONFIG_HOST, CONFIG_INCLUDE, CONFIG_REMOTE,
+        CONFIG_SYNC_STASH, CONFIG_SYNC_TAGS, CONFIG_USER,
+    };
+
+    use crate::cli::{
+        cli, nomad, specified_git, specified_verbosity, specified_workflow, DEFAULT_REMOTE,
+    };
+
+    const INITIAL_BRANCH: &str = "master";
+
+    /// Discards all output, since these tests only look at the resulting [`Workflow`].
+    struct NoRenderer;
+
+    impl Renderer for NoRenderer {
+        fn writer<T>(
+            &mut self,
+            func: impl FnOnce(&mut dyn Write) -> anyhow::Result<T>,
+        ) -> anyhow::Result<T> {
+            func(&mut io::sink())
+        }
+
+        fn are_spinners_visible(&self) -> bool {
+            false
+        }
+
+        fn spinner<T>(
+            &mut self,
+            _description: impl Into<Cow<'static, str>>,
+            func: impl FnOnce() -> anyhow::Result<T>,
+        ) -> anyhow::Result<T> {
+            func()
+        }
+
+        fn prompt(&mut self, _question: &str, default: &str) -> anyhow::Result<String> {
+            Ok(default.to_owned())
+        }
+    }
+
+    /// A throwaway repository with a single commit on [`INITIAL_BRANCH`], for arguments that
+    /// default to something from git.
+    struct GitRemote {
+        root_dir: TempDir,
+        git: GitBinary<'static>,
+    }
+
+    impl GitRemote {
+        fn init(verbosity: Option<Verbosity>) -> Self {
+            let root_dir = tempdir().unwrap();
+            let git = |args: &[&str]| {
+                let output = Command::new("git")
+                    .current_dir(root_dir.path())
+                    .args([
+                        "-c",
+                        "user.name=git-nomad",
+                        "-c",
+                        "user.email=git-nomad@invalid",
+                    ])
+                    .args(args)
+                    .output()
+                    .unwrap();
+                assert!(output.status.success(), "git {:?} failed", args);
+            };
+            git(&["init", "--initial-branch", INITIAL_BRANCH]);
+            git(&["commit", "--allow-empty", "-m", "commit0"]);
+
+            let git = GitBinary::new(
+                &mut NoRenderer,
+                verbosity,
+                Cow::from("git"),
+                root_dir.path(),
+            )
+            .unwrap();
+            Self { root_dir, git }
+        }
+
+        fn working_directory(&self) -> &Path {
+            self.root_dir.path()
+        }
+    }
+
+    struct CliTest {
+        default_user: User<'static>,
+        default_host: Host<'static>,
+    }
+
+    impl CliTest {
+        fn default_host_filter(&self) -> Filter<Host<'_>> {
+            Filter::Deny([self.default_host.always_borrow()].into())
+        }
+
+        fn matches(&self, args: &[&str]) -> clap::error::Result<ArgMatches> {
+            let mut vec = vec!["git-nomad"];
+            vec.extend_from_slice(args);
+            cli(
+                Some(self.default_user.clone()),
+                Some(self.default_host.clone()),
+                &vec,
+            )
+        }
+
+        fn remote(&self, args: &[&str]) -> CliTestRemote {
+            CliTestRemote {
+                matches: self.matches(args).unwrap(),
+                remote: GitRemote::init(Some(Verbosity::max())),
+            }
+        }
+    }
+
+    struct CliTestRemote {
+        matches: ArgMatches,
+        remote: GitRemote,
+    }
+
+    impl CliTestRemote {
+        fn set_config(&mut self, key: &str, value: &str) -> &mut Self {
+            self.remote
+                .git
+                .set_config(&mut NoRenderer, ConfigScope::Local, key, value)
+                .unwrap();
+            self
+        }
+
+        fn add_config(&mut self, key: &str, value: &str) -> &mut Self {
+            self.remote
+                .git
+                .add_config(&mut NoRenderer, ConfigScope::Local, key, value)
+                .unwrap();
+            self
+        }
+
+        fn workflow(&mut self) -> Workflow<'_> {
+            specified_workflow(&mut NoRenderer, &mut self.matches, &self.remote.git).unwrap()
+        }
+    }
+
+    impl Default for CliTest {
+        fn default() -> Self {
+            Self {
+                default_user: User::from("default_user"),
+                default_host: Host::from("default_host"),
+            }
+        }
+    }
+
+    /// Should print help and stop processing if no subcommand is specified.
+    #[test]
+    fn subcommand_is_required() {
+        let cli_test = CliTest::default();
+        let matches = cli_test.matches(&[]);
+        assert!(matches.is_err());
+        assert_eq!(
+            match matches {
+                Ok(_) => unreachable!(),
+                Err(e) => e.kind(),
+            },
+            ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand,
+        );
+    }
+
+    /// `--git` before/after the subcommand.
+    #[test]
+    fn git_option() {
+        for args in &[&["--git", "foo", "ls"], &["ls", "--git", "foo"]] {
+            println!("{:?}", args);
+            let cli_test = CliTest::default();
+            let mut matches = cli_test.matches(*args).unwrap();
+            assert_eq!(specified_git(&mut matches), "foo");
+        }
+    }
+
+    #[test]
+    fn quiet_verbosity() {
+        for args in &[
+            &["--quiet", "ls"],
+            &["-q", "ls"],
+            &["ls", "--quiet"],
+            &["ls", "-q"],
+        ] {
+            println!("{:?}", args);
+            let cli_test = CliTest::default();
+            let mut matches = cli_test.matches(*args).unwrap();
+            assert_eq!(specified_verbosity(&mut matches), None);
+        }
+    }
+
+    #[test]
+    fn default_verbosity() {
+        let cli_test = CliTest::default();
+        let mut matches = cli_test.matches(&["ls"]).unwrap();
+        assert_eq!(
+            specified_verbosity(&mut matches),
+            Some(Verbosity::default())
+        );
+    }
+
+    #[test]
+    fn verbose_verbosity() {
+        for args in &[
+            &["--verbose", "ls"],
+            &["-v", "ls"],
+            &["ls", "--verbose"],
+            &["ls", "-v"],
+        ] {
+            println!("{:?}", args);
+            let cli_test = CliTest::default();
+            let mut matches = cli_test.matches(*args).unwrap();
+            assert_eq!(
+                specified_verbosity(&mut matches),
+                Some(Verbosity::verbose())
+            );
+        }
+    }
+
+    #[test]
+    fn max_verbosity() {
+        for args in &[
+            &["--verbose", "--verbose", "ls"] as &[&str],
+            &["ls", "-vv"],
+            &["ls", "-v", "--verbose"],
+            &["ls", "-vv", "-vv"],
+        ] {
+            println!("{:?}", args);
+            let cli_test = CliTest::default();
+            let mut matches = cli_test.matches(args).unwrap();
+            assert_eq!(specified_verbosity(&mut matches), Some(Verbosity::max()));
+        }
+    }
+
+    #[test]
+    fn ls() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["ls"]).workflow(),
+            Workflow::Ls {
+                printer: LsPrinter::Grouped,
+                user: cli_test.default_user.always_borrow(),
+                fetch_remotes: Vec::new(),
+                host_filter: cli_test.default_host_filter(),
+                branch_filter: Filter::All,
+            },
+        );
+    }
+
+    #[test]
+    fn ls_fetch_remote_default() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["ls", "--fetch"]).workflow(),
+            Workflow::Ls {
+                printer: LsPrinter::Grouped,
+                user: cli_test.default_user.always_borrow(),
+                fetch_remotes: vec![DEFAULT_REMOTE],
+                host_filter: cli_test.default_host_filter(),
+                branch_filter: Filter::All,
+            },
+        );
+    }
+
+    #[test]
+    fn ls_fetch_remote_global() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["--remote", "foo", "ls", "--fetch"])
+                .workflow(),
+            Workflow::Ls {
+                printer: LsPrinter::Grouped,
+                user: cli_test.default_user.always_borrow(),
+                fetch_remotes: vec![Remote::from("foo")],
+                host_filter: cli_test.default_host_filter(),
+                branch_filter: Filter::All,
+            },
+        );
+    }
+
+    #[test]
+    fn ls_fetch_remote_local() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["ls", "--fetch", "--remote", "foo"])
+                .workflow(),
+            Workflow::Ls {
+                printer: LsPrinter::Grouped,
+                user: cli_test.default_user.always_borrow(),
+                fetch_remotes: vec![Remote::from("foo")],
+                host_filter: cli_test.default_host_filter(),
+                branch_filter: Filter::All,
+            },
+        );
+    }
+
+    #[test]
+    fn ls_print_grouped() {
+        for args in &[
+            &["ls", "--print", "grouped"] as &[&str],
+            &["ls", "--print=grouped"],
+        ] {
+            println!("{:?}", args);
+
+            let cli_test = CliTest::default();
+            assert_eq!(
+                cli_test.remote(args).workflow(),
+                Workflow::Ls {
+                    printer: LsPrinter::Grouped,
+                    user: cli_test.default_user.always_borrow(),
+                    fetch_remotes: Vec::new(),
+                    host_filter: cli_test.default_host_filter(),
+                    branch_filter: Filter::All,
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn ls_long() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["ls", "--long"]).workflow(),
+            Workflow::Ls {
+                printer: LsPrinter::Long,
+                user: cli_test.default_user.always_borrow(),
+                fetch_remotes: Vec::new(),
+                host_filter: cli_test.default_host_filter(),
+                branch_filter: Filter::All,
+            }
+        );
+        assert!(cli_test
+            .matches(&["ls", "--long", "--print", "ref"])
+            .is_err());
+    }
+
+    #[test]
+    fn ls_format() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["ls", "--format", "%(host) %(branch)"])
+                .workflow(),
+            Workflow::Ls {
+                printer: LsPrinter::Format(LsFormat::parse("%(host) %(branch)").unwrap()),
+                user: cli_test.default_user.always_borrow(),
+                fetch_remotes: Vec::new(),
+                host_filter: cli_test.default_host_filter(),
+                branch_filter: Filter::All,
+            }
+        );
+        assert!(cli_test.matches(&["ls", "--format", "%(nope)"]).is_err());
+        assert!(cli_test
+            .matches(&["ls", "--format", "%(host)", "--long"])
+            .is_err());
+    }
+
+    #[test]
+    fn ls_print_ref() {
+        for args in &[&["ls", "--print", "ref"] as &[&str], &["ls", "--print=ref"]] {
+            println!("{:?}", args);
+
+            let cli_test = CliTest::default();
+            assert_eq!(
+                cli_test.remote(args).workflow(),
+                Workflow::Ls {
+                    printer: LsPrinter::Ref,
+                    user: cli_test.default_user.always_borrow(),
+                    fetch_remotes: Vec::new(),
+                    host_filter: cli_test.default_host_filter(),
+                    branch_filter: Filter::All,
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn ls_print_commit() {
+        for args in &[
+            &["ls", "--print", "commit"] as &[&str],
+            &["ls", "--print=commit"],
+        ] {
+            println!("{:?}", args);
+
+            let cli_test = CliTest::default();
+            assert_eq!(
+                cli_test.remote(args).workflow(),
+                Workflow::Ls {
+                    printer: LsPrinter::Commit,
+                    user: cli_test.default_user.always_borrow(),
+                    fetch_remotes: Vec::new(),
+                    host_filter: cli_test.default_host_filter(),
+                    branch_filter: Filter::All,
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn ls_print_json() {
+        for (args, printer) in [
+            (&["ls", "--print", "json"] as &[&str], LsPrinter::Json),
+            (&["ls", "--print=jsonl"], LsPrinter::JsonLines),
+        ] {
+            println!("{:?}", args);
+
+            let cli_test = CliTest::default();
+            assert_eq!(
+                cli_test.remote(args).workflow(),
+                Workflow::Ls {
+                    printer,
+                    user: cli_test.default_user.always_borrow(),
+                    fetch_remotes: Vec::new(),
+                    host_filter: cli_test.default_host_filter(),
+                    branch_filter: Filter::All,
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn ls_explicit() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["ls", "-U", "explicit_user"]).workflow(),
+            Workflow::Ls {
+                printer: LsPrinter::Grouped,
+                user: User::from("explicit_user"),
+                fetch_remotes: Vec::new(),
+                host_filter: cli_test.default_host_filter(),
+                branch_filter: Filter::All,
+            },
+        );
+    }
+
+    #[test]
+    fn ls_config_beats_default() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["ls"])
+                .set_config(CONFIG_USER, "config_user")
+                .workflow(),
+            Workflow::Ls {
+                printer: LsPrinter::Grouped,
+                user: User::from("config_user"),
+                fetch_remotes: Vec::new(),
+                host_filter: cli_test.default_host_filter(),
+                branch_filter: Filter::All,
+            },
+        );
+    }
+
+    #[test]
+    fn ls_head() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["ls", "--head"]).workflow(),
+            Workflow::Ls {
+                printer: LsPrinter::Grouped,
+                user: cli_test.default_user.always_borrow(),
+                fetch_remotes: Vec::new(),
+                host_filter: cli_test.default_host_filter(),
+                branch_filter: Filter::Allow(["master"].map(Branch::from).into()),
+            },
+        );
+    }
+
+    #[test]
+    fn ls_branches() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["ls", "-b", "foo", "--branch", "bar", "--branch=baz"])
+                .workflow(),
+            Workflow::Ls {
+                printer: LsPrinter::Grouped,
+                user: cli_test.default_user.always_borrow(),
+                fetch_remotes: Vec::new(),
+                host_filter: cli_test.default_host_filter(),
+                branch_filter: Filter::Allow(["foo", "bar", "baz"].map(Branch::from).into()),
+            },
+        );
+    }
+
+    #[test]
+    fn ls_print_self() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["ls", "--print-self"]).workflow(),
+            Workflow::Ls {
+                printer: LsPrinter::Grouped,
+                user: cli_test.default_user.always_borrow(),
+                fetch_remotes: Vec::new(),
+                host_filter: Filter::All,
+                branch_filter: Filter::All,
+            },
+        );
+    }
+
+    /// Invoke `sync` with explicit `user` and `host`
+    #[test]
+    fn sync_explicit() {
+        for args in &[
+            &[
+                "--user", "user0", "sync", "--host", "host0", "--remote", "remote",
+            ] as &[&str],
+            &["sync", "-U", "user0", "-H", "host0", "-R", "remote"],
+        ] {
+            println!("{:?}", args);
+            let cli_test = CliTest::default();
+            assert_eq!(
+                cli_test.remote(args).workflow(),
+                Workflow::Sync {
+                    user: User::from("user0"),
+                    host: Host::from("host0"),
+                    remotes: vec![Remote::from("remote")],
+                    branch_patterns: BranchPatterns::default(),
+                    extras: SyncExtras::default(),
+                    dry_run: false,
+                },
+            );
+        }
+    }
+
+    /// Invoke `sync` with `user` and `host` coming from `git config`.
+    #[test]
+    fn sync_config() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["sync"])
+                .set_config(CONFIG_USER, "user0")
+                .set_config(CONFIG_HOST, "host0")
+                .workflow(),
+            Workflow::Sync {
+                user: User::from("user0"),
+                host: Host::from("host0"),
+                remotes: vec![DEFAULT_REMOTE.clone()],
+                branch_patterns: BranchPatterns::default(),
+                extras: SyncExtras::default(),
+                dry_run: false,
+            }
+        );
+    }
+
+    /// Invoke `sync` with defaults.
+    #[test]
+    fn sync_default() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["sync"]).workflow(),
+            Workflow::Sync {
+                user: cli_test.default_user.always_borrow(),
+                host: cli_test.default_host.always_borrow(),
+                remotes: vec![DEFAULT_REMOTE.clone()],
+                branch_patterns: BranchPatterns::default(),
+                extras: SyncExtras::default(),
+                dry_run: false,
+            }
+        );
+    }
+
+    /// `purge --older-than` applies to every other host.
+    #[test]
+    fn purge_older_than() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["purge", "--older-than", "30d"])
+                .workflow(),
+            Workflow::Purge {
+                user: cli_test.default_user.always_borrow(),
+                remotes: vec![DEFAULT_REMOTE.clone()],
+                host_filter: cli_test.default_host_filter(),
+                older_than: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+                dry_run: false,
+            }
+        );
+    }
+
+    /// `purge --older-than --all` can expire the current host too.
+    #[test]
+    fn purge_older_than_all() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["purge", "--all", "--older-than", "30d"])
+                .workflow(),
+            Workflow::Purge {
+                user: cli_test.default_user.always_borrow(),
+                remotes: vec![DEFAULT_REMOTE.clone()],
+                host_filter: Filter::All,
+                older_than: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+                dry_run: false,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_age() {
+        for (value, seconds) in [
+            ("90s", 90),
+            ("5m", 300),
+            ("2h", 7200),
+            ("1d", 86400),
+            ("2w", 1209600),
+        ] {
+            assert_eq!(super::parse_age(value), Ok(Duration::from_secs(seconds)));
+        }
+
+        for value in ["", "30", "d", "30x", "-1d", "1.5d", "99999999999999999999d"] {
+            assert!(super::parse_age(value).is_err(), "{:?}", value);
+        }
+    }
+
+    /// `--remote` can be repeated to sync with several remotes.
+    #[test]
+    fn sync_multiple_remotes() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["sync", "-R", "origin", "--remote", "mirror"])
+                .add_config(CONFIG_REMOTE, "ignored")
+                .workflow(),
+            Workflow::Sync {
+                user: cli_test.default_user.always_borrow(),
+                host: cli_test.default_host.always_borrow(),
+                remotes: vec![Remote::from("origin"), Remote::from("mirror")],
+                branch_patterns: BranchPatterns::default(),
+                extras: SyncExtras::default(),
+                dry_run: false,
+            }
+        );
+    }
+
+    /// Remotes can come from `git config` when not specified on the command line.
+    #[test]
+    fn sync_remotes_config() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["sync"])
+                .add_config(CONFIG_REMOTE, "origin")
+                .add_config(CONFIG_REMOTE, "mirror")
+                .workflow(),
+            Workflow::Sync {
+                user: cli_test.default_user.always_borrow(),
+                host: cli_test.default_host.always_borrow(),
+                remotes: vec![Remote::from("origin"), Remote::from("mirror")],
+                branch_patterns: BranchPatterns::default(),
+                extras: SyncExtras::default(),
+                dry_run: false,
+            }
+        );
+    }
+
+    /// Invoke `sync` with branch patterns coming from `git config`.
+    #[test]
+    fn sync_branch_patterns_config() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["sync"])
+                .add_config(CONFIG_INCLUDE, "feature/*")
+                .add_config(CONFIG_INCLUDE, "master")
+                .add_config(CONFIG_EXCLUDE, "feature/huge")
+                .workflow(),
+            Workflow::Sync {
+                user: cli_test.default_user.always_borrow(),
+                host: cli_test.default_host.always_borrow(),
+                remotes: vec![DEFAULT_REMOTE.clone()],
+                branch_patterns: BranchPatterns::new(["feature/*", "master"], ["feature/huge"])
+                    .unwrap(),
+                extras: SyncExtras::default(),
+                dry_run: false,
+            }
+        );
+    }
+
+    /// Branch patterns on the command line replace the ones from `git config`.
+    #[test]
+    fn sync_branch_patterns_override_config() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["sync", "--exclude", "wip/*", "--exclude", "scratch"])
+                .add_config(CONFIG_INCLUDE, "feature/*")
+                .add_config(CONFIG_EXCLUDE, "feature/huge")
+                .workflow(),
+            Workflow::Sync {
+                user: cli_test.default_user.always_borrow(),
+                host: cli_test.default_host.always_borrow(),
+                remotes: vec![DEFAULT_REMOTE.clone()],
+                branch_patterns: BranchPatterns::new(["feature/*"], ["wip/*", "scratch"]).unwrap(),
+                extras: SyncExtras::default(),
+                dry_run: false,
+            }
+        );
+    }
+
+    /// Invalid branch patterns should be reported rather than silently ignored.
+    #[test]
+    fn sync_invalid_branch_pattern() {
+        let cli_test = CliTest::default();
+        let mut cli_test_remote = cli_test.remote(&["sync", "--include", "feature/[*"]);
+        assert!(specified_workflow(
+            &mut NoRenderer,
+            &mut cli_test_remote.matches,
+            &cli_test_remote.remote.git,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn status() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["status"]).workflow(),
+            Workflow::Status {
+                user: cli_test.default_user.always_borrow(),
+                fetch_remotes: Vec::new(),
+                host_filter: cli_test.default_host_filter(),
+                branch_filter: Filter::All,
+            }
+        );
+    }
+
+    #[test]
+    fn status_fetch_branches() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["status", "--fetch", "-b", "foo", "--branch=bar"])
+                .workflow(),
+            Workflow::Status {
+                user: cli_test.default_user.always_borrow(),
+                fetch_remotes: vec![DEFAULT_REMOTE],
+                host_filter: cli_test.default_host_filter(),
+                branch_filter: Filter::Allow(["foo", "bar"].map(Branch::from).into()),
+            }
+        );
+    }
+
+    /// Stash entries are synced with `--stash` or `nomad.syncStash`.
+    #[test]
+    fn sync_stash() {
+        let cli_test = CliTest::default();
+        let expected = Workflow::Sync {
+            user: cli_test.default_user.always_borrow(),
+            host: cli_test.default_host.always_borrow(),
+            remotes: vec![DEFAULT_REMOTE.clone()],
+            branch_patterns: BranchPatterns::default(),
+            extras: SyncExtras {
+                stash: true,
+                ..SyncExtras::default()
+            },
+            dry_run: false,
+        };
+
+        assert_eq!(cli_test.remote(&["sync", "--stash"]).workflow(), expected);
+        assert_eq!(
+            cli_test
+                .remote(&["sync"])
+                .set_config(CONFIG_SYNC_STASH, "yes")
+                .workflow(),
+            expected,
+        );
+    }
+
+    /// Tags are synced with `--tags` or `nomad.syncTags`.
+    #[test]
+    fn sync_tags() {
+        let cli_test = CliTest::default();
+        let expected = Workflow::Sync {
+            user: cli_test.default_user.always_borrow(),
+            host: cli_test.default_host.always_borrow(),
+            remotes: vec![DEFAULT_REMOTE.clone()],
+            branch_patterns: BranchPatterns::default(),
+            extras: SyncExtras {
+                tags: true,
+                ..SyncExtras::default()
+            },
+            dry_run: false,
+        };
+
+        assert_eq!(cli_test.remote(&["sync", "--tags"]).workflow(), expected);
+        assert_eq!(
+            cli_test
+                .remote(&["sync"])
+                .set_config(CONFIG_SYNC_TAGS, "true")
+                .workflow(),
+            expected,
+        );
+    }
+
+    #[test]
+    fn sync_include_worktree() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["sync", "--include-worktree"]).workflow(),
+            Workflow::Sync {
+                user: cli_test.default_user.always_borrow(),
+                host: cli_test.default_host.always_borrow(),
+                remotes: vec![DEFAULT_REMOTE.clone()],
+                branch_patterns: BranchPatterns::default(),
+                extras: SyncExtras {
+                    worktree: true,
+                    ..SyncExtras::default()
+                },
+                dry_run: false,
+            },
+        );
+    }
+
+    /// The branch defaults to the current one.
+    #[test]
+    fn worktree_apply() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["worktree", "apply", "laptop"]).workflow(),
+            Workflow::WorktreeApply {
+                user: cli_test.default_user.always_borrow(),
+                host: Host::from("laptop"),
+                branch: Branch::from(INITIAL_BRANCH),
+                dry_run: false,
+            },
+        );
+        assert_eq!(
+            cli_test
+                .remote(&["worktree", "apply", "laptop", "feature"])
+                .workflow(),
+            Workflow::WorktreeApply {
+                user: cli_test.default_user.always_borrow(),
+                host: Host::from("laptop"),
+                branch: Branch::from("feature"),
+                dry_run: false,
+            },
+        );
+    }
+
+    #[test]
+    fn stash_apply() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["stash", "apply", "laptop"]).workflow(),
+            Workflow::StashApply {
+                user: cli_test.default_user.always_borrow(),
+                host: Host::from("laptop"),
+                index: 0,
+                dry_run: false,
+            },
+        );
+        assert_eq!(
+            cli_test
+                .remote(&["stash", "apply", "laptop", "2"])
+                .workflow(),
+            Workflow::StashApply {
+                user: cli_test.default_user.always_borrow(),
+                host: Host::from("laptop"),
+                index: 2,
+                dry_run: false,
+            },
+        );
+        assert!(cli_test.matches(&["stash", "apply"]).is_err());
+        assert!(cli_test
+            .matches(&["stash", "apply", "laptop", "-1"])
+            .is_err());
+    }
+
+    #[test]
+    fn checkout_branch_only() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["checkout", "feature"]).workflow(),
+            Workflow::Checkout {
+                user: cli_test.default_user.always_borrow(),
+                fetch_remotes: Vec::new(),
+                host_filter: cli_test.default_host_filter(),
+                branch: Branch::from("feature"),
+                force: false,
+                dry_run: false,
+            }
+        );
+    }
+
+    #[test]
+    fn checkout_host_and_branch() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["checkout", "--fetch", "--force", "desktop", "feature"])
+                .workflow(),
+            Workflow::Checkout {
+                user: cli_test.default_user.always_borrow(),
+                fetch_remotes: vec![DEFAULT_REMOTE],
+                host_filter: Filter::Allow([Host::from("desktop")].into()),
+                branch: Branch::from("feature"),
+                force: true,
+                dry_run: false,
+            }
+        );
+    }
+
+    /// `checkout` requires at least a branch and at most a host and a branch.
+    #[test]
+    fn checkout_arity() {
+        let cli_test = CliTest::default();
+        assert!(cli_test.matches(&["checkout"]).is_err());
+        assert!(cli_test.matches(&["checkout", "a", "b", "c"]).is_err());
+    }
+
+    #[test]
+    fn push() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&[
+                    "push",
+                    "-b",
+                    "feature",
+                    "--branch",
+                    "other",
+                    "--exclude",
+                    "wip/*"
+                ])
+                .workflow(),
+            Workflow::Push {
+                user: cli_test.default_user.always_borrow(),
+                host: cli_test.default_host.always_borrow(),
+                remotes: vec![DEFAULT_REMOTE.clone()],
+                branch_patterns: BranchPatterns::new([] as [&str; 0], ["wip/*"]).unwrap(),
+                branches: vec![Branch::from("feature"), Branch::from("other")],
+                dry_run: false,
+            }
+        );
+    }
+
+    #[test]
+    fn push_dry_run() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["--dry-run", "push", "-b", "feature"])
+                .workflow(),
+            Workflow::Push {
+                user: cli_test.default_user.always_borrow(),
+                host: cli_test.default_host.always_borrow(),
+                remotes: vec![DEFAULT_REMOTE.clone()],
+                branch_patterns: BranchPatterns::default(),
+                branches: vec![Branch::from("feature")],
+                dry_run: true,
+            }
+        );
+    }
+
+    /// `push` is pointless without branches.
+    #[test]
+    fn push_requires_branch() {
+        let cli_test = CliTest::default();
+        assert!(cli_test.matches(&["push"]).is_err());
+    }
+
+    #[test]
+    fn init() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["init"]).workflow(),
+            Workflow::Init {
+                user: InitSetting {
+                    value: cli_test.default_user.always_borrow(),
+                    ask: true,
+                },
+                host: InitSetting {
+                    value: cli_test.default_host.always_borrow(),
+                    ask: true,
+                },
+                remotes: InitSetting {
+                    value: vec![DEFAULT_REMOTE.clone()],
+                    ask: true,
+                },
+                scope: ConfigScope::Local,
+                dry_run: false,
+            },
+        );
+    }
+
+    /// Values passed explicitly should be saved without asking.
+    #[test]
+    fn init_explicit() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["init", "--host", "explicit_host", "--global"])
+                .workflow(),
+            Workflow::Init {
+                user: InitSetting {
+                    value: cli_test.default_user.always_borrow(),
+                    ask: true,
+                },
+                host: InitSetting {
+                    value: Host::from("explicit_host"),
+                    ask: false,
+                },
+                remotes: InitSetting {
+                    value: vec![DEFAULT_REMOTE.clone()],
+                    ask: true,
+                },
+                scope: ConfigScope::Global,
+                dry_run: false,
+            },
+        );
+
+        let mut cli_remote = cli_test.remote(&["init", "-y"]);
+        cli_remote.set_config(CONFIG_USER, "config_user");
+        assert_eq!(
+            cli_remote.workflow(),
+            Workflow::Init {
+                user: InitSetting {
+                    value: User::from("config_user"),
+                    ask: false,
+                },
+                host: InitSetting {
+                    value: cli_test.default_host.always_borrow(),
+                    ask: false,
+                },
+                remotes: InitSetting {
+                    value: vec![DEFAULT_REMOTE.clone()],
+                    ask: false,
+                },
+                scope: ConfigScope::Local,
+                dry_run: false,
+            },
+        );
+    }
+
+    /// Each setting should report where its value came from.
+    #[test]
+    fn config_show() {
+        let cli_test = CliTest::default();
+        let mut cli_remote = cli_test.remote(&["--user", "explicit_user", "config", "show"]);
+        cli_remote.set_config(CONFIG_HOST, "config_host");
+        cli_remote.add_config(CONFIG_EXCLUDE, "wip/*");
+        let origin = cli_remote
+            .remote
+            .git
+            .get_config_entries(&mut NoRenderer, Some(ConfigScope::Local), CONFIG_HOST)
+            .unwrap()
+            .remove(0)
+            .origin;
+
+        assert_eq!(
+            cli_remote.workflow(),
+            Workflow::ConfigShow {
+                settings: vec![
+                    Setting {
+                        name: CONFIG_USER,
+                        values: vec!["explicit_user".to_string()],
+                        source: SettingSource::CommandLine,
+                    },
+                    Setting {
+                        name: CONFIG_HOST,
+                        values: vec!["config_host".to_string()],
+                        source: SettingSource::GitConfig(vec![origin.clone()]),
+                    },
+                    Setting {
+                        name: CONFIG_REMOTE,
+                        values: vec![DEFAULT_REMOTE.0.to_string()],
+                        source: SettingSource::Default("default"),
+                    },
+                    Setting {
+                        name: CONFIG_INCLUDE,
+                        values: vec![],
+                        source: SettingSource::Default("default"),
+                    },
+                    Setting {
+                        name: CONFIG_EXCLUDE,
+                        values: vec!["wip/*".to_string()],
+                        source: SettingSource::GitConfig(vec![origin]),
+                    },
+                    Setting {
+                        name: CONFIG_SYNC_STASH,
+                        values: vec![],
+                        source: SettingSource::Default("default"),
+                    },
+                    Setting {
+                        name: CONFIG_SYNC_TAGS,
+                        values: vec![],
+                        source: SettingSource::Default("default"),
+                    },
+                    Setting {
+                        name: CONFIG_CLONE_ID,
+                        values: vec![],
+                        source: SettingSource::Default("generated on first sync"),
+                    },
+                ],
+            },
+        );
+    }
+
+    /// Keys can be given with or without the `nomad.` prefix.
+    #[test]
+    fn config_get_set_unset() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["config", "get", "nomad.host"]).workflow(),
+            Workflow::ConfigGet {
+                key: "host".to_string(),
+            },
+        );
+        assert_eq!(
+            cli_test
+                .remote(&["config", "set", "remote", "origin", "mirror"])
+                .workflow(),
+            Workflow::ConfigSet {
+                key: "remote".to_string(),
+                values: vec!["origin".to_string(), "mirror".to_string()],
+                scope: ConfigScope::Local,
+                dry_run: false,
+            },
+        );
+        assert_eq!(
+            cli_test
+                .remote(&["--dry-run", "config", "unset", "--global", "nomad.host"])
+                .workflow(),
+            Workflow::ConfigUnset {
+                key: "host".to_string(),
+                scope: ConfigScope::Global,
+                dry_run: true,
+            },
+        );
+    }
+
+    /// `set` needs at least one value.
+    #[test]
+    fn config_set_requires_value() {
+        let cli_test = CliTest::default();
+        assert!(cli_test.matches(&["config", "set", "host"]).is_err());
+        assert!(cli_test.matches(&["config", "get", "nomad."]).is_err());
+    }
+
+    #[test]
+    fn hooks() {
+        let cli_test = CliTest::default();
+        assert!(cli_test.matches(&["hooks"]).is_err());
+        assert_eq!(
+            cli_test.remote(&["hooks", "install"]).workflow(),
+            Workflow::InstallHooks { dry_run: false },
+        );
+        assert_eq!(
+            cli_test
+                .remote(&["hooks", "uninstall", "--dry-run"])
+                .workflow(),
+            Workflow::UninstallHooks { dry_run: true },
+        );
+    }
+
+    #[test]
+    fn sync_dry_run() {
+        for args in &[&["--dry-run", "sync"] as &[&str], &["sync", "-n"]] {
+            println!("{:?}", args);
+            let cli_test = CliTest::default();
+            assert_eq!(
+                cli_test.remote(args).workflow(),
+                Workflow::Sync {
+                    user: cli_test.default_user.always_borrow(),
+                    host: cli_test.default_host.always_borrow(),
+                    remotes: vec![DEFAULT_REMOTE.clone()],
+                    branch_patterns: BranchPatterns::default(),
+                    extras: SyncExtras::default(),
+                    dry_run: true,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn purge_dry_run() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["purge", "--all", "--dry-run"]).workflow(),
+            Workflow::Purge {
+                user: cli_test.default_user.always_borrow(),
+                remotes: vec![DEFAULT_REMOTE.clone()],
+                host_filter: Filter::All,
+                older_than: None,
+                dry_run: true,
+            }
+        );
+    }
+
+    #[test]
+    fn purge_all() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test.remote(&["purge", "--all"]).workflow(),
+            Workflow::Purge {
+                user: cli_test.default_user.always_borrow(),
+                remotes: vec![DEFAULT_REMOTE.clone()],
+                host_filter: Filter::All,
+                older_than: None,
+                dry_run: false,
+            }
+        );
+    }
+
+    #[test]
+    fn purge_hosts() {
+        let cli_test = CliTest::default();
+        assert_eq!(
+            cli_test
+                .remote(&["--host=host0", "purge", "-R", "remote"])
+                .workflow(),
+            Workflow::Purge {
+                user: cli_test.default_user.always_borrow(),
+                remotes: vec![Remote::from("remote")],
+                host_filter: Filter::Allow(HashSet::from_iter(["host0"].map(Host::from))),
+                older_than: None,
+                dry_run: false,
+            }
+        );
+    }
+
+    /// Invoking all the real logic in `nomad` should not panic.
+    #[test]
+    fn nomad_ls() {
+        let origin = GitRemote::init(None);
+        nomad(
+            &mut NoRenderer,
+            ["git-nomad", "ls"],
+            origin.working_directory(),
+        )
+        .unwrap();
+    }
+
+    /// Failures outside of a git repository should be distinguishable by exit code.
+    #[test]
+    fn nomad_not_a_repository() {
+        let dir = tempdir().unwrap();
+        let error = nomad(&mut NoRenderer, ["git-nomad", "ls"], dir.path()).unwrap_err();
+        assert_eq!(exit_code(&error), NomadError::NotARepository.exit_code());
+    }
+
+    /// Failures to reach the remote should be distinguishable by exit code.
+    #[test]
+    fn nomad_remote_unreachable() {
+        let origin = GitRemote::init(None);
+        let error = nomad(
+            &mut NoRenderer,
+            ["git-nomad", "sync", "--remote", "nowhere"],
+            origin.working_directory(),
+        )
+        .unwrap_err();
+        assert_eq!(exit_code(&error), NomadError::RemoteUnreachable.exit_code());
+    }
+}
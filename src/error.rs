@@ -0,0 +1,139 @@
+//! See [`NomadError`] for the primary entry point.
+
+use std::{error::Error, fmt};
+
+use crate::git_ref::GitRefParseError;
+
+/// Failures that scripts may want to tell apart, each with a documented exit code.
+///
+/// These are attached to an [`anyhow::Error`] either as the root cause or as context underneath a
+/// more detailed message, see [`exit_code`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum NomadError {
+    /// The `git` binary could not be executed.
+    GitNotFound,
+    /// The working directory is not inside a git repository.
+    NotARepository,
+    /// `HEAD` does not point to a branch.
+    DetachedHead,
+    /// The remote could not be contacted at all.
+    RemoteUnreachable,
+    /// The remote was contacted but refused the credentials (or lack thereof).
+    AuthenticationFailed,
+    /// The remote refused to update some refs, usually because they changed since the last fetch.
+    PushRejected,
+    /// Another clone is already pushing refs as the same host.
+    HostNameCollision,
+    /// `git` printed a ref in a format that could not be understood.
+    RefParse(GitRefParseError),
+}
+
+/// Exit code for any failure that isn't a [`NomadError`].
+pub const EXIT_FAILURE: u8 = 1;
+
+impl NomadError {
+    /// The process exit code that this error should produce.
+    ///
+    /// 2 is reserved for invalid command line usage, which `clap` reports on its own.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::GitNotFound => 3,
+            Self::NotARepository => 4,
+            Self::DetachedHead => 5,
+            Self::RemoteUnreachable => 6,
+            Self::AuthenticationFailed => 7,
+            Self::PushRejected => 8,
+            Self::HostNameCollision => 9,
+            Self::RefParse(_) => 10,
+        }
+    }
+}
+
+impl fmt::Display for NomadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::GitNotFound => write!(f, "git could not be executed"),
+            Self::NotARepository => write!(f, "not a git repository"),
+            Self::DetachedHead => write!(f, "HEAD is detached, check out a branch first"),
+            Self::RemoteUnreachable => write!(f, "the remote could not be reached"),
+            Self::AuthenticationFailed => write!(f, "authentication with the remote failed"),
+            Self::PushRejected => write!(f, "the remote rejected the push"),
+            Self::HostNameCollision => write!(f, "another clone is pushing as the same host"),
+            Self::RefParse(_) => write!(f, "could not parse a ref"),
+        }
+    }
+}
+
+impl Error for NomadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::RefParse(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<GitRefParseError> for NomadError {
+    fn from(err: GitRefParseError) -> Self {
+        Self::RefParse(err)
+    }
+}
+
+/// The process exit code for `error`, from the outermost [`NomadError`] attached to it.
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+    error
+        .downcast_ref::<NomadError>()
+        .map_or(EXIT_FAILURE, NomadError::exit_code)
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{anyhow, Context};
+
+    use crate::git_ref::GitRefParseError;
+
+    use super::{exit_code, NomadError, EXIT_FAILURE};
+
+    #[test]
+    fn exit_code_through_context() {
+        assert_eq!(exit_code(&anyhow!("something else")), EXIT_FAILURE);
+
+        let err = anyhow::Error::new(NomadError::PushRejected)
+            .context("command failure")
+            .context("syncing");
+        assert_eq!(exit_code(&err), 8);
+
+        // The outermost error wins, even when it is attached as context.
+        let err = err
+            .context(NomadError::HostNameCollision)
+            .context("Host name collision");
+        assert_eq!(exit_code(&err), 9);
+
+        let err = Err::<(), _>(NomadError::from(GitRefParseError::MissingName(
+            "abc".to_string(),
+        )))
+        .context("listing refs")
+        .unwrap_err();
+        assert_eq!(exit_code(&err), 10);
+    }
+
+    #[test]
+    fn exit_codes_are_distinct() {
+        let errors = [
+            NomadError::GitNotFound,
+            NomadError::NotARepository,
+            NomadError::DetachedHead,
+            NomadError::RemoteUnreachable,
+            NomadError::AuthenticationFailed,
+            NomadError::PushRejected,
+            NomadError::HostNameCollision,
+            NomadError::RefParse(GitRefParseError::TooManyParts(String::new())),
+        ];
+        let mut codes = errors.iter().map(NomadError::exit_code).collect::<Vec<_>>();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+        assert!(!codes.contains(&EXIT_FAILURE));
+        assert!(!codes.contains(&2));
+    }
+}
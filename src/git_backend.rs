@@ -0,0 +1,209 @@
+//! See [`GitBackend`] for the primary entry point.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use anyhow::Result;
+
+use crate::{
+    git_binary::{ConfigEntry, ConfigScope, RefDetails},
+    git_ref::GitRef,
+    renderer::Renderer,
+    snapshot::{PruneFrom, Snapshot},
+    types::{Branch, Host, NomadRef, RefKind, Remote, RemoteNomadRefSet, User},
+};
+
+/// The repository operations that a [`crate::Workflow`] needs, so that workflows do not care
+/// how they are carried out.
+///
+/// [`crate::GitBinary`] implements everything by invoking a `git` subprocess.
+pub trait GitBackend {
+    /// Whether workflows should print anything beyond what the user explicitly asked for.
+    fn is_output_allowed(&self) -> bool;
+
+    /// Read a single namespaced config value, which is `None` when unset.
+    fn get_config(&self, renderer: &mut impl Renderer, key: &str) -> Result<Option<String>>;
+
+    /// Read a namespaced flag, which is `false` when unset.
+    fn get_config_bool(&self, renderer: &mut impl Renderer, key: &str) -> Result<bool>;
+
+    /// Read every value of a namespaced key along with where it was set, only from `scope` if
+    /// specified.
+    fn get_config_entries(
+        &self,
+        renderer: &mut impl Renderer,
+        scope: Option<ConfigScope>,
+        key: &str,
+    ) -> Result<Vec<ConfigEntry>>;
+
+    /// Read every value of a multi-valued namespaced key.
+    fn get_config_all(&self, renderer: &mut impl Renderer, key: &str) -> Result<Vec<String>> {
+        Ok(self
+            .get_config_entries(renderer, None, key)?
+            .into_iter()
+            .map(|entry| entry.value)
+            .collect())
+    }
+
+    /// Replace every value of a multi-valued namespaced key in `scope` with `values`.
+    fn set_config_all(
+        &self,
+        renderer: &mut impl Renderer,
+        scope: ConfigScope,
+        key: &str,
+        values: &[&str],
+    ) -> Result<()>;
+
+    /// Remove every value of a namespaced key from `scope`, failing if it is not set there.
+    fn unset_config(
+        &self,
+        renderer: &mut impl Renderer,
+        scope: ConfigScope,
+        key: &str,
+    ) -> Result<()>;
+
+    /// The random ID of this clone, generated and saved the first time it is needed.
+    fn clone_id(&self, renderer: &mut impl Renderer) -> Result<String>;
+
+    /// The directory that git runs hooks from.
+    fn hooks_dir(&self, renderer: &mut impl Renderer) -> Result<PathBuf>;
+
+    /// Resolve a single ref by its full name.
+    fn get_ref(
+        &self,
+        renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+        ref_name: impl AsRef<str>,
+    ) -> Result<GitRef>;
+
+    /// List all the non-HEAD refs in the repository.
+    fn list_refs(
+        &self,
+        renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+    ) -> Result<Vec<GitRef>>;
+
+    /// Build a point in time snapshot for all refs that nomad cares about.
+    fn snapshot<'a>(
+        &self,
+        renderer: &mut impl Renderer,
+        user: &'a User,
+    ) -> Result<Snapshot<'a, GitRef>> {
+        let refs = self.list_refs(renderer, "Fetching all refs")?;
+
+        let mut local_branches = HashSet::<Branch>::new();
+        let mut nomad_refs = Vec::<NomadRef<'a, GitRef>>::new();
+
+        for r in refs {
+            if let Some(name) = r.name.strip_prefix("refs/heads/") {
+                local_branches.insert(Branch::from(name.to_string()));
+            }
+
+            if let Ok(nomad_ref) = NomadRef::<GitRef>::from_git_local_ref(user, r) {
+                nomad_refs.push(nomad_ref);
+            }
+        }
+
+        Ok(Snapshot::new(user, local_branches, nomad_refs))
+    }
+
+    /// When the commit behind every local nomad managed ref was committed, in seconds since the
+    /// Unix epoch keyed by commit ID.
+    fn nomad_committer_dates(&self, renderer: &mut impl Renderer) -> Result<HashMap<String, u64>>;
+
+    /// What every local nomad managed ref points at, keyed by ref name.
+    fn nomad_ref_details(
+        &self,
+        renderer: &mut impl Renderer,
+    ) -> Result<HashMap<String, RefDetails>>;
+
+    /// The branch that `HEAD` points to.
+    fn current_branch(&self, renderer: &mut impl Renderer) -> Result<Branch<'static>>;
+
+    /// How many commits are only reachable from `left` and only reachable from `right`.
+    fn count_left_right(
+        &self,
+        renderer: &mut impl Renderer,
+        left: &str,
+        right: &str,
+    ) -> Result<(usize, usize)>;
+
+    /// The best common ancestor of `left` and `right`.
+    fn merge_base(&self, renderer: &mut impl Renderer, left: &str, right: &str) -> Result<String>;
+
+    /// Point `branch_name` at `commit_id` and switch to it.
+    fn checkout_branch(
+        &self,
+        renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+        branch_name: &Branch,
+        commit_id: &str,
+    ) -> Result<()>;
+
+    /// The commit IDs of every stash entry, newest first.
+    fn stash_entries(&self, renderer: &mut impl Renderer) -> Result<Vec<String>>;
+
+    /// A stash-like commit of uncommitted changes, or `None` if there aren't any.
+    fn worktree_snapshot(&self, renderer: &mut impl Renderer) -> Result<Option<String>>;
+
+    /// Whether [`GitBackend::worktree_snapshot`] would find uncommitted changes, without writing
+    /// anything.
+    fn has_worktree_changes(&self, renderer: &mut impl Renderer) -> Result<bool>;
+
+    /// Apply the stash-like commit `commit_id` to the working tree.
+    fn apply_stash(
+        &self,
+        renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+        commit_id: &str,
+    ) -> Result<()>;
+
+    /// Fetch all nomad managed refs from `remote`, returning the set of refs that it has.
+    fn fetch_nomad_refs(
+        &self,
+        renderer: &mut impl Renderer,
+        user: &User,
+        remote: &Remote,
+    ) -> Result<RemoteNomadRefSet>;
+
+    /// List the nomad managed refs that `remote` has, without fetching them.
+    fn list_nomad_refs(
+        &self,
+        renderer: &mut impl Renderer,
+        user: &User,
+        remote: &Remote,
+    ) -> Result<Vec<NomadRef<'static, GitRef>>>;
+
+    /// Push `branches` and `extra_refs` to nomad managed refs in `remote`, refusing to clobber
+    /// refs that another clone pushed as the same host.
+    #[allow(clippy::too_many_arguments)]
+    fn push_nomad_refs<'b>(
+        &self,
+        renderer: &mut impl Renderer,
+        user: &User,
+        host: &Host,
+        remote: &Remote,
+        clone_id: &str,
+        branches: impl IntoIterator<Item = &'b Branch<'b>>,
+        extra_refs: &[(RefKind, Branch, String)],
+    ) -> Result<()>;
+
+    /// Forget which clone was pushing as each host in `remote` that `is_forgotten`.
+    fn forget_clone_markers(
+        &self,
+        renderer: &mut impl Renderer,
+        user: &User,
+        remote: &Remote,
+        is_forgotten: impl Fn(&Host) -> bool,
+    ) -> Result<()>;
+
+    /// Delete nomad managed refs locally and from the remotes that have them.
+    fn prune_nomad_refs<'a>(
+        &self,
+        renderer: &mut impl Renderer,
+        remote_nomad_refs: &[(&Remote, RemoteNomadRefSet)],
+        prune: impl Iterator<Item = PruneFrom<'a, GitRef>>,
+    ) -> Result<()>;
+}
@@ -1,13 +1,24 @@
 //! See [`GitBinary`] for the primary entry point.
 
-use anyhow::{bail, Result};
-use std::{borrow::Cow, collections::HashSet, ffi::OsStr, path::Path, process::Command};
+use anyhow::{bail, Context, Result};
+use std::{
+    borrow::Cow,
+    collections::{hash_map::RandomState, HashMap},
+    ffi::OsStr,
+    fs::{self, File, TryLockError},
+    hash::{BuildHasher, Hasher},
+    io::{Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 use crate::{
+    error::NomadError,
+    git_backend::GitBackend,
     git_ref::GitRef,
     renderer::Renderer,
-    snapshot::{PruneFrom, Snapshot},
-    types::{Branch, Host, NomadRef, Remote, User},
+    snapshot::PruneFrom,
+    types::{Branch, Host, NomadRef, RefKind, Remote, RemoteNomadRefSet, User},
     verbosity::{is_output_allowed, output_stdout, run_notable, run_trivial, Verbosity},
 };
 
@@ -39,10 +50,10 @@ pub fn git_command(name: impl AsRef<OsStr>) -> Command {
 
 /// Containerizes all the naming schemes used by nomad from the wild west of all other git tools,
 /// both built-in and third party.
-mod namespace {
+pub(crate) mod namespace {
     use crate::{
         git_ref::GitRef,
-        types::{Branch, Host, NomadRef, User},
+        types::{Branch, Host, NomadRef, RefKind, Remote, User},
     };
 
     /// The main name that we declare to be ours and nobody elses. This lays claim to the section
@@ -54,42 +65,175 @@ mod namespace {
         format!("{}.{}", PREFIX, key)
     }
 
-    /// The refspec to list remote nomad managed refs.
-    pub fn list_refspec(user: &User) -> String {
-        format!("refs/{prefix}/{user}/*", prefix = PREFIX, user = user.0)
+    /// The `git config` key (within the `nomad` section) for the random ID of a clone.
+    pub const CONFIG_CLONE_ID: &str = "cloneId";
+
+    /// Where `git stash` entries are published, apart from `refs/{PREFIX}` so that their names
+    /// never clash with branches.
+    pub const STASH_PREFIX: &str = "nomad-stash";
+
+    /// Where working tree snapshots are published, for the same reason as [`STASH_PREFIX`].
+    pub const WORKTREE_PREFIX: &str = "nomad-worktree";
+
+    /// Where tags are published, for the same reason as [`STASH_PREFIX`].
+    pub const TAG_PREFIX: &str = "nomad-tags";
+
+    /// Every hierarchy under `refs/` that holds nomad managed refs.
+    pub const PREFIXES: [&str; 4] = [PREFIX, STASH_PREFIX, WORKTREE_PREFIX, TAG_PREFIX];
+
+    /// The hierarchy under `refs/` that refs of `kind` are published in.
+    pub fn kind_prefix(kind: RefKind) -> &'static str {
+        match kind {
+            RefKind::Branch => PREFIX,
+            RefKind::Stash => STASH_PREFIX,
+            RefKind::Worktree => WORKTREE_PREFIX,
+            RefKind::Tag => TAG_PREFIX,
+        }
+    }
+
+    /// The [`RefKind`] of refs in the `refs/{prefix}` hierarchy, if that is one of the
+    /// [`PREFIXES`].
+    fn prefix_kind(prefix: &str) -> Option<RefKind> {
+        match prefix {
+            PREFIX => Some(RefKind::Branch),
+            STASH_PREFIX => Some(RefKind::Stash),
+            WORKTREE_PREFIX => Some(RefKind::Worktree),
+            TAG_PREFIX => Some(RefKind::Tag),
+            _ => None,
+        }
+    }
+
+    /// The patterns to list local nomad managed refs with `git for-each-ref`.
+    pub fn local_patterns() -> Vec<String> {
+        PREFIXES
+            .iter()
+            .map(|prefix| format!("refs/{}/", prefix))
+            .collect()
+    }
+
+    /// The refspecs to list remote nomad managed refs.
+    pub fn list_refspecs(user: &User) -> Vec<String> {
+        PREFIXES
+            .iter()
+            .map(|prefix| format!("refs/{}/{}/*", prefix, user.0))
+            .collect()
+    }
+
+    /// Where nomad managed refs under `refs/{prefix}` from a single remote are mirrored locally.
+    ///
+    /// Kept apart from `refs/{prefix}` so that fetching with `--prune` only ever deletes refs that
+    /// track this one remote.
+    pub fn tracking_namespace(prefix: &str, remote: &Remote) -> String {
+        format!("refs/{}-remotes/{}", prefix, remote_component(remote))
+    }
+
+    /// Whether `name` is in the [`tracking_namespace`] of any remote.
+    pub fn is_tracking_ref(name: &str) -> bool {
+        PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(&format!("refs/{}-remotes/", prefix)))
     }
 
-    /// The refspec to fetch remote nomad managed refs as local refs.
+    /// The name of `remote` as a single ref component.
     ///
-    /// `refs/nomad/rraval/apollo/master` becomes `refs/nomad/apollo/master`.
+    /// Remote names may contain `/`, which would otherwise nest the namespace of remote `origin/x`
+    /// within that of `origin`, and have fetching or listing one remote touch the other's refs.
+    fn remote_component(remote: &Remote) -> String {
+        remote.0.replace('%', "%25").replace('/', "%2F")
+    }
+
+    /// The file in the common `.git` directory that pushes lock to take turns.
+    pub const PUSH_LOCK: &str = "nomad-push.lock";
+
+    /// The ref in the remote that records which clone is pushing as `host`, pointing to a blob
+    /// containing its `nomad.cloneId`.
     ///
-    /// `refs/nomad/rraval/boreas/feature` becomes `refs/nomad/boreas/feature`.
-    pub fn fetch_refspec(user: &User) -> String {
+    /// This lives outside of `refs/{PREFIX}` so that it is never mistaken for a branch.
+    pub fn clone_marker_ref(user: &User, host: &Host) -> String {
+        format!("refs/{}-clone/{}/{}", PREFIX, user.0, host.0)
+    }
+
+    /// Where the [`clone_marker_ref`]s of all hosts in `remote` are mirrored locally.
+    pub fn clone_marker_namespace(remote: &Remote) -> String {
+        format!("refs/{}-remote-clones/{}", PREFIX, remote_component(remote))
+    }
+
+    /// The refspec to fetch every [`clone_marker_ref`] for `user` into the
+    /// [`clone_marker_namespace`] for `remote`.
+    pub fn clone_marker_refspec(user: &User, remote: &Remote) -> String {
         format!(
-            "+{remote_pattern}:refs/{prefix}/*",
-            remote_pattern = list_refspec(user),
+            "+refs/{prefix}-clone/{user}/*:{namespace}/*",
             prefix = PREFIX,
+            user = user.0,
+            namespace = clone_marker_namespace(remote),
         )
     }
 
-    /// The refspec to push local branches as nomad managed refs in the remote.
+    /// The refspecs to fetch remote nomad managed refs into the [`tracking_namespace`]s for the
+    /// remote.
+    ///
+    /// From remote `origin`, `refs/nomad/rraval/apollo/master` becomes
+    /// `refs/nomad-remotes/origin/apollo/master`.
+    pub fn tracking_refspecs(user: &User, remote: &Remote) -> Vec<String> {
+        PREFIXES
+            .iter()
+            .map(|prefix| {
+                format!(
+                    "+refs/{prefix}/{user}/*:{namespace}/*",
+                    prefix = prefix,
+                    user = user.0,
+                    namespace = tracking_namespace(prefix, remote),
+                )
+            })
+            .collect()
+    }
+
+    /// Like [`tracking_refspecs`], but only for the refs of `host`.
+    pub fn host_tracking_refspecs(user: &User, host: &Host, remote: &Remote) -> Vec<String> {
+        PREFIXES
+            .iter()
+            .map(|prefix| {
+                format!(
+                    "+refs/{prefix}/{user}/{host}/*:{namespace}/{host}/*",
+                    prefix = prefix,
+                    user = user.0,
+                    host = host.0,
+                    namespace = tracking_namespace(prefix, remote),
+                )
+            })
+            .collect()
+    }
+
+    /// The refspec to push a specific commit as the nomad managed ref in the remote.
     ///
-    /// When run on host `boreas` that has a branch named `feature`:
-    /// `refs/heads/feature` becomes `refs/nomad/rraval/boreas/feature`.
-    pub fn push_refspec(user: &User, host: &Host) -> String {
+    /// Pushing the commit that was read rather than `refs/heads/{branch}` pins exactly what the
+    /// remote ends up with, even if the branch moves during the push.
+    ///
+    /// When run on host `boreas` that has a branch named `feature` at `abc123`:
+    /// `abc123` becomes `refs/nomad/rraval/boreas/feature`.
+    pub fn push_refspec<Ref>(commit_id: &str, nomad_ref: &NomadRef<Ref>) -> String {
+        format!("{}:{}", commit_id, nomad_ref.to_git_remote_ref())
+    }
+
+    /// The `git push` option that only lets `remote_ref` be overwritten if the remote still has
+    /// the `expected` commit there, or has no such ref at all if nothing is expected.
+    pub fn lease(remote_ref: &str, expected: Option<&str>) -> String {
         format!(
-            "+refs/heads/*:refs/{prefix}/{user}/{host}/*",
-            prefix = PREFIX,
-            user = user.0,
-            host = host.0,
+            "--force-with-lease={}:{}",
+            remote_ref,
+            expected.unwrap_or_default()
         )
     }
 
     impl<Ref> NomadRef<'_, Ref> {
         /// A nomad ref in the local clone, which elides the user name for convenience.
-        #[cfg(test)]
         pub fn to_git_local_ref(&self) -> String {
-            format!("refs/{}/{}/{}", PREFIX, self.host.0, self.branch.0)
+            format!(
+                "refs/{}/{}/{}",
+                kind_prefix(self.kind),
+                self.host.0,
+                self.branch.0
+            )
         }
 
         /// A nomad ref in the remote. The remote may have many users that all use `git-nomad` and
@@ -97,7 +241,20 @@ mod namespace {
         pub fn to_git_remote_ref(&self) -> String {
             format!(
                 "refs/{}/{}/{}/{}",
-                PREFIX, self.user.0, self.host.0, self.branch.0
+                kind_prefix(self.kind),
+                self.user.0,
+                self.host.0,
+                self.branch.0
+            )
+        }
+
+        /// A nomad ref as mirrored in the tracking namespace for `remote`.
+        pub fn to_git_tracking_ref(&self, remote: &Remote) -> String {
+            format!(
+                "{}/{}/{}",
+                tracking_namespace(kind_prefix(self.kind), remote),
+                self.host.0,
+                self.branch.0
             )
         }
     }
@@ -105,21 +262,26 @@ mod namespace {
     impl NomadRef<'_, GitRef> {
         /// Constructs a [`NomadRef`] from a git ref in the local clone, which elides the user name
         /// for convenience.
+        ///
+        /// Everything after the host is treated as the branch name, since branches like
+        /// `feature/login` contain slashes of their own.
         pub fn from_git_local_ref<'a>(
             user: &'a User,
             git_ref: GitRef,
         ) -> Result<NomadRef<'a, GitRef>, GitRef> {
             let parts = git_ref.name.split('/').collect::<Vec<_>>();
             match parts.as_slice() {
-                ["refs", prefix, host, branch_name] => {
-                    if prefix != &PREFIX {
-                        return Err(git_ref);
-                    }
+                ["refs", prefix, host, branch_parts @ ..] if !branch_parts.is_empty() => {
+                    let kind = match prefix_kind(prefix) {
+                        Some(kind) => kind,
+                        None => return Err(git_ref),
+                    };
 
                     Ok(NomadRef {
                         user: user.always_borrow(),
                         host: Host::from(host.to_string()),
-                        branch: Branch::from(branch_name.to_string()),
+                        kind,
+                        branch: Branch::from(branch_parts.join("/")),
                         ref_: git_ref,
                     })
                 }
@@ -127,20 +289,51 @@ mod namespace {
             }
         }
 
+        /// Constructs a [`NomadRef`] from a git ref in the tracking namespace for `remote`.
+        ///
+        /// The resulting `ref_` is the local ref that should point to the same commit, which is
+        /// how fetched refs make their way into the local clone.
+        pub fn from_git_tracking_ref<'a>(
+            user: &'a User,
+            remote: &Remote,
+            git_ref: GitRef,
+        ) -> Result<NomadRef<'a, GitRef>, GitRef> {
+            let local_name = match PREFIXES.iter().find_map(|prefix| {
+                let rest = git_ref
+                    .name
+                    .strip_prefix(&tracking_namespace(prefix, remote))?
+                    .strip_prefix('/')?;
+                Some(format!("refs/{}/{}", prefix, rest))
+            }) {
+                Some(local_name) => local_name,
+                None => return Err(git_ref),
+            };
+
+            Self::from_git_local_ref(
+                user,
+                GitRef {
+                    commit_id: git_ref.commit_id,
+                    name: local_name,
+                },
+            )
+        }
+
         /// Constructs a [`NomadRef`] from a git ref in the remote, which includes the user as part
         /// of the ref name.
         pub fn from_git_remote_ref(git_ref: GitRef) -> Result<NomadRef<'static, GitRef>, GitRef> {
             let parts = git_ref.name.split('/').collect::<Vec<_>>();
             match parts.as_slice() {
-                ["refs", prefix, user, host, branch_name] => {
-                    if prefix != &PREFIX {
-                        return Err(git_ref);
-                    }
+                ["refs", prefix, user, host, branch_parts @ ..] if !branch_parts.is_empty() => {
+                    let kind = match prefix_kind(prefix) {
+                        Some(kind) => kind,
+                        None => return Err(git_ref),
+                    };
 
                     Ok(NomadRef {
                         user: User::from(user.to_string()),
                         host: Host::from(host.to_string()),
-                        branch: Branch::from(branch_name.to_string()),
+                        kind,
+                        branch: Branch::from(branch_parts.join("/")),
                         ref_: git_ref,
                     })
                 }
@@ -153,12 +346,13 @@ mod namespace {
     mod tests {
         use crate::{
             git_ref::GitRef,
-            types::{Branch, Host, NomadRef, User},
+            types::{Branch, Host, NomadRef, RefKind, Remote, User},
         };
 
         const USER: &str = "user0";
         const HOST: &str = "host0";
         const BRANCH: &str = "branch0";
+        const NESTED_BRANCH: &str = "feature/login/form";
 
         /// [`NomadRef::from_git_local_ref`] should be able to parse ref names produced by
         /// [`NomadRef::to_git_local_ref`] (they are duals).
@@ -167,6 +361,7 @@ mod namespace {
             let local_ref_name = NomadRef {
                 user: User::from(USER),
                 host: Host::from(HOST),
+                kind: RefKind::Branch,
                 branch: Branch::from(BRANCH),
                 ref_: (),
             }
@@ -192,6 +387,7 @@ mod namespace {
             let remote_ref_name = NomadRef {
                 user: User::from(USER),
                 host: Host::from(HOST),
+                kind: RefKind::Branch,
                 branch: Branch::from(BRANCH),
                 ref_: (),
             }
@@ -209,6 +405,154 @@ mod namespace {
             assert_eq!(&nomad_ref.branch.0, BRANCH);
         }
 
+        /// Branch names with slashes should survive a round trip through local ref names.
+        #[test]
+        fn test_to_and_from_local_ref_nested_branch() {
+            let local_ref_name = NomadRef {
+                user: User::from(USER),
+                host: Host::from(HOST),
+                kind: RefKind::Branch,
+                branch: Branch::from(NESTED_BRANCH),
+                ref_: (),
+            }
+            .to_git_local_ref();
+
+            let local_git_ref = GitRef {
+                commit_id: "some_commit_id".to_string(),
+                name: local_ref_name,
+            };
+
+            let user = &User::from(USER);
+            let nomad_ref = NomadRef::<GitRef>::from_git_local_ref(user, local_git_ref).unwrap();
+
+            assert_eq!(&nomad_ref.host.0, HOST);
+            assert_eq!(&nomad_ref.branch.0, NESTED_BRANCH);
+        }
+
+        /// Branch names with slashes should survive a round trip through remote ref names.
+        #[test]
+        fn test_to_and_from_remote_ref_nested_branch() {
+            let remote_ref_name = NomadRef {
+                user: User::from(USER),
+                host: Host::from(HOST),
+                kind: RefKind::Branch,
+                branch: Branch::from(NESTED_BRANCH),
+                ref_: (),
+            }
+            .to_git_remote_ref();
+
+            let remote_git_ref = GitRef {
+                commit_id: "some_commit_id".to_string(),
+                name: remote_ref_name,
+            };
+
+            let nomad_ref = NomadRef::<GitRef>::from_git_remote_ref(remote_git_ref).unwrap();
+
+            assert_eq!(&nomad_ref.user.0, USER);
+            assert_eq!(&nomad_ref.host.0, HOST);
+            assert_eq!(&nomad_ref.branch.0, NESTED_BRANCH);
+        }
+
+        /// [`NomadRef::from_git_tracking_ref`] should parse ref names produced by
+        /// [`NomadRef::to_git_tracking_ref`] into the equivalent local ref, but only for the same
+        /// remote.
+        #[test]
+        fn test_to_and_from_tracking_ref() {
+            let user = &User::from(USER);
+            let remote = &Remote::from("origin");
+            let nomad_ref = NomadRef {
+                user: User::from(USER),
+                host: Host::from(HOST),
+                kind: RefKind::Branch,
+                branch: Branch::from(NESTED_BRANCH),
+                ref_: (),
+            };
+
+            let tracking_git_ref = GitRef {
+                commit_id: "some_commit_id".to_string(),
+                name: nomad_ref.to_git_tracking_ref(remote),
+            };
+
+            let parsed = NomadRef::<GitRef>::from_git_tracking_ref(
+                user,
+                &Remote::from("origin-other"),
+                tracking_git_ref.clone(),
+            );
+            assert!(parsed.is_err());
+
+            let parsed =
+                NomadRef::<GitRef>::from_git_tracking_ref(user, remote, tracking_git_ref).unwrap();
+            assert_eq!(&parsed.host.0, HOST);
+            assert_eq!(&parsed.branch.0, NESTED_BRANCH);
+            assert_eq!(parsed.ref_.name, nomad_ref.to_git_local_ref());
+            assert_eq!(parsed.ref_.commit_id, "some_commit_id");
+        }
+
+        /// Refs of every [`RefKind`] should keep their kind through a round trip, even when a
+        /// branch has the same name as a ref of another kind.
+        #[test]
+        fn test_to_and_from_ref_kinds() {
+            let user = &User::from(USER);
+            let remote = &Remote::from("origin");
+
+            for (kind, name) in [
+                (RefKind::Branch, "stash/0"),
+                (RefKind::Branch, "0"),
+                (RefKind::Stash, "0"),
+                (RefKind::Branch, "worktree/master"),
+                (RefKind::Worktree, "master"),
+                (RefKind::Branch, "tags/v1"),
+                (RefKind::Tag, "v1"),
+            ] {
+                let nomad_ref = NomadRef {
+                    user: User::from(USER),
+                    host: Host::from(HOST),
+                    kind,
+                    branch: Branch::from(name),
+                    ref_: (),
+                };
+                let git_ref = |name| GitRef {
+                    commit_id: "some_commit_id".to_string(),
+                    name,
+                };
+
+                let parsed = [
+                    NomadRef::<GitRef>::from_git_local_ref(
+                        user,
+                        git_ref(nomad_ref.to_git_local_ref()),
+                    ),
+                    NomadRef::<GitRef>::from_git_remote_ref(git_ref(nomad_ref.to_git_remote_ref())),
+                    NomadRef::<GitRef>::from_git_tracking_ref(
+                        user,
+                        remote,
+                        git_ref(nomad_ref.to_git_tracking_ref(remote)),
+                    ),
+                ];
+                for parsed in parsed {
+                    let parsed = parsed.unwrap();
+                    assert_eq!(parsed.kind, kind);
+                    assert_eq!(&parsed.branch.0, name);
+                }
+            }
+        }
+
+        /// A ref that stops at the host has no branch and should not parse.
+        #[test]
+        fn test_from_ref_missing_branch() {
+            let user = &User::from(USER);
+            let local_git_ref = GitRef {
+                commit_id: "some_commit_id".to_string(),
+                name: "refs/nomad/host".to_string(),
+            };
+            assert!(NomadRef::<GitRef>::from_git_local_ref(user, local_git_ref).is_err());
+
+            let remote_git_ref = GitRef {
+                commit_id: "some_commit_id".to_string(),
+                name: "refs/nomad/user/host".to_string(),
+            };
+            assert!(NomadRef::<GitRef>::from_git_remote_ref(remote_git_ref).is_err());
+        }
+
         /// [`NomadRef::from_git_remote_ref`] should refuse to parse refs with a different prefix.
         #[test]
         fn test_from_remote_ref_wrong_prefix() {
@@ -223,6 +567,55 @@ mod namespace {
     }
 }
 
+/// The `git config` key (within the `nomad` section) for the random ID of a clone.
+pub use namespace::CONFIG_CLONE_ID;
+
+/// Which `git config` file to write to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConfigScope {
+    /// The config of the current repository, shared by all of its worktrees.
+    Local,
+    /// The config of the current user, shared by all of their repositories.
+    Global,
+}
+
+impl ConfigScope {
+    /// The `git config` option that selects this scope.
+    fn flag(self) -> &'static str {
+        match self {
+            Self::Local => "--local",
+            Self::Global => "--global",
+        }
+    }
+
+    /// How git refers to this scope.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Local => "local",
+            Self::Global => "global",
+        }
+    }
+}
+
+/// A single value read from `git config`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigEntry {
+    /// Where the value was set, usually the path to a config file.
+    pub origin: String,
+    pub value: String,
+}
+
+/// What a ref points at, in enough detail for a human to remember what it was for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefDetails {
+    /// The abbreviated commit ID (or tag object ID for annotated tags).
+    pub short_id: String,
+    /// When the commit was committed (or the tag was tagged), like `3 days ago`.
+    pub relative_date: String,
+    /// The first line of the commit (or tag) message.
+    pub subject: String,
+}
+
 /// Implements repository manipulations by delegating to some ambient `git` binary that exists
 /// somewhere on the system.
 #[derive(PartialEq, Eq)]
@@ -236,6 +629,12 @@ pub struct GitBinary<'name> {
 
     /// The absolute path to the `.git` directory of the repository.
     git_dir: String,
+
+    /// The absolute path to the top level of the working tree, if there is one.
+    ///
+    /// Bare repositories (or invocations from within the `.git` directory itself) have no working
+    /// tree, in which case operations that need one like `git checkout` will fail.
+    work_tree: Option<String>,
 }
 
 impl<'name> GitBinary<'name> {
@@ -259,23 +658,47 @@ impl<'name> GitBinary<'name> {
         .map(LineArity::from)
         .and_then(LineArity::one)?;
 
+        // Commands are run with an explicit `--git-dir`, which makes git treat the current
+        // directory as the top of the working tree unless told otherwise.
+        let work_tree = run_trivial(
+            renderer,
+            verbosity,
+            "Resolving working tree",
+            git_command(name.as_ref())
+                .current_dir(cwd)
+                .args(["rev-parse", "--show-toplevel"]),
+        )
+        .and_then(output_stdout)
+        .map(LineArity::from)
+        .and_then(LineArity::one)
+        .ok();
+
         Ok(GitBinary {
             verbosity,
             name,
             git_dir,
+            work_tree,
         })
     }
 }
 
 impl GitBinary<'_> {
-    /// Invoke a git sub-command with an explicit `--git-dir` to make it independent of the working
-    /// directory it is invoked from.
+    /// Invoke a git sub-command with an explicit `--git-dir` (and `--work-tree`, if there is one)
+    /// to make it independent of the working directory it is invoked from.
     pub fn command(&self) -> Command {
         let mut command = git_command(self.name.as_ref());
         command.args(["--git-dir", &self.git_dir]);
+        if let Some(work_tree) = &self.work_tree {
+            command.args(["--work-tree", work_tree]);
+        }
         command
     }
 
+    /// The absolute path to the `.git` directory of the repository.
+    pub fn git_dir(&self) -> &Path {
+        Path::new(&self.git_dir)
+    }
+
     /// Wraps `git config` to read a single namespaced value.
     pub fn get_config(&self, renderer: &mut impl Renderer, key: &str) -> Result<Option<String>> {
         self.get_config_with_env(renderer, key, [] as [(&str, &str); 0])
@@ -306,16 +729,139 @@ impl GitBinary<'_> {
         .and_then(LineArity::zero_or_one)
     }
 
+    /// Wraps `git config --type=bool` to read a namespaced flag, which is `false` when unset.
+    ///
+    /// Git does the parsing, so the usual spellings like `yes` and `on` work.
+    pub fn get_config_bool(&self, renderer: &mut impl Renderer, key: &str) -> Result<bool> {
+        let value = run_trivial(
+            renderer,
+            self.verbosity,
+            format!("Get config {}", key),
+            self.command().args([
+                "config",
+                "--type=bool",
+                "--default",
+                "false",
+                "--get",
+                &namespace::config_key(key),
+            ]),
+        )
+        .and_then(output_stdout)
+        .map(LineArity::from)
+        .and_then(LineArity::one)?;
+        Ok(value == "true")
+    }
+
+    /// Wraps `git config` to read every value of a namespaced key along with where it was set, in
+    /// the order git encounters them. Only reads from `scope` if specified, or from every config
+    /// file otherwise.
+    pub fn get_config_entries(
+        &self,
+        renderer: &mut impl Renderer,
+        scope: Option<ConfigScope>,
+        key: &str,
+    ) -> Result<Vec<ConfigEntry>> {
+        let key = namespace::config_key(key);
+
+        // `git config --get-all` exits with a non-zero code when the key does not exist, and
+        // unlike `--get` does not support `--default`. Listing everything always succeeds.
+        let output = run_trivial(
+            renderer,
+            self.verbosity,
+            format!("Get all config {}", key),
+            self.command()
+                .arg("config")
+                .args(scope.map(ConfigScope::flag))
+                .args(["--null", "--show-origin", "--list"]),
+        )
+        .and_then(output_stdout)?;
+
+        // Each entry is `<origin>\0<name>\n<value>\0`
+        let mut fields = output.split('\0');
+        let mut entries = Vec::new();
+        while let (Some(origin), Some(name_and_value)) = (fields.next(), fields.next()) {
+            if let Some((name, value)) = name_and_value.split_once('\n') {
+                if name.eq_ignore_ascii_case(&key) {
+                    entries.push(ConfigEntry {
+                        origin: origin.strip_prefix("file:").unwrap_or(origin).to_string(),
+                        value: value.to_string(),
+                    });
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// The directory git runs hooks from, which is `core.hooksPath` if configured and the
+    /// `hooks` directory inside the `.git` directory otherwise.
+    pub fn hooks_dir(&self, renderer: &mut impl Renderer) -> Result<PathBuf> {
+        let hooks_path = run_trivial(
+            renderer,
+            self.verbosity,
+            "Get config core.hooksPath",
+            self.command().args([
+                "config",
+                // Expands a leading `~` like git itself does.
+                "--type=path",
+                "--default",
+                "",
+                "--get",
+                "core.hooksPath",
+            ]),
+        )
+        .and_then(output_stdout)
+        .map(LineArity::from)
+        .and_then(LineArity::zero_or_one)?;
+
+        Ok(match hooks_path {
+            None => Path::new(&self.git_dir).join("hooks"),
+            // Relative paths are resolved against the directory hooks run in, which is the top of
+            // the working tree for non-bare repositories.
+            Some(hooks_path) => {
+                Path::new(self.work_tree.as_ref().unwrap_or(&self.git_dir)).join(hooks_path)
+            }
+        })
+    }
+
+    /// Wraps `git config` to append a value to a multi-valued namespaced key.
+    pub fn add_config(
+        &self,
+        renderer: &mut impl Renderer,
+        scope: ConfigScope,
+        key: &str,
+        value: &str,
+    ) -> Result<()> {
+        run_trivial(
+            renderer,
+            self.verbosity,
+            format!("Add config {} = {}", key, value),
+            self.command().args([
+                "config",
+                scope.flag(),
+                "--add",
+                &namespace::config_key(key),
+                value,
+            ]),
+        )?;
+        Ok(())
+    }
+
     /// Wraps `git config` to write a single namespaced value.
-    #[cfg(test)]
-    pub fn set_config(&self, renderer: &mut impl Renderer, key: &str, value: &str) -> Result<()> {
+    pub fn set_config(
+        &self,
+        renderer: &mut impl Renderer,
+        scope: ConfigScope,
+        key: &str,
+        value: &str,
+    ) -> Result<()> {
         run_trivial(
             renderer,
             self.verbosity,
             format!("Set config {} = {}", key, value),
             self.command().args([
                 "config",
-                "--local",
+                scope.flag(),
                 "--replace-all",
                 &namespace::config_key(key),
                 value,
@@ -324,40 +870,93 @@ impl GitBinary<'_> {
         Ok(())
     }
 
-    /// Wraps `git fetch` to fetch refs from a given remote into the local repository.
-    ///
-    /// # Panics
+    /// Wraps `git config` to remove every value of a namespaced key from `scope`.
     ///
-    /// If `refspecs` is empty, which means git will use the user configured default behaviour
-    /// which is definitely not what we want.
-    fn fetch_refspecs<Description, RefSpec>(
+    /// Fails if the key is not set in `scope`.
+    pub fn unset_config(
         &self,
         renderer: &mut impl Renderer,
-        description: Description,
-        remote: &Remote,
-        refspecs: &[RefSpec],
-    ) -> Result<()>
-    where
-        Description: AsRef<str>,
-        RefSpec: AsRef<OsStr>,
-    {
-        assert!(!refspecs.is_empty());
-        run_notable(
+        scope: ConfigScope,
+        key: &str,
+    ) -> Result<()> {
+        if self
+            .get_config_entries(renderer, Some(scope), key)?
+            .is_empty()
+        {
+            bail!(
+                "{} is not set in {} git config",
+                namespace::config_key(key),
+                scope.name()
+            );
+        }
+
+        run_trivial(
             renderer,
             self.verbosity,
-            description,
-            self.command().args(["fetch", &remote.0]).args(refspecs),
+            format!("Unset config {}", key),
+            self.command().args([
+                "config",
+                scope.flag(),
+                "--unset-all",
+                &namespace::config_key(key),
+            ]),
         )?;
         Ok(())
     }
 
-    /// Wraps `git push` to push refs from the local repository into the given remote.
+    /// Replace every value of a multi-valued namespaced key with `values`.
     ///
     /// # Panics
     ///
-    /// If `refspecs` is empty, which means git will use the user configured default behaviour
-    /// which is definitely not what we want.
-    fn push_refspecs<Description, RefSpec>(
+    /// If `values` is empty, since there is nothing to replace the existing values with.
+    pub fn set_config_all(
+        &self,
+        renderer: &mut impl Renderer,
+        scope: ConfigScope,
+        key: &str,
+        values: &[&str],
+    ) -> Result<()> {
+        let (first, rest) = values.split_first().expect("at least one value");
+        self.set_config(renderer, scope, key, first)?;
+        for value in rest {
+            self.add_config(renderer, scope, key, value)?;
+        }
+        Ok(())
+    }
+
+    /// The random ID that tells this clone apart from others pushing as the same host, which is
+    /// generated and stored in `nomad.cloneId` the first time it is needed.
+    pub fn clone_id(&self, renderer: &mut impl Renderer) -> Result<String> {
+        if let Some(clone_id) = self.get_config(renderer, namespace::CONFIG_CLONE_ID)? {
+            return Ok(clone_id);
+        }
+
+        // The first pushes from hooks can run concurrently, and would otherwise each publish a
+        // different ID and then accuse each other of pushing as the same host.
+        let _lock = self.lock_pushes(renderer)?;
+        if let Some(clone_id) = self.get_config(renderer, namespace::CONFIG_CLONE_ID)? {
+            return Ok(clone_id);
+        }
+
+        let clone_id = generate_clone_id();
+        self.set_config(
+            renderer,
+            ConfigScope::Local,
+            namespace::CONFIG_CLONE_ID,
+            &clone_id,
+        )?;
+        Ok(clone_id)
+    }
+
+    /// Wraps `git fetch --prune` to fetch refs from a given remote into the local repository,
+    /// deleting local refs matched by the destination side of `refspecs` that the remote no
+    /// longer has.
+    ///
+    /// # Panics
+    ///
+    /// If `refspecs` is empty, which means git will use the user configured default behaviour
+    /// which is definitely not what we want.
+    fn fetch_refspecs<Description, RefSpec>(
         &self,
         renderer: &mut impl Renderer,
         description: Description,
@@ -374,14 +973,47 @@ impl GitBinary<'_> {
             self.verbosity,
             description,
             self.command()
-                .args(["push", "--no-verify", &remote.0])
+                .args(["fetch", "--prune", &remote.0])
+                .args(refspecs),
+        )?;
+        Ok(())
+    }
+
+    /// Wraps `git push --atomic` to push refs from the local repository into the given remote,
+    /// where either every ref is updated or none are.
+    ///
+    /// `leases` are passed through as options, see [`namespace::lease`].
+    ///
+    /// # Panics
+    ///
+    /// If `refspecs` is empty, which means git will use the user configured default behaviour
+    /// which is definitely not what we want.
+    fn push_refspecs<Description, RefSpec>(
+        &self,
+        renderer: &mut impl Renderer,
+        description: Description,
+        remote: &Remote,
+        leases: &[String],
+        refspecs: &[RefSpec],
+    ) -> Result<()>
+    where
+        Description: AsRef<str>,
+        RefSpec: AsRef<OsStr>,
+    {
+        assert!(!refspecs.is_empty());
+        run_notable(
+            renderer,
+            self.verbosity,
+            description,
+            self.command()
+                .args(["push", "--no-verify", "--atomic", "--porcelain", &remote.0])
+                .args(leases)
                 .args(refspecs),
         )?;
         Ok(())
     }
 
     /// Extract a single `GitRef` for a given `ref_name`.
-    #[cfg(test)]
     pub fn get_ref<Description, RefName>(
         &self,
         renderer: &mut impl Renderer,
@@ -402,7 +1034,9 @@ impl GitBinary<'_> {
         .and_then(output_stdout)
         .map(LineArity::from)
         .and_then(LineArity::one)
-        .and_then(|line| GitRef::parse_show_ref_line(&line).map_err(Into::into))
+        .and_then(|line| {
+            GitRef::parse_show_ref_line(&line).map_err(|err| NomadError::from(err).into())
+        })
     }
 
     /// List all the non-HEAD refs in the repository as `GitRef`s.
@@ -423,10 +1057,78 @@ impl GitBinary<'_> {
         .and_then(output_stdout)?;
         output
             .lines()
-            .map(|line| GitRef::parse_show_ref_line(line).map_err(Into::into))
+            .map(|line| {
+                GitRef::parse_show_ref_line(line).map_err(|err| NomadError::from(err).into())
+            })
             .collect()
     }
 
+    /// Wraps `git for-each-ref` to find when the commit behind every local nomad managed ref was
+    /// committed, in seconds since the Unix epoch keyed by commit ID.
+    pub fn nomad_committer_dates(
+        &self,
+        renderer: &mut impl Renderer,
+    ) -> Result<HashMap<String, u64>> {
+        let output = run_trivial(
+            renderer,
+            self.verbosity,
+            "Get commit dates",
+            self.command()
+                .args([
+                    "for-each-ref",
+                    "--format=%(objectname) %(committerdate:unix)",
+                ])
+                .args(namespace::local_patterns()),
+        )
+        .and_then(output_stdout)?;
+
+        Ok(output
+            .lines()
+            .filter_map(|line| {
+                let (commit_id, date) = line.split_once(' ')?;
+                Some((commit_id.to_string(), date.parse().ok()?))
+            })
+            .collect())
+    }
+
+    /// Wraps `git for-each-ref` to describe what every local nomad managed ref points at, keyed by
+    /// ref name.
+    ///
+    /// This is a single invocation no matter how many refs there are, instead of one `git log`
+    /// per ref.
+    pub fn nomad_ref_details(
+        &self,
+        renderer: &mut impl Renderer,
+    ) -> Result<HashMap<String, RefDetails>> {
+        let output = run_trivial(
+            renderer,
+            self.verbosity,
+            "Describing refs",
+            self.command()
+                .args([
+                    "for-each-ref",
+                    // `creatordate` is the committer date for commits and the tagger date for tags.
+                    "--format=%(refname)%00%(objectname:short)%00%(creatordate:relative)%00%(contents:subject)",
+                ])
+                .args(namespace::local_patterns()),
+        )
+        .and_then(output_stdout)?;
+
+        Ok(output
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(4, '\0');
+                let name = parts.next()?;
+                let details = RefDetails {
+                    short_id: parts.next()?.to_string(),
+                    relative_date: parts.next()?.to_string(),
+                    subject: parts.next()?.to_string(),
+                };
+                Some((name.to_string(), details))
+            })
+            .collect())
+    }
+
     /// Wraps `git ls-remote` to query a remote for all refs that match the given `refspecs`.
     ///
     /// # Panics
@@ -456,25 +1158,142 @@ impl GitBinary<'_> {
         .and_then(output_stdout)?;
         output
             .lines()
-            .map(|line| GitRef::parse_ls_remote_line(line).map_err(Into::into))
+            .map(|line| {
+                GitRef::parse_ls_remote_line(line).map_err(|err| NomadError::from(err).into())
+            })
             .collect()
     }
 
-    /// Delete a ref from the repository.
+    /// Build a `git update-ref --stdin` command that applies `instructions` in a single
+    /// transaction.
+    fn update_ref_command(
+        &self,
+        instructions: impl IntoIterator<Item = String>,
+    ) -> Result<Command> {
+        let mut command = self.command();
+        command
+            .args(["update-ref", "--stdin"])
+            .stdin(stdin_file(instructions)?);
+        Ok(command)
+    }
+
+    /// Write `clone_id` into the object database, returning the ID of the blob that a
+    /// [`namespace::clone_marker_ref`] should point to.
+    fn clone_marker(&self, renderer: &mut impl Renderer, clone_id: &str) -> Result<String> {
+        let mut command = self.command();
+        command
+            .args(["hash-object", "-w", "--stdin"])
+            .stdin(stdin_file([clone_id])?);
+        run_trivial(renderer, self.verbosity, "Write clone ID", &mut command)
+            .and_then(output_stdout)
+            .map(LineArity::from)
+            .and_then(LineArity::one)
+    }
+
+    /// Wait until no other nomad process is pushing from this repository, returning a file that
+    /// keeps the others waiting until it is dropped.
+    ///
+    /// Pushes read their leases from local refs and record what they pushed afterwards, so two at
+    /// once (like the hooks for commits in quick succession) would trip over each other's leases
+    /// and look like a host name collision. The lock is held by the OS, so it is released even if
+    /// the process dies.
+    ///
+    /// The lock is not reentrant, so it must be dropped before calling anything else that takes
+    /// it.
+    fn lock_pushes(&self, renderer: &mut impl Renderer) -> Result<File> {
+        // Linked worktrees share refs, so they must share the lock too.
+        let common_dir = run_trivial(
+            renderer,
+            self.verbosity,
+            "Resolving common .git directory",
+            self.command()
+                .current_dir(&self.git_dir)
+                .args(["rev-parse", "--git-common-dir"]),
+        )
+        .and_then(output_stdout)
+        .map(LineArity::from)
+        .and_then(LineArity::one)?;
+        let path = Path::new(&self.git_dir)
+            .join(common_dir)
+            .join(namespace::PUSH_LOCK);
+
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("opening {}", path.display()))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                renderer.spinner("Waiting for another push to finish", || {
+                    file.lock()
+                        .with_context(|| format!("locking {}", path.display()))
+                })?;
+            }
+            Err(TryLockError::Error(error)) => {
+                return Err(error).with_context(|| format!("locking {}", path.display()))
+            }
+        }
+
+        Ok(file)
+    }
+
+    /// Read back the clone ID that a [`namespace::clone_marker_ref`] points to.
+    fn read_clone_marker(&self, renderer: &mut impl Renderer, marker: &str) -> Result<String> {
+        run_trivial(
+            renderer,
+            self.verbosity,
+            "Read clone ID",
+            self.command().args(["cat-file", "blob", marker]),
+        )
+        .and_then(output_stdout)
+        .map(LineArity::from)
+        .and_then(LineArity::one)
+    }
+
+    /// Delete refs from the repository in a single transaction, which fails without deleting
+    /// anything if any ref no longer points to the expected commit.
+    ///
+    /// `tracking_ref_names` are deleted in the same transaction regardless of what they point to
+    /// (or whether they exist at all), without being mentioned in the output.
     ///
     /// Note that deleting refs on a remote is done via [`GitBinary::push_refspecs`].
-    fn delete_ref<Description>(
+    fn delete_refs<Description>(
         &self,
         renderer: &mut impl Renderer,
         description: Description,
-        git_ref: &GitRef,
+        git_refs: &[GitRef],
+        tracking_ref_names: &[String],
     ) -> Result<()>
     where
         Description: AsRef<str>,
     {
-        let mut command = self.command();
-        command.args(["update-ref", "-d", &git_ref.name, &git_ref.commit_id]);
+        if git_refs.is_empty() && tracking_ref_names.is_empty() {
+            return Ok(());
+        }
+
+        let mut command = self.update_ref_command(
+            git_refs
+                .iter()
+                .map(|git_ref| format!("delete {} {}", git_ref.name, git_ref.commit_id))
+                .chain(
+                    tracking_ref_names
+                        .iter()
+                        .map(|name| format!("delete {}", name)),
+                ),
+        )?;
         run_notable(renderer, self.verbosity, description, &mut command)?;
+
+        if self.is_output_allowed() {
+            renderer.writer(|w| {
+                for git_ref in git_refs {
+                    writeln!(w, "  Delete {} (was {})", git_ref.name, git_ref.commit_id)?;
+                }
+                Ok(())
+            })?;
+        }
+
         Ok(())
     }
 
@@ -494,6 +1313,70 @@ impl GitBinary<'_> {
         .map(Branch::from)
     }
 
+    /// Count the commits reachable from only `left` and only `right` respectively, which is the
+    /// same "ahead/behind" information that `git status` displays.
+    pub fn count_left_right(
+        &self,
+        renderer: &mut impl Renderer,
+        left: &str,
+        right: &str,
+    ) -> Result<(usize, usize)> {
+        let line = run_trivial(
+            renderer,
+            self.verbosity,
+            format!("Comparing {} with {}", left, right),
+            self.command().args([
+                "rev-list",
+                "--left-right",
+                "--count",
+                &format!("{}...{}", left, right),
+            ]),
+        )
+        .and_then(output_stdout)
+        .map(LineArity::from)
+        .and_then(LineArity::one)?;
+
+        match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [left_count, right_count] => Ok((left_count.parse()?, right_count.parse()?)),
+            _ => bail!("Unexpected rev-list output: {}", line),
+        }
+    }
+
+    /// Find the best common ancestor of two commits.
+    pub fn merge_base(
+        &self,
+        renderer: &mut impl Renderer,
+        left: &str,
+        right: &str,
+    ) -> Result<String> {
+        run_trivial(
+            renderer,
+            self.verbosity,
+            format!("Finding merge base of {} and {}", left, right),
+            self.command().args(["merge-base", left, right]),
+        )
+        .and_then(output_stdout)
+        .map(LineArity::from)
+        .and_then(LineArity::one)
+    }
+
+    /// Point `branch_name` at `commit_id` (creating it if necessary) and switch the working tree
+    /// to it.
+    ///
+    /// Callers are responsible for making sure this does not throw away any commits.
+    pub fn checkout_branch(
+        &self,
+        renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+        branch_name: &Branch,
+        commit_id: &str,
+    ) -> Result<()> {
+        let mut command = self.command();
+        command.args(["checkout", "-B", &branch_name.0, commit_id]);
+        run_notable(renderer, self.verbosity, description, &mut command)?;
+        Ok(())
+    }
+
     /// Create a git branch named `branch_name`.
     #[cfg(test)]
     pub fn create_branch(
@@ -522,146 +1405,747 @@ impl GitBinary<'_> {
         Ok(())
     }
 
+    /// The commit IDs of every `git stash` entry, newest first like `stash@{0}`.
+    pub fn stash_entries(&self, renderer: &mut impl Renderer) -> Result<Vec<String>> {
+        let output = run_trivial(
+            renderer,
+            self.verbosity,
+            "Listing stash entries",
+            self.command().args(["stash", "list", "--format=%H"]),
+        )
+        .and_then(output_stdout)?;
+        Ok(output.lines().map(String::from).collect())
+    }
+
+    /// Wraps `git stash create` to commit the index and working tree without touching either, or
+    /// HEAD. There is nothing to commit if the working tree is clean.
+    pub fn worktree_snapshot(&self, renderer: &mut impl Renderer) -> Result<Option<String>> {
+        // Unlike `git stash push`, `git stash create` does not refresh the index first. A file that
+        // was rewritten with its committed contents within the same second as the index was
+        // written looks modified, and `git stash create` then fails when it finds nothing to stash.
+        //
+        // Refreshing rewrites the index, so both run against a throwaway copy of it instead.
+        let index_path = run_trivial(
+            renderer,
+            self.verbosity,
+            "Resolving index",
+            self.command()
+                .current_dir(&self.git_dir)
+                .args(["rev-parse", "--git-path", "index"]),
+        )
+        .and_then(output_stdout)
+        .map(LineArity::from)
+        .and_then(LineArity::one)?;
+        let index_path = Path::new(&self.git_dir).join(index_path);
+
+        let index_dir = tempfile::tempdir().context("creating index copy")?;
+        let index_copy = index_dir.path().join("index");
+        if index_path.exists() {
+            fs::copy(&index_path, &index_copy)
+                .with_context(|| format!("copying {}", index_path.display()))?;
+        }
+
+        run_trivial(
+            renderer,
+            self.verbosity,
+            "Refreshing index",
+            self.command().env("GIT_INDEX_FILE", &index_copy).args([
+                "update-index",
+                "-q",
+                "--refresh",
+            ]),
+        )?;
+
+        run_trivial(
+            renderer,
+            self.verbosity,
+            "Snapshotting working tree",
+            self.command().env("GIT_INDEX_FILE", &index_copy).args([
+                "stash",
+                "create",
+                "git-nomad worktree snapshot",
+            ]),
+        )
+        .and_then(output_stdout)
+        .map(LineArity::from)
+        .and_then(LineArity::zero_or_one)
+    }
+
+    /// Whether [`GitBinary::worktree_snapshot`] would find anything to commit, without writing to
+    /// the repository at all.
+    pub fn has_worktree_changes(&self, renderer: &mut impl Renderer) -> Result<bool> {
+        let output = run_trivial(
+            renderer,
+            self.verbosity,
+            "Checking for uncommitted changes",
+            self.command().args([
+                // Otherwise `git status` may write a refreshed index.
+                "--no-optional-locks",
+                "status",
+                "--porcelain",
+                "--untracked-files=no",
+            ]),
+        )
+        .and_then(output_stdout)?;
+        Ok(!output.trim().is_empty())
+    }
+
+    /// Apply the stash-like commit `commit_id` to the working tree, without adding it to the
+    /// local stash.
+    pub fn apply_stash(
+        &self,
+        renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+        commit_id: &str,
+    ) -> Result<()> {
+        let mut command = self.command();
+        command.args(["stash", "apply", commit_id]);
+        run_notable(renderer, self.verbosity, description, &mut command)?;
+        Ok(())
+    }
+
     /// Should higher level commands be producing output, or has the user requested quiet mode?
     pub fn is_output_allowed(&self) -> bool {
         is_output_allowed(self.verbosity)
     }
 
-    /// Build a point in time snapshot for all refs that nomad cares about from the state in the
-    /// local git clone.
-    pub fn snapshot<'a>(
+    /// Fetch all nomad managed refs from a given remote, returning the set of refs that the remote
+    /// has.
+    ///
+    /// Parsing the output of `git fetch` is best avoided since it is a porcelain command. Instead,
+    /// refs are fetched with `--prune` into a namespace that only tracks this remote, which then
+    /// mirrors the remote exactly and can be read back locally. The usual local refs are updated
+    /// from there without pruning, since other remotes may still know about refs this one doesn't.
+    pub fn fetch_nomad_refs(
+        &self,
+        renderer: &mut impl Renderer,
+        user: &User,
+        remote: &Remote,
+    ) -> Result<RemoteNomadRefSet> {
+        self.fetch_refspecs(
+            renderer,
+            format!("Fetching branches from {}", remote.0),
+            remote,
+            &namespace::tracking_refspecs(user, remote)
+                .into_iter()
+                .chain([namespace::clone_marker_refspec(user, remote)])
+                .collect::<Vec<_>>(),
+        )?;
+
+        let output =
+            run_trivial(
+                renderer,
+                self.verbosity,
+                format!("Listing branches fetched from {}", remote.0),
+                self.command()
+                    .args(["for-each-ref", "--format=%(objectname) %(refname)"])
+                    .args(namespace::PREFIXES.iter().map(|prefix| {
+                        format!("{}/", namespace::tracking_namespace(prefix, remote))
+                    })),
+            )
+            .and_then(output_stdout)?;
+
+        let mut nomad_refs = Vec::new();
+        for line in output.lines() {
+            let git_ref = GitRef::parse_show_ref_line(line).map_err(NomadError::from)?;
+            if let Ok(nomad_ref) = NomadRef::<GitRef>::from_git_tracking_ref(user, remote, git_ref)
+            {
+                nomad_refs.push(nomad_ref);
+            }
+        }
+
+        if !nomad_refs.is_empty() {
+            let mut command = self.update_ref_command(nomad_refs.iter().map(|nomad_ref| {
+                format!(
+                    "update {} {}",
+                    nomad_ref.ref_.name, nomad_ref.ref_.commit_id
+                )
+            }))?;
+            run_trivial(
+                renderer,
+                self.verbosity,
+                format!("Updating branches from {}", remote.0),
+                &mut command,
+            )?;
+        }
+
+        Ok(nomad_refs.into_iter().collect())
+    }
+
+    /// List all nomad managed refs from a given remote without fetching them, which costs a
+    /// separate network round-trip via `git ls-remote`.
+    ///
+    /// Prefer the set returned by [`Self::fetch_nomad_refs`] when fetching anyways.
+    pub fn list_nomad_refs(
         &self,
         renderer: &mut impl Renderer,
-        user: &'a User,
-    ) -> Result<Snapshot<'a, GitRef>> {
-        let refs = self.list_refs(renderer, "Fetching all refs")?;
+        user: &User,
+        remote: &Remote,
+    ) -> Result<impl Iterator<Item = NomadRef<'static, GitRef>>> {
+        let remote_refs = self.list_remote_refs(
+            renderer,
+            format!("Listing branches at {}", remote.0),
+            remote,
+            &namespace::list_refspecs(user),
+        )?;
 
-        let mut local_branches = HashSet::<Branch>::new();
-        let mut nomad_refs = Vec::<NomadRef<'a, GitRef>>::new();
+        Ok(remote_refs
+            .into_iter()
+            .filter_map(|ref_| NomadRef::<GitRef>::from_git_remote_ref(ref_).ok()))
+    }
 
-        for r in refs {
-            if let Some(name) = r.name.strip_prefix("refs/heads/") {
-                local_branches.insert(Branch::from(name.to_string()));
+    /// Push the given local branches to nomad managed refs in the remote, in a single atomic push.
+    ///
+    /// Each ref is only overwritten if the remote still has the commit that this clone last pushed
+    /// or fetched there (or nothing, if it has never seen one). Anything else means that another
+    /// clone is pushing under the same host name, which is reported as an error instead of
+    /// clobbering its refs.
+    ///
+    /// The same goes for the clone marker ref that publishes `clone_id` alongside
+    /// the branches, which also catches another clone that fetched before pushing as this host.
+    ///
+    /// What this clone last saw goes stale when another clone purges this host, so a rejected push
+    /// is retried once after fetching what the remote has for this host. Only the clone marker can
+    /// then tell whether another clone has taken over the host name.
+    ///
+    /// `extra_refs` are published alongside the branches at the given commit IDs, for refs of other
+    /// [`RefKind`]s like `git stash` entries.
+    ///
+    /// Branches that do not exist are skipped, and nothing happens if there are no branches left
+    /// to push.
+    ///
+    /// Concurrent pushes from the same repository wait for each other, so that they don't trip
+    /// over each other's leases.
+    #[allow(clippy::too_many_arguments)]
+    pub fn push_nomad_refs<'b>(
+        &self,
+        renderer: &mut impl Renderer,
+        user: &User,
+        host: &Host,
+        remote: &Remote,
+        clone_id: &str,
+        branches: impl IntoIterator<Item = &'b Branch<'b>>,
+        extra_refs: &[(RefKind, Branch, String)],
+    ) -> Result<()> {
+        let _lock = self.lock_pushes(renderer)?;
+        let branches = branches.into_iter().collect::<Vec<_>>();
+        let mut refreshed = false;
+
+        let pushed = loop {
+            let refs = self
+                .list_refs(renderer, "Fetching all refs")?
+                .into_iter()
+                .map(|git_ref| (git_ref.name, git_ref.commit_id))
+                .collect::<HashMap<_, _>>();
+
+            // Clones that have never fetched into a tracking namespace only have the local nomad
+            // refs to go on, which is accurate as long as they were only ever synced with one
+            // remote.
+            let is_tracking = refreshed || refs.keys().any(|name| namespace::is_tracking_ref(name));
+
+            let mut leases = Vec::new();
+            let mut refspecs = Vec::new();
+            let mut pushed = Vec::new();
+
+            let branches = branches.iter().filter_map(|branch| {
+                let commit_id = refs.get(&format!("refs/heads/{}", branch.0))?;
+                Some((RefKind::Branch, branch.always_borrow(), commit_id))
+            });
+            let extra_refs = extra_refs
+                .iter()
+                .map(|(kind, branch, commit_id)| (*kind, branch.always_borrow(), commit_id));
+
+            for (kind, branch, commit_id) in branches.chain(extra_refs) {
+                let nomad_ref = NomadRef {
+                    user: user.always_borrow(),
+                    host: host.always_borrow(),
+                    kind,
+                    branch,
+                    ref_: (),
+                };
+                let last_known = if is_tracking {
+                    nomad_ref.to_git_tracking_ref(remote)
+                } else {
+                    nomad_ref.to_git_local_ref()
+                };
+
+                leases.push(namespace::lease(
+                    &nomad_ref.to_git_remote_ref(),
+                    refs.get(&last_known).map(String::as_str),
+                ));
+                refspecs.push(namespace::push_refspec(commit_id, &nomad_ref));
+                pushed.push(format!(
+                    "update {} {}",
+                    nomad_ref.to_git_tracking_ref(remote),
+                    commit_id
+                ));
             }
 
-            if let Ok(nomad_ref) = NomadRef::<GitRef>::from_git_local_ref(user, r) {
-                nomad_refs.push(nomad_ref);
+            if refspecs.is_empty() {
+                return Ok(());
             }
-        }
 
-        Ok(Snapshot::new(user, local_branches, nomad_refs))
+            let marker = self.clone_marker(renderer, clone_id)?;
+            let marker_ref = namespace::clone_marker_ref(user, host);
+            let tracked_marker_ref =
+                format!("{}/{}", namespace::clone_marker_namespace(remote), host.0);
+            let tracked_marker = refs.get(&tracked_marker_ref).map(String::as_str);
+
+            if let Some(other) = tracked_marker.filter(|&other| other != marker) {
+                let other_clone_id = self.read_clone_marker(renderer, other)?;
+                return Err(host_collision(remote, host, &other_clone_id));
+            }
+
+            leases.push(namespace::lease(&marker_ref, tracked_marker));
+            refspecs.push(format!("{}:{}", marker, marker_ref));
+            pushed.push(format!("update {} {}", tracked_marker_ref, marker));
+
+            match self.push_refspecs(
+                renderer,
+                format!("Pushing local branches to {}", remote.0),
+                remote,
+                &leases,
+                &refspecs,
+            ) {
+                Ok(()) => break pushed,
+                Err(error) if !refreshed && !stale_refs(&error).is_empty() => {
+                    self.fetch_refspecs(
+                        renderer,
+                        format!("Fetching host {} from {}", host.0, remote.0),
+                        remote,
+                        &namespace::host_tracking_refspecs(user, host, remote)
+                            .into_iter()
+                            .chain([namespace::clone_marker_refspec(user, remote)])
+                            .collect::<Vec<_>>(),
+                    )?;
+                    refreshed = true;
+                }
+                Err(error) => return Err(explain_host_collision(error, remote, host)),
+            }
+        };
+
+        // Record what the remote now has, so that the next push expects it.
+        let mut command = self.update_ref_command(pushed)?;
+        run_trivial(
+            renderer,
+            self.verbosity,
+            format!("Recording branches pushed to {}", remote.0),
+            &mut command,
+        )?;
+
+        Ok(())
     }
 
-    /// Fetch all nomad managed refs from a given remote.
-    pub fn fetch_nomad_refs(
+    /// Delete the clone marker ref of every host in `remote` that matches
+    /// `is_forgotten`, so that another clone can start pushing as that host.
+    ///
+    /// Only markers that were fetched are deleted, and only if they haven't changed since.
+    pub fn forget_clone_markers(
         &self,
         renderer: &mut impl Renderer,
         user: &User,
         remote: &Remote,
+        is_forgotten: impl Fn(&Host) -> bool,
     ) -> Result<()> {
-        self.fetch_refspecs(
+        let namespace = namespace::clone_marker_namespace(remote);
+        let output = run_trivial(
             renderer,
-            format!("Fetching branches from {}", remote.0),
+            self.verbosity,
+            format!("Listing hosts fetched from {}", remote.0),
+            self.command().args([
+                "for-each-ref",
+                "--format=%(objectname) %(refname)",
+                &format!("{}/", namespace),
+            ]),
+        )
+        .and_then(output_stdout)?;
+
+        let mut leases = Vec::new();
+        let mut refspecs = Vec::new();
+        let mut forgotten = Vec::new();
+
+        for line in output.lines() {
+            let git_ref = GitRef::parse_show_ref_line(line).map_err(NomadError::from)?;
+            let host = match git_ref
+                .name
+                .strip_prefix(&namespace)
+                .and_then(|rest| rest.strip_prefix('/'))
+            {
+                Some(host) => Host::from(host.to_string()),
+                None => continue,
+            };
+
+            if is_forgotten(&host) {
+                let marker_ref = namespace::clone_marker_ref(user, &host);
+                leases.push(namespace::lease(&marker_ref, Some(&git_ref.commit_id)));
+                refspecs.push(format!(":{}", marker_ref));
+                forgotten.push(format!("delete {} {}", git_ref.name, git_ref.commit_id));
+            }
+        }
+
+        if refspecs.is_empty() {
+            return Ok(());
+        }
+
+        self.push_refspecs(
+            renderer,
+            format!("Forgetting hosts at {}", remote.0),
             remote,
-            &[&namespace::fetch_refspec(user)],
+            &leases,
+            &refspecs,
+        )?;
+
+        let mut command = self.update_ref_command(forgotten)?;
+        run_trivial(
+            renderer,
+            self.verbosity,
+            format!("Forgetting hosts fetched from {}", remote.0),
+            &mut command,
+        )?;
+
+        Ok(())
+    }
+
+    /// Delete the given nomad managed refs.
+    ///
+    /// Refs pruned from [`PruneFrom::LocalAndRemote`] are deleted from every remote whose
+    /// [`RemoteNomadRefSet`] contains them, since deleting a ref that a remote does not have is an
+    /// error. Like [`Self::push_nomad_refs`], each is only deleted if the remote still has the
+    /// commit that this clone last fetched there, so that a commit another clone pushed since then
+    /// is never lost.
+    pub fn prune_nomad_refs<'a>(
+        &self,
+        renderer: &mut impl Renderer,
+        remote_nomad_refs: &[(&Remote, RemoteNomadRefSet)],
+        prune: impl Iterator<Item = PruneFrom<'a, GitRef>>,
+    ) -> Result<()> {
+        let _lock = self.lock_pushes(renderer)?;
+        let known = self
+            .list_refs(renderer, "Fetching all refs")?
+            .into_iter()
+            .map(|git_ref| (git_ref.name, git_ref.commit_id))
+            .collect::<HashMap<_, _>>();
+
+        let mut leases = vec![Vec::<String>::new(); remote_nomad_refs.len()];
+        let mut refspecs = vec![Vec::<String>::new(); remote_nomad_refs.len()];
+        let mut refs = Vec::<GitRef>::new();
+        let mut tracking_ref_names = Vec::<String>::new();
+
+        for prune_from in prune {
+            if let PruneFrom::LocalAndRemote(ref nomad_ref) = prune_from {
+                for (((remote, set), leases), refspecs) in
+                    remote_nomad_refs.iter().zip(&mut leases).zip(&mut refspecs)
+                {
+                    if set.contains(nomad_ref) {
+                        let tracking_ref_name = nomad_ref.to_git_tracking_ref(remote);
+                        leases.push(namespace::lease(
+                            &nomad_ref.to_git_remote_ref(),
+                            Some(
+                                known
+                                    .get(&tracking_ref_name)
+                                    .unwrap_or(&nomad_ref.ref_.commit_id),
+                            ),
+                        ));
+                        refspecs.push(format!(":{}", nomad_ref.to_git_remote_ref()));
+                        tracking_ref_names.push(tracking_ref_name);
+                    }
+                }
+            }
+
+            refs.push(
+                match prune_from {
+                    PruneFrom::LocalOnly(nomad_ref) | PruneFrom::LocalAndRemote(nomad_ref) => {
+                        nomad_ref
+                    }
+                }
+                .ref_,
+            );
+        }
+
+        // Delete from the remotes first
+        for (((remote, _), leases), refspecs) in remote_nomad_refs.iter().zip(leases).zip(refspecs)
+        {
+            if !refspecs.is_empty() {
+                self.push_refspecs(
+                    renderer,
+                    format!("Pruning branches at {}", remote.0),
+                    remote,
+                    &leases,
+                    &refspecs,
+                )?;
+            }
+        }
+
+        // ... then delete locally. This order means that interruptions leave the local ref around
+        // to be picked up and pruned again.
+        //
+        // In practice, we do a fetch from the remote first anyways, which would recreate the local
+        // ref if this code deleted local refs first and then was interrupted.
+        //
+        // But that is non-local reasoning and this ordering is theoretically correct.
+        self.delete_refs(
+            renderer,
+            "Pruning branches locally",
+            &refs,
+            &tracking_ref_names,
         )
     }
+}
+
+impl GitBackend for GitBinary<'_> {
+    fn is_output_allowed(&self) -> bool {
+        GitBinary::is_output_allowed(self)
+    }
+
+    fn get_config(&self, renderer: &mut impl Renderer, key: &str) -> Result<Option<String>> {
+        GitBinary::get_config(self, renderer, key)
+    }
+
+    fn get_config_bool(&self, renderer: &mut impl Renderer, key: &str) -> Result<bool> {
+        GitBinary::get_config_bool(self, renderer, key)
+    }
+
+    fn get_config_entries(
+        &self,
+        renderer: &mut impl Renderer,
+        scope: Option<ConfigScope>,
+        key: &str,
+    ) -> Result<Vec<ConfigEntry>> {
+        GitBinary::get_config_entries(self, renderer, scope, key)
+    }
+
+    fn set_config_all(
+        &self,
+        renderer: &mut impl Renderer,
+        scope: ConfigScope,
+        key: &str,
+        values: &[&str],
+    ) -> Result<()> {
+        GitBinary::set_config_all(self, renderer, scope, key, values)
+    }
+
+    fn unset_config(
+        &self,
+        renderer: &mut impl Renderer,
+        scope: ConfigScope,
+        key: &str,
+    ) -> Result<()> {
+        GitBinary::unset_config(self, renderer, scope, key)
+    }
+
+    fn clone_id(&self, renderer: &mut impl Renderer) -> Result<String> {
+        GitBinary::clone_id(self, renderer)
+    }
+
+    fn hooks_dir(&self, renderer: &mut impl Renderer) -> Result<PathBuf> {
+        GitBinary::hooks_dir(self, renderer)
+    }
+
+    fn get_ref(
+        &self,
+        renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+        ref_name: impl AsRef<str>,
+    ) -> Result<GitRef> {
+        GitBinary::get_ref(self, renderer, description, ref_name)
+    }
+
+    fn list_refs(
+        &self,
+        renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+    ) -> Result<Vec<GitRef>> {
+        GitBinary::list_refs(self, renderer, description)
+    }
+
+    fn nomad_committer_dates(&self, renderer: &mut impl Renderer) -> Result<HashMap<String, u64>> {
+        GitBinary::nomad_committer_dates(self, renderer)
+    }
+
+    fn nomad_ref_details(
+        &self,
+        renderer: &mut impl Renderer,
+    ) -> Result<HashMap<String, RefDetails>> {
+        GitBinary::nomad_ref_details(self, renderer)
+    }
+
+    fn current_branch(&self, renderer: &mut impl Renderer) -> Result<Branch<'static>> {
+        GitBinary::current_branch(self, renderer)
+    }
+
+    fn count_left_right(
+        &self,
+        renderer: &mut impl Renderer,
+        left: &str,
+        right: &str,
+    ) -> Result<(usize, usize)> {
+        GitBinary::count_left_right(self, renderer, left, right)
+    }
+
+    fn merge_base(&self, renderer: &mut impl Renderer, left: &str, right: &str) -> Result<String> {
+        GitBinary::merge_base(self, renderer, left, right)
+    }
+
+    fn checkout_branch(
+        &self,
+        renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+        branch_name: &Branch,
+        commit_id: &str,
+    ) -> Result<()> {
+        GitBinary::checkout_branch(self, renderer, description, branch_name, commit_id)
+    }
+
+    fn stash_entries(&self, renderer: &mut impl Renderer) -> Result<Vec<String>> {
+        GitBinary::stash_entries(self, renderer)
+    }
+
+    fn worktree_snapshot(&self, renderer: &mut impl Renderer) -> Result<Option<String>> {
+        GitBinary::worktree_snapshot(self, renderer)
+    }
+
+    fn has_worktree_changes(&self, renderer: &mut impl Renderer) -> Result<bool> {
+        GitBinary::has_worktree_changes(self, renderer)
+    }
+
+    fn apply_stash(
+        &self,
+        renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+        commit_id: &str,
+    ) -> Result<()> {
+        GitBinary::apply_stash(self, renderer, description, commit_id)
+    }
 
-    /// List all nomad managed refs from a given remote.
-    ///
-    /// Separated from [`Self::fetch_nomad_refs`] because not all callers want to pay the overhead
-    /// of actually listing the fetched refs.
-    pub fn list_nomad_refs(
+    fn fetch_nomad_refs(
         &self,
         renderer: &mut impl Renderer,
         user: &User,
         remote: &Remote,
-    ) -> Result<impl Iterator<Item = NomadRef<GitRef>>> {
-        // In an ideal world, we would be able to get the list of refs fetched directly from `git`.
-        //
-        // However, `git fetch` is a porcelain command and we don't want to get into parsing its
-        // output, so do an entirely separate network fetch with the plumbing `git ls-remote` which
-        // we can parse instead.
-        let remote_refs = self.list_remote_refs(
-            renderer,
-            format!("Listing branches at {}", remote.0),
-            remote,
-            &[&namespace::list_refspec(user)],
-        )?;
+    ) -> Result<RemoteNomadRefSet> {
+        GitBinary::fetch_nomad_refs(self, renderer, user, remote)
+    }
 
-        Ok(remote_refs
-            .into_iter()
-            .filter_map(|ref_| NomadRef::<GitRef>::from_git_remote_ref(ref_).ok()))
+    fn list_nomad_refs(
+        &self,
+        renderer: &mut impl Renderer,
+        user: &User,
+        remote: &Remote,
+    ) -> Result<Vec<NomadRef<'static, GitRef>>> {
+        Ok(GitBinary::list_nomad_refs(self, renderer, user, remote)?.collect())
     }
 
-    /// Push local branches to nomad managed refs in the remote.
-    pub fn push_nomad_refs(
+    fn push_nomad_refs<'b>(
         &self,
         renderer: &mut impl Renderer,
         user: &User,
         host: &Host,
         remote: &Remote,
+        clone_id: &str,
+        branches: impl IntoIterator<Item = &'b Branch<'b>>,
+        extra_refs: &[(RefKind, Branch, String)],
     ) -> Result<()> {
-        self.push_refspecs(
-            renderer,
-            format!("Pushing local branches to {}", remote.0),
-            remote,
-            &[&namespace::push_refspec(user, host)],
+        GitBinary::push_nomad_refs(
+            self, renderer, user, host, remote, clone_id, branches, extra_refs,
         )
     }
 
-    /// Delete the given nomad managed refs.
-    pub fn prune_nomad_refs<'a>(
+    fn forget_clone_markers(
         &self,
         renderer: &mut impl Renderer,
+        user: &User,
         remote: &Remote,
+        is_forgotten: impl Fn(&Host) -> bool,
+    ) -> Result<()> {
+        GitBinary::forget_clone_markers(self, renderer, user, remote, is_forgotten)
+    }
+
+    fn prune_nomad_refs<'a>(
+        &self,
+        renderer: &mut impl Renderer,
+        remote_nomad_refs: &[(&Remote, RemoteNomadRefSet)],
         prune: impl Iterator<Item = PruneFrom<'a, GitRef>>,
     ) -> Result<()> {
-        let mut refspecs = Vec::<String>::new();
-        let mut refs = Vec::<GitRef>::new();
+        GitBinary::prune_nomad_refs(self, renderer, remote_nomad_refs, prune)
+    }
+}
 
-        for prune_from in prune {
-            if let PruneFrom::LocalAndRemote(ref nomad_ref) = prune_from {
-                refspecs.push(format!(":{}", nomad_ref.to_git_remote_ref()));
-            }
+/// A file containing `lines`, rewound so that it can be used as the stdin of a command.
+///
+/// Input goes through a file since commands are run to completion with their output captured.
+fn stdin_file<Line: AsRef<str>>(lines: impl IntoIterator<Item = Line>) -> Result<File> {
+    let mut file = tempfile::tempfile().context("creating command input")?;
+    for line in lines {
+        writeln!(file, "{}", line.as_ref()).context("writing command input")?;
+    }
+    file.seek(SeekFrom::Start(0))
+        .context("rewinding command input")?;
+    Ok(file)
+}
 
-            refs.push(
-                match prune_from {
-                    PruneFrom::LocalOnly(nomad_ref) | PruneFrom::LocalAndRemote(nomad_ref) => {
-                        nomad_ref
-                    }
-                }
-                .ref_,
-            );
-        }
+/// A random ID for `nomad.cloneId`.
+fn generate_clone_id() -> String {
+    // `RandomState` is seeded from OS randomness, which is plenty to tell clones apart without
+    // pulling in another dependency.
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    format!("{:016x}", hasher.finish())
+}
 
-        // Delete from the remote first
-        if !refspecs.is_empty() {
-            self.push_refspecs(
-                renderer,
-                format!("Pruning branches at {}", remote.0),
-                remote,
-                &refspecs,
-            )?;
-        }
+/// The error for a host that the clone with `other_clone_id` is already pushing as.
+pub(crate) fn host_collision(remote: &Remote, host: &Host, other_clone_id: &str) -> anyhow::Error {
+    anyhow::Error::new(NomadError::HostNameCollision).context(format!(
+        "Host name collision at {}: host {:?} is already being pushed by another clone (with \
+         nomad.cloneId {}). Pick a distinct host for this clone with `--host` or `git config \
+         nomad.host <name>`, or run `git nomad purge --host {}` if the other clone no longer \
+         exists.",
+        remote.0, host.0, other_clone_id, host.0,
+    ))
+}
 
-        // ... then delete locally. This order means that interruptions leave the local ref around
-        // to be picked up and pruned again.
-        //
-        // In practice, we do a fetch from the remote first anyways, which would recreate the local
-        // ref if this code deleted local refs first and then was interrupted.
-        //
-        // But that is non-local reasoning and this ordering is theoretically correct.
-        for r in refs {
-            self.delete_ref(
-                renderer,
-                format!("  Delete {} (was {})", r.name, r.commit_id),
-                &r,
-            )?;
-        }
+/// The refs that a push was rejected for because the remote no longer had what this clone
+/// expected there.
+pub(crate) fn stale_refs(error: &anyhow::Error) -> Vec<String> {
+    // `git push --porcelain` reports such refs as `!\t<src>:<dst>\t[rejected] (stale info)`
+    error
+        .to_string()
+        .lines()
+        .filter(|line| line.starts_with('!') && line.ends_with("(stale info)"))
+        .filter_map(|line| {
+            let (_, dst) = line.split('\t').nth(1)?.split_once(':')?;
+            Some(dst.to_string())
+        })
+        .collect()
+}
 
-        Ok(())
+/// Explain a push that failed because the remote no longer had what this clone expected, even
+/// after fetching it again. Either the remote changed again in the meantime, or another clone is
+/// pushing under the same host name.
+///
+/// Other failures are returned unchanged.
+pub(crate) fn explain_host_collision(
+    error: anyhow::Error,
+    remote: &Remote,
+    host: &Host,
+) -> anyhow::Error {
+    let collisions = stale_refs(&error);
+    if collisions.is_empty() {
+        return error;
     }
+
+    error
+        .context(NomadError::HostNameCollision)
+        .context(format!(
+            "Host name collision at {}: {} changed since this clone last pushed or fetched it. \
+             Run `git nomad sync` again to fetch what the remote has now, or run `git nomad purge \
+             --host {}` to start over if nothing else should be pushing as this host. If another \
+             clone is pushing as host {:?}, pick a distinct host for this clone with `--host` or \
+             `git config nomad.host <name>`.",
+            remote.0,
+            collisions.join(", "),
+            host.0,
+            host.0,
+        ))
 }
 
 /// Utility to parse line based output of various `git` sub-commands.
@@ -767,17 +2251,19 @@ mod test_line_arity {
 
 #[cfg(test)]
 mod test_impl {
-    use std::{borrow::Cow, fs};
+    use std::{borrow::Cow, fs, path::Path};
 
     use tempfile::{tempdir, TempDir};
 
     use crate::{
+        error::NomadError,
+        git_backend::GitBackend,
         renderer::test::NoRenderer,
         types::Branch,
-        verbosity::{run_notable, Verbosity},
+        verbosity::{run_notable, run_trivial, Verbosity},
     };
 
-    use super::{git_command, GitBinary};
+    use super::{git_command, ConfigScope, GitBinary};
     use anyhow::Result;
 
     const INITIAL_BRANCH: &str = "branch0";
@@ -831,6 +2317,31 @@ mod test_impl {
         Ok(())
     }
 
+    /// Find the working tree when run from a subdirectory of the repo.
+    #[test]
+    fn work_tree_in_subdir() -> Result<()> {
+        let (name, tmpdir) = git_init()?;
+        let subdir = tmpdir.path().join("subdir");
+        fs::create_dir(&subdir)?;
+
+        let git = GitBinary::new(&mut NoRenderer, None, name, subdir.as_path())?;
+        assert_eq!(git.work_tree.as_deref(), tmpdir.path().to_str());
+
+        Ok(())
+    }
+
+    /// Bare repositories have no working tree, which should not prevent construction.
+    #[test]
+    fn work_tree_in_bare_repo() -> Result<()> {
+        let (name, tmpdir) = git_init()?;
+        let git_dir = tmpdir.path().join(".git");
+
+        let git = GitBinary::new(&mut NoRenderer, None, name, git_dir.as_path())?;
+        assert_eq!(git.work_tree, None);
+
+        Ok(())
+    }
+
     /// `get_config` should handle missing configuration.
     #[test]
     fn read_empty_config() -> Result<()> {
@@ -843,13 +2354,82 @@ mod test_impl {
         Ok(())
     }
 
+    /// Without `core.hooksPath`, hooks live in the `.git` directory.
+    #[test]
+    fn hooks_dir_default() -> Result<()> {
+        let (name, tmpdir) = git_init()?;
+        let git = GitBinary::new(&mut NoRenderer, None, name, tmpdir.path())?;
+
+        assert_eq!(
+            git.hooks_dir(&mut NoRenderer)?,
+            Path::new(&git.git_dir).join("hooks")
+        );
+
+        Ok(())
+    }
+
+    /// A relative `core.hooksPath` is relative to the top of the working tree.
+    #[test]
+    fn hooks_dir_configured() -> Result<()> {
+        let (name, tmpdir) = git_init()?;
+        let git = GitBinary::new(&mut NoRenderer, None, name, tmpdir.path())?;
+
+        run_trivial(
+            &mut NoRenderer,
+            None,
+            "Set core.hooksPath",
+            git.command()
+                .args(["config", "--local", "core.hooksPath", ".githooks"]),
+        )?;
+
+        assert_eq!(
+            git.hooks_dir(&mut NoRenderer)?,
+            Path::new(git.work_tree.as_ref().unwrap()).join(".githooks")
+        );
+
+        Ok(())
+    }
+
+    /// `get_config_all` should return nothing for missing configuration.
+    #[test]
+    fn read_empty_config_all() -> Result<()> {
+        let (name, tmpdir) = git_init()?;
+        let git = GitBinary::new(&mut NoRenderer, None, name, tmpdir.path())?;
+
+        let got = git.get_config_all(&mut NoRenderer, "key")?;
+        assert_eq!(got, Vec::<String>::new());
+
+        Ok(())
+    }
+
+    /// `get_config_all` should return every value in order.
+    #[test]
+    fn add_then_read_config_all() -> Result<()> {
+        let (name, tmpdir) = git_init()?;
+        let git = GitBinary::new(&mut NoRenderer, None, name, tmpdir.path())?;
+
+        git.add_config(&mut NoRenderer, ConfigScope::Local, "key", "value0")?;
+        git.add_config(&mut NoRenderer, ConfigScope::Local, "key", "value1")?;
+        git.set_config(
+            &mut NoRenderer,
+            ConfigScope::Local,
+            "otherkey",
+            "othervalue",
+        )?;
+        let got = git.get_config_all(&mut NoRenderer, "key")?;
+
+        assert_eq!(got, vec!["value0".to_string(), "value1".to_string()]);
+
+        Ok(())
+    }
+
     /// Verify read-your-writes.
     #[test]
     fn write_then_read_config() -> Result<()> {
         let (name, tmpdir) = git_init()?;
         let git = GitBinary::new(&mut NoRenderer, None, name, tmpdir.path())?;
 
-        git.set_config(&mut NoRenderer, "key", "testvalue")?;
+        git.set_config(&mut NoRenderer, ConfigScope::Local, "key", "testvalue")?;
         let got = git.get_config(&mut NoRenderer, "key")?;
 
         assert_eq!(got, Some("testvalue".to_string()));
@@ -960,8 +2540,11 @@ mod test_impl {
             git.command().args(["checkout", &head.commit_id]),
         )?;
 
-        let branch_result = git.current_branch(&mut NoRenderer);
-        assert!(branch_result.is_err());
+        let error = git.current_branch(&mut NoRenderer).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<NomadError>(),
+            Some(&NomadError::DetachedHead)
+        );
 
         Ok(())
     }
@@ -970,12 +2553,21 @@ mod test_impl {
 #[cfg(test)]
 mod test_backend {
     use crate::{
+        error::NomadError,
+        git_backend::GitBackend,
+        git_ref::GitRef,
         git_testing::{GitCommitId, GitRemote, INITIAL_BRANCH},
-        verbosity::Verbosity,
+        renderer::test::NoRenderer,
+        snapshot::PruneFrom,
+        verbosity::{run_notable, Verbosity},
     };
     use std::{collections::HashSet, iter::FromIterator};
 
-    use crate::types::NomadRef;
+    use super::explain_host_collision;
+
+    use crate::types::{Branch, Host, NomadRef, RefKind, Remote};
+
+    const NESTED_BRANCH: &str = "feature/login";
 
     /// Push should put local branches to remote `refs/nomad/{user}/{host}/{branch}`
     #[test]
@@ -990,6 +2582,153 @@ mod test_backend {
         );
     }
 
+    /// Push should handle branch names that contain slashes.
+    #[test]
+    fn push_nested_branch() {
+        let origin = GitRemote::init(None);
+        let host0 = origin.clone("user0", "host0");
+        host0
+            .git
+            .create_branch(
+                &mut NoRenderer,
+                "Create nested branch",
+                &Branch::from(NESTED_BRANCH),
+            )
+            .unwrap();
+        host0.push();
+
+        assert_eq!(
+            origin.nomad_refs(),
+            HashSet::from_iter([
+                host0.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+                host0.get_nomad_ref(NESTED_BRANCH).unwrap(),
+            ]),
+        );
+    }
+
+    /// Pushing again should overwrite the remote ref even if history was rewritten, since this
+    /// clone knows what it pushed last time.
+    #[test]
+    fn push_rewritten_history() {
+        let origin = GitRemote::init(None);
+        let host0 = origin.clone("user0", "host0");
+        host0.commit("First");
+        host0.push();
+
+        run_notable(
+            &mut NoRenderer,
+            None,
+            "Rewind",
+            host0.git.command().args(["reset", "--hard", "HEAD~1"]),
+        )
+        .unwrap();
+        let rewritten = host0.commit("Rewritten");
+        host0.push();
+
+        assert_eq!(
+            origin
+                .nomad_refs()
+                .into_iter()
+                .map(|nomad_ref| nomad_ref.ref_)
+                .collect::<Vec<_>>(),
+            vec![rewritten],
+        );
+    }
+
+    /// Pushing over refs that some other clone pushed under the same host name should fail
+    /// without touching the remote, even after fetching them.
+    #[test]
+    fn push_host_collision() {
+        let origin = GitRemote::init(None);
+        let host0 = origin.clone("user0", "host0");
+        host0.push();
+        let before = origin.nomad_refs();
+
+        let mut impostor = origin.clone("user0", "impostor");
+        impostor.host = Host::from("host0");
+        impostor.commit("Clobber");
+        let branches = impostor
+            .git
+            .snapshot(&mut NoRenderer, &impostor.user)
+            .unwrap()
+            .local_branches;
+        let clone_id = impostor.git.clone_id(&mut NoRenderer).unwrap();
+        let push = || {
+            impostor.git.push_nomad_refs(
+                &mut NoRenderer,
+                &impostor.user,
+                &impostor.host,
+                &impostor.remote,
+                &clone_id,
+                &branches,
+                &[],
+            )
+        };
+
+        let error = push().unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("Host name collision at origin: "),
+            "{:#}",
+            error
+        );
+        assert_eq!(origin.nomad_refs(), before);
+
+        impostor.fetch();
+        let error = push().unwrap_err();
+        assert!(
+            error.to_string().starts_with(&format!(
+                "Host name collision at origin: host \"host0\" is already being pushed by another \
+                 clone (with nomad.cloneId {})",
+                host0.git.clone_id(&mut NoRenderer).unwrap()
+            )),
+            "{:#}",
+            error
+        );
+        assert_eq!(origin.nomad_refs(), before);
+    }
+
+    /// A push that is still rejected after fetching again should suggest syncing or purging
+    /// before renaming the host, since the remote may just have changed in the meantime.
+    #[test]
+    fn explain_stale_push() {
+        let error = anyhow::anyhow!(
+            "Pushing local branches to origin\n\
+             !\tabc123:refs/nomad/user0/host0/master\t[rejected] (stale info)"
+        );
+        let error = explain_host_collision(error, &Remote::from("origin"), &Host::from("host0"));
+
+        assert_eq!(
+            error.downcast_ref::<NomadError>(),
+            Some(&NomadError::HostNameCollision)
+        );
+        let message = error.to_string();
+        assert!(
+            message.starts_with(
+                "Host name collision at origin: refs/nomad/user0/host0/master changed since this \
+                 clone last pushed or fetched it."
+            ),
+            "{}",
+            message
+        );
+        let suggests = |text: &str| message.find(text).unwrap();
+        assert!(suggests("git nomad sync") < suggests("distinct host"));
+        assert!(suggests("git nomad purge --host host0") < suggests("distinct host"));
+    }
+
+    /// The clone ID should be generated once and then stay the same.
+    #[test]
+    fn clone_id_is_stable() {
+        let origin = GitRemote::init(None);
+        let host0 = origin.clone("user0", "host0");
+        let host1 = origin.clone("user0", "host1");
+
+        let clone_id = host0.git.clone_id(&mut NoRenderer).unwrap();
+        assert_eq!(host0.git.clone_id(&mut NoRenderer).unwrap(), clone_id);
+        assert_ne!(host1.git.clone_id(&mut NoRenderer).unwrap(), clone_id);
+    }
+
     /// Fetch should pull refs for all hosts that have pushed under the configured user under
     /// `refs/nomad/{host}/{branch}`
     #[test]
@@ -1016,6 +2755,44 @@ mod test_backend {
         );
     }
 
+    /// Fetch should report exactly the refs the remote has, including after refs are deleted from
+    /// the remote, but leave pruning the corresponding local refs to the caller.
+    #[test]
+    fn fetch_reports_remote_refs() {
+        let origin = GitRemote::init(None);
+        let host0 = origin.clone("user0", "host0");
+        host0
+            .git
+            .create_branch(
+                &mut NoRenderer,
+                "Create nested branch",
+                &Branch::from(NESTED_BRANCH),
+            )
+            .unwrap();
+        host0.push();
+
+        let host1 = origin.clone("user0", "host1");
+        let fetched = host1
+            .git
+            .fetch_nomad_refs(&mut NoRenderer, &host1.user, &host1.remote)
+            .unwrap();
+        let nested_ref = host0.get_nomad_ref(NESTED_BRANCH).unwrap();
+        assert!(fetched.contains(&host0.get_nomad_ref(INITIAL_BRANCH).unwrap()));
+        assert!(fetched.contains(&nested_ref));
+        assert_eq!(host1.nomad_refs().len(), 2);
+
+        host0.fetch();
+        host0.prune_local_and_remote([NESTED_BRANCH]);
+
+        let fetched = host1
+            .git
+            .fetch_nomad_refs(&mut NoRenderer, &host1.user, &host1.remote)
+            .unwrap();
+        assert!(fetched.contains(&host0.get_nomad_ref(INITIAL_BRANCH).unwrap()));
+        assert!(!fetched.contains(&nested_ref));
+        assert_eq!(host1.nomad_refs().len(), 2);
+    }
+
     /// Pushing should create nomad refs in the remote.
     /// Fetching should create nomad refs locally.
     /// Pruning should delete refs in the local and remote.
@@ -1052,4 +2829,162 @@ mod test_backend {
         assert_eq!(origin.nomad_refs(), HashSet::new());
         assert_eq!(host0.nomad_refs(), HashSet::new());
     }
+
+    /// Pruning many refs should delete all of them.
+    #[test]
+    fn prune_many() {
+        let origin = GitRemote::init(None);
+        let host0 = origin.clone("user0", "host0");
+        host0
+            .git
+            .create_branch(
+                &mut NoRenderer,
+                "Create nested branch",
+                &Branch::from(NESTED_BRANCH),
+            )
+            .unwrap();
+        host0.push();
+        host0.fetch();
+        assert_eq!(host0.nomad_refs().len(), 2);
+
+        host0.prune_local_and_remote([INITIAL_BRANCH, NESTED_BRANCH]);
+        assert_eq!(origin.nomad_refs(), HashSet::new());
+        assert_eq!(host0.nomad_refs(), HashSet::new());
+
+        // The refs that tracked what the remote had should be gone too
+        let leftover = host0
+            .git
+            .list_refs(&mut NoRenderer, "")
+            .unwrap()
+            .into_iter()
+            .filter(|git_ref| git_ref.name.starts_with("refs/nomad-remotes/"))
+            .collect::<Vec<_>>();
+        assert_eq!(leftover, Vec::new());
+    }
+
+    /// Fetching from a remote whose name has a `/` should neither see nor prune the refs fetched
+    /// from the remote named by its prefix, and vice versa.
+    #[test]
+    fn fetch_nested_remote_names() {
+        let origin = GitRemote::init(None);
+        let nested = GitRemote::init(None);
+        let host0 = origin.clone("user0", "host0");
+        let host1 = nested.clone("user0", "host1");
+        host0.push();
+        host1.push();
+
+        let nested_remote = host0.add_remote("origin/x", &nested);
+        let fetch = |remote: &Remote| {
+            host0
+                .git
+                .fetch_nomad_refs(&mut NoRenderer, &host0.user, remote)
+                .unwrap();
+        };
+        let tracked_hosts = |remote: &Remote| {
+            host0
+                .git
+                .list_refs(&mut NoRenderer, "List tracking refs")
+                .unwrap()
+                .into_iter()
+                .filter_map(|git_ref| {
+                    NomadRef::<GitRef>::from_git_tracking_ref(&host0.user, remote, git_ref).ok()
+                })
+                .map(|nomad_ref| nomad_ref.host.0.into_owned())
+                .collect::<HashSet<_>>()
+        };
+
+        fetch(&host0.remote);
+        fetch(&nested_remote);
+        assert_eq!(
+            tracked_hosts(&host0.remote),
+            HashSet::from(["host0".to_string()])
+        );
+
+        // Fetching with `--prune` again should leave the refs of the other remote alone
+        fetch(&host0.remote);
+        assert_eq!(
+            tracked_hosts(&nested_remote),
+            HashSet::from(["host1".to_string()])
+        );
+    }
+
+    /// Pruning should not delete a remote ref that another clone pushed to since it was fetched.
+    #[test]
+    fn prune_keeps_newer_remote_refs() {
+        let origin = GitRemote::init(None);
+        let host0 = origin.clone("user0", "host0");
+        let host1 = origin.clone("user0", "host1");
+        host0.push();
+
+        let remote_nomad_refs = host1
+            .git
+            .fetch_nomad_refs(&mut NoRenderer, &host1.user, &host1.remote)
+            .unwrap();
+        let snapshot = host1.git.snapshot(&mut NoRenderer, &host1.user).unwrap();
+
+        host0.commit("Move on");
+        host0.push();
+        let before = origin.nomad_refs();
+
+        let result = host1.git.prune_nomad_refs(
+            &mut NoRenderer,
+            &[(&host1.remote, remote_nomad_refs)],
+            snapshot
+                .prune_by_hosts(|host| host == &host0.host)
+                .into_iter(),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(origin.nomad_refs(), before);
+    }
+
+    /// Local pruning should delete nothing if any ref has moved since it was read.
+    #[test]
+    fn prune_is_atomic() {
+        let origin = GitRemote::init(None);
+        let host0 = origin.clone("user0", "host0");
+        host0
+            .git
+            .create_branch(
+                &mut NoRenderer,
+                "Create nested branch",
+                &Branch::from(NESTED_BRANCH),
+            )
+            .unwrap();
+        host0.push();
+        host0.fetch();
+
+        let snapshot = host0.git.snapshot(&mut NoRenderer, &host0.user).unwrap();
+
+        // Move one of the refs out from under the snapshot
+        let moved = host0.commit("Move on");
+        let nested_ref = NomadRef::<()> {
+            user: host0.user.always_borrow(),
+            host: host0.host.always_borrow(),
+            kind: RefKind::Branch,
+            branch: Branch::from(NESTED_BRANCH),
+            ref_: (),
+        }
+        .to_git_local_ref();
+        run_notable(
+            &mut NoRenderer,
+            None,
+            "Move nested ref",
+            host0
+                .git
+                .command()
+                .args(["update-ref", &nested_ref, &moved.0]),
+        )
+        .unwrap();
+        let before = host0.nomad_refs();
+
+        let result = host0.git.prune_nomad_refs(
+            &mut NoRenderer,
+            &[],
+            snapshot.nomad_refs.into_iter().map(PruneFrom::LocalOnly),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(host0.nomad_refs(), before);
+    }
 }
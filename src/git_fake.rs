@@ -0,0 +1,1312 @@
+//! An in-memory stand-in for [`crate::GitBinary`], so that workflow scenarios can be tested in
+//! milliseconds without spawning `git` or touching the filesystem.
+//!
+//! [`FakeRemote`] and [`FakeClone`] mirror [`crate::git_testing::GitRemote`] and
+//! [`crate::git_testing::GitClone`], and [`FakeGit`] implements [`GitBackend`] with the same ref
+//! layout as the real thing.
+//!
+//! Pushing, leases, clone markers, and pruning are modelled here rather than shared with
+//! [`crate::GitBinary`], so passing against the fake says nothing about how `git` itself handles
+//! `--force-with-lease`, `--atomic`, or concurrent pushes. Tests of those belong with the real
+//! backends, like `push_host_collision` in [`crate::git_binary`] and the hooks integration test.
+
+use std::{
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    error::NomadError,
+    git_backend::GitBackend,
+    git_binary::{
+        explain_host_collision, host_collision, namespace, stale_refs, ConfigEntry, ConfigScope,
+        RefDetails,
+    },
+    git_ref::GitRef,
+    git_testing::{GitCommitId, INITIAL_BRANCH},
+    renderer::{test::NoRenderer, Renderer},
+    snapshot::PruneFrom,
+    types::{Branch, Host, NomadRef, RefKind, Remote, RemoteNomadRefSet, User},
+};
+
+const ORIGIN: &str = "origin";
+
+/// Commit IDs count up from here, across every fake repository since tests run in parallel.
+static NEXT_COMMIT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Everything an object ID can refer to.
+#[derive(Clone, Debug)]
+enum Object {
+    Commit(Commit),
+    /// Only used for the clone IDs behind [`namespace::clone_marker_ref`]s.
+    Blob(String),
+}
+
+#[derive(Clone, Debug)]
+struct Commit {
+    parents: Vec<String>,
+    /// Seconds since the Unix epoch.
+    date: u64,
+    subject: String,
+    /// The uncommitted changes that a stash-like commit captured.
+    changes: Option<String>,
+}
+
+type Objects = HashMap<String, Object>;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn new_commit(objects: &mut Objects, commit: Commit) -> String {
+    let commit_id = format!("{:040x}", NEXT_COMMIT_ID.fetch_add(1, Ordering::Relaxed));
+    objects.insert(commit_id.clone(), Object::Commit(commit));
+    commit_id
+}
+
+/// Like `git hash-object -w`, the same contents always produce the same ID.
+fn new_blob(objects: &mut Objects, contents: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    // Shifted well clear of the commit IDs, which count up from 1.
+    let blob_id = format!("{:040x}", u128::from(hasher.finish()) << 64);
+    objects.insert(blob_id.clone(), Object::Blob(contents.to_string()));
+    blob_id
+}
+
+/// Copy `ids` and every commit reachable from them from `from` into `to`, like fetching or
+/// pushing them does.
+fn transfer<'a>(from: &Objects, to: &mut Objects, ids: impl IntoIterator<Item = &'a String>) {
+    let mut pending = ids.into_iter().cloned().collect::<Vec<_>>();
+    while let Some(id) = pending.pop() {
+        if to.contains_key(&id) {
+            continue;
+        }
+        if let Some(object) = from.get(&id) {
+            if let Object::Commit(commit) = object {
+                pending.extend(commit.parents.iter().cloned());
+            }
+            to.insert(id, object.clone());
+        }
+    }
+}
+
+/// `commit_id` and every commit reachable from it.
+fn ancestors(objects: &Objects, commit_id: &str) -> HashSet<String> {
+    let mut seen = HashSet::new();
+    let mut pending = vec![commit_id.to_string()];
+    while let Some(id) = pending.pop() {
+        if let Some(Object::Commit(commit)) = objects.get(&id) {
+            pending.extend(commit.parents.iter().cloned());
+        }
+        seen.insert(id);
+    }
+    seen
+}
+
+/// Roughly how `git` describes a date relative to now, like `3 days ago`.
+fn relative_date(date: u64) -> String {
+    let seconds = now().saturating_sub(date);
+    match seconds {
+        0..=89 => format!("{} seconds ago", seconds),
+        90..=5399 => format!("{} minutes ago", (seconds + 30) / 60),
+        5400..=129_599 => format!("{} hours ago", (seconds + 30 * 60) / (60 * 60)),
+        _ => format!("{} days ago", (seconds + 12 * 60 * 60) / (24 * 60 * 60)),
+    }
+}
+
+/// Ways that a [`FakeRemote`] can be made to fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemoteFailure {
+    /// Fetching, listing, and pushing all fail as if the remote could not be contacted.
+    Unreachable,
+    /// Fetching and listing work, but every push is refused.
+    RejectPushes,
+}
+
+#[derive(Default)]
+struct RemoteRepo {
+    refs: BTreeMap<String, String>,
+    objects: Objects,
+    failure: Option<RemoteFailure>,
+}
+
+/// Simulates a git remote in memory.
+pub struct FakeRemote {
+    repo: Rc<RefCell<RemoteRepo>>,
+}
+
+impl FakeRemote {
+    /// Initializes a remote with a single commit on [`INITIAL_BRANCH`].
+    pub fn init() -> FakeRemote {
+        let mut repo = RemoteRepo::default();
+        let commit_id = new_commit(
+            &mut repo.objects,
+            Commit {
+                parents: Vec::new(),
+                date: now(),
+                subject: "commit0".to_string(),
+                changes: None,
+            },
+        );
+        repo.refs
+            .insert(format!("refs/heads/{}", INITIAL_BRANCH), commit_id);
+
+        FakeRemote {
+            repo: Rc::new(RefCell::new(repo)),
+        }
+    }
+
+    /// Creates a clone that can act like a [`Host`], with [`INITIAL_BRANCH`] checked out.
+    pub fn clone(&self, user: &'static str, host: &'static str) -> FakeClone {
+        let clone = FakeClone {
+            remote: Remote::from(ORIGIN),
+            user: User::from(user),
+            host: Host::from(host),
+            git: FakeGit {
+                output_allowed: false,
+                repo: RefCell::new(LocalRepo {
+                    refs: BTreeMap::new(),
+                    objects: Objects::new(),
+                    head: Head::Branch(INITIAL_BRANCH.to_string()),
+                    config: Vec::new(),
+                    remotes: BTreeMap::new(),
+                    stash: Vec::new(),
+                    changes: None,
+                }),
+            },
+        };
+        clone.add_remote(ORIGIN, self);
+
+        let mut repo = clone.git.repo.borrow_mut();
+        let commit_id = repo.refs[&format!("refs/remotes/{}/{}", ORIGIN, INITIAL_BRANCH)].clone();
+        repo.refs
+            .insert(format!("refs/heads/{}", INITIAL_BRANCH), commit_id);
+        drop(repo);
+
+        clone
+    }
+
+    /// Make every following operation against this remote fail, or stop failing with `None`.
+    pub fn fail_with(&self, failure: Option<RemoteFailure>) {
+        self.repo.borrow_mut().failure = failure;
+    }
+
+    /// List all nomad managed refs in the remote.
+    pub fn nomad_refs(&self) -> HashSet<NomadRef<'static, GitCommitId>> {
+        self.repo
+            .borrow()
+            .refs
+            .iter()
+            .filter_map(|(name, commit_id)| {
+                NomadRef::<GitRef>::from_git_remote_ref(GitRef {
+                    commit_id: commit_id.clone(),
+                    name: name.clone(),
+                })
+                .ok()
+                .map(Into::into)
+            })
+            .collect()
+    }
+}
+
+/// What `HEAD` points to.
+enum Head {
+    Branch(String),
+    Detached(String),
+}
+
+struct LocalRepo {
+    refs: BTreeMap<String, String>,
+    objects: Objects,
+    head: Head,
+    /// Every `(scope, key, value)` in the order that they were set, where the key is within the
+    /// `nomad` section.
+    config: Vec<(ConfigScope, String, String)>,
+    remotes: BTreeMap<String, Rc<RefCell<RemoteRepo>>>,
+    /// Newest first, like `stash@{0}`.
+    stash: Vec<String>,
+    /// Uncommitted changes in the working tree, if there are any.
+    changes: Option<String>,
+}
+
+impl LocalRepo {
+    fn head_commit(&self) -> String {
+        match &self.head {
+            Head::Branch(branch) => self.refs[&format!("refs/heads/{}", branch)].clone(),
+            Head::Detached(commit_id) => commit_id.clone(),
+        }
+    }
+
+    /// Make a new commit on top of `HEAD` and move `HEAD` (or the branch it points to) there.
+    fn commit(&mut self, subject: &str, date: u64) -> String {
+        let parent = self.head_commit();
+        let commit_id = new_commit(
+            &mut self.objects,
+            Commit {
+                parents: vec![parent],
+                date,
+                subject: subject.to_string(),
+                changes: None,
+            },
+        );
+        match &mut self.head {
+            Head::Branch(branch) => {
+                self.refs
+                    .insert(format!("refs/heads/{}", branch), commit_id.clone());
+            }
+            Head::Detached(head) => *head = commit_id.clone(),
+        }
+        commit_id
+    }
+
+    /// Resolve a commit ID, branch, or full ref name to a commit ID.
+    fn resolve(&self, rev: &str) -> Result<String> {
+        if self.objects.contains_key(rev) {
+            return Ok(rev.to_string());
+        }
+        [rev.to_string(), format!("refs/heads/{}", rev)]
+            .iter()
+            .find_map(|name| self.refs.get(name))
+            .cloned()
+            .with_context(|| format!("unknown revision {}", rev))
+    }
+
+    /// Every value of `key`, global ones first like git reads them.
+    fn config_entries(&self, scope: Option<ConfigScope>, key: &str) -> Vec<(ConfigScope, &str)> {
+        let mut entries = self
+            .config
+            .iter()
+            .filter(|(entry_scope, entry_key, _)| {
+                scope.is_none_or(|scope| scope == *entry_scope)
+                    && entry_key.eq_ignore_ascii_case(key)
+            })
+            .map(|(scope, _, value)| (*scope, value.as_str()))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(scope, _)| *scope == ConfigScope::Local);
+        entries
+    }
+
+    /// Look up `remote`, failing like `git` does when it cannot be contacted.
+    fn remote(&self, description: &str, remote: &Remote) -> Result<Rc<RefCell<RemoteRepo>>> {
+        let unreachable = |why: &str| {
+            Err(anyhow::Error::new(NomadError::RemoteUnreachable)
+                .context(format!("{}: {} {}", description, remote.0, why)))
+        };
+
+        match self.remotes.get(remote.0.as_ref()) {
+            None => unreachable("does not appear to be a git repository"),
+            Some(repo) if repo.borrow().failure == Some(RemoteFailure::Unreachable) => {
+                unreachable("could not be reached")
+            }
+            Some(repo) => Ok(Rc::clone(repo)),
+        }
+    }
+
+    /// Like `git fetch --prune` with a single `+<src>/*:<dst>/*` refspec.
+    fn fetch_refspec(&mut self, remote: &RemoteRepo, refspec: &str) {
+        let (src, dst) = refspec
+            .trim_start_matches('+')
+            .split_once(':')
+            .expect("refspec has a destination");
+        let (src, dst) = (src.trim_end_matches('*'), dst.trim_end_matches('*'));
+
+        let fetched = remote
+            .refs
+            .iter()
+            .filter_map(|(name, commit_id)| {
+                let rest = name.strip_prefix(src)?;
+                Some((format!("{}{}", dst, rest), commit_id.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        transfer(
+            &remote.objects,
+            &mut self.objects,
+            fetched.iter().map(|(_, commit_id)| commit_id),
+        );
+        self.refs.retain(|name, _| !name.starts_with(dst));
+        self.refs.extend(fetched);
+    }
+
+    /// Like `git push --atomic --porcelain`, with a `--force-with-lease` for every
+    /// `(ref, expected)` in `leases`. A `None` in `updates` deletes the ref.
+    ///
+    /// Refused refs are reported in the same format as `git push --porcelain`, so that
+    /// [`explain_host_collision`] can make sense of them.
+    fn push(
+        &self,
+        description: &str,
+        remote: &Remote,
+        leases: &[(String, Option<String>)],
+        updates: &[(String, Option<String>)],
+    ) -> Result<()> {
+        let remote_repo = self.remote(description, remote)?;
+        let mut remote_repo = remote_repo.borrow_mut();
+
+        if remote_repo.failure == Some(RemoteFailure::RejectPushes) {
+            return Err(anyhow::Error::new(NomadError::PushRejected)
+                .context(format!("{}: {} refused the push", description, remote.0)));
+        }
+
+        let source = |name: &str| {
+            updates
+                .iter()
+                .find(|(update, _)| update == name)
+                .and_then(|(_, commit_id)| commit_id.clone())
+                .unwrap_or_default()
+        };
+        let stale = leases
+            .iter()
+            .filter(|(name, expected)| remote_repo.refs.get(name) != expected.as_ref())
+            .map(|(name, _)| format!("!\t{}:{}\t[rejected] (stale info)", source(name), name));
+        let missing = updates
+            .iter()
+            .filter(|(name, commit_id)| commit_id.is_none() && !remote_repo.refs.contains_key(name))
+            .map(|(name, _)| format!("!\t:{}\t[remote rejected] (no such ref)", name));
+        let rejected = stale.chain(missing).collect::<Vec<_>>();
+
+        if !rejected.is_empty() {
+            return Err(
+                anyhow::Error::new(NomadError::PushRejected).context(format!(
+                    "{}\n{}",
+                    description,
+                    rejected.join("\n")
+                )),
+            );
+        }
+
+        transfer(
+            &self.objects,
+            &mut remote_repo.objects,
+            updates
+                .iter()
+                .filter_map(|(_, commit_id)| commit_id.as_ref()),
+        );
+        for (name, commit_id) in updates {
+            match commit_id {
+                Some(commit_id) => remote_repo.refs.insert(name.clone(), commit_id.clone()),
+                None => remote_repo.refs.remove(name),
+            };
+        }
+
+        Ok(())
+    }
+}
+
+/// Implements [`GitBackend`] in memory.
+pub struct FakeGit {
+    /// Whether workflows should print anything, like a [`crate::GitBinary`] with some verbosity.
+    pub output_allowed: bool,
+    repo: RefCell<LocalRepo>,
+}
+
+impl GitBackend for FakeGit {
+    fn is_output_allowed(&self) -> bool {
+        self.output_allowed
+    }
+
+    fn get_config(&self, _renderer: &mut impl Renderer, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .repo
+            .borrow()
+            .config_entries(None, key)
+            .last()
+            .map(|(_, value)| value.to_string()))
+    }
+
+    fn get_config_bool(&self, renderer: &mut impl Renderer, key: &str) -> Result<bool> {
+        match self.get_config(renderer, key)?.as_deref() {
+            None | Some("false" | "no" | "off" | "0" | "") => Ok(false),
+            Some("true" | "yes" | "on" | "1") => Ok(true),
+            Some(value) => bail!(
+                "bad boolean config value '{}' for '{}'",
+                value,
+                namespace::config_key(key)
+            ),
+        }
+    }
+
+    fn get_config_entries(
+        &self,
+        _renderer: &mut impl Renderer,
+        scope: Option<ConfigScope>,
+        key: &str,
+    ) -> Result<Vec<ConfigEntry>> {
+        Ok(self
+            .repo
+            .borrow()
+            .config_entries(scope, key)
+            .into_iter()
+            .map(|(scope, value)| ConfigEntry {
+                origin: match scope {
+                    ConfigScope::Local => ".git/config",
+                    ConfigScope::Global => "~/.gitconfig",
+                }
+                .to_string(),
+                value: value.to_string(),
+            })
+            .collect())
+    }
+
+    fn set_config_all(
+        &self,
+        _renderer: &mut impl Renderer,
+        scope: ConfigScope,
+        key: &str,
+        values: &[&str],
+    ) -> Result<()> {
+        assert!(!values.is_empty(), "at least one value");
+        let mut repo = self.repo.borrow_mut();
+        repo.config.retain(|(entry_scope, entry_key, _)| {
+            *entry_scope != scope || !entry_key.eq_ignore_ascii_case(key)
+        });
+        repo.config.extend(
+            values
+                .iter()
+                .map(|value| (scope, key.to_string(), value.to_string())),
+        );
+        Ok(())
+    }
+
+    fn unset_config(
+        &self,
+        _renderer: &mut impl Renderer,
+        scope: ConfigScope,
+        key: &str,
+    ) -> Result<()> {
+        let mut repo = self.repo.borrow_mut();
+        if repo.config_entries(Some(scope), key).is_empty() {
+            bail!(
+                "{} is not set in {} git config",
+                namespace::config_key(key),
+                scope.name()
+            );
+        }
+        repo.config.retain(|(entry_scope, entry_key, _)| {
+            *entry_scope != scope || !entry_key.eq_ignore_ascii_case(key)
+        });
+        Ok(())
+    }
+
+    fn clone_id(&self, renderer: &mut impl Renderer) -> Result<String> {
+        if let Some(clone_id) = self.get_config(renderer, namespace::CONFIG_CLONE_ID)? {
+            return Ok(clone_id);
+        }
+
+        let clone_id = format!("{:016x}", NEXT_COMMIT_ID.fetch_add(1, Ordering::Relaxed));
+        self.set_config_all(
+            renderer,
+            ConfigScope::Local,
+            namespace::CONFIG_CLONE_ID,
+            &[&clone_id],
+        )?;
+        Ok(clone_id)
+    }
+
+    fn hooks_dir(&self, _renderer: &mut impl Renderer) -> Result<PathBuf> {
+        bail!("Fake repositories have no hooks directory")
+    }
+
+    fn get_ref(
+        &self,
+        _renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+        ref_name: impl AsRef<str>,
+    ) -> Result<GitRef> {
+        let ref_name = ref_name.as_ref();
+        match self.repo.borrow().refs.get(ref_name) {
+            Some(commit_id) => Ok(GitRef {
+                commit_id: commit_id.clone(),
+                name: ref_name.to_string(),
+            }),
+            None => bail!("{}: '{}' - not a valid ref", description.as_ref(), ref_name),
+        }
+    }
+
+    fn list_refs(
+        &self,
+        _renderer: &mut impl Renderer,
+        _description: impl AsRef<str>,
+    ) -> Result<Vec<GitRef>> {
+        Ok(self
+            .repo
+            .borrow()
+            .refs
+            .iter()
+            .map(|(name, commit_id)| GitRef {
+                commit_id: commit_id.clone(),
+                name: name.clone(),
+            })
+            .collect())
+    }
+
+    fn nomad_committer_dates(&self, _renderer: &mut impl Renderer) -> Result<HashMap<String, u64>> {
+        let repo = self.repo.borrow();
+        let patterns = namespace::local_patterns();
+        Ok(repo
+            .refs
+            .iter()
+            .filter(|(name, _)| patterns.iter().any(|pattern| name.starts_with(pattern)))
+            .filter_map(|(_, commit_id)| match repo.objects.get(commit_id)? {
+                Object::Commit(commit) => Some((commit_id.clone(), commit.date)),
+                Object::Blob(_) => None,
+            })
+            .collect())
+    }
+
+    fn nomad_ref_details(
+        &self,
+        _renderer: &mut impl Renderer,
+    ) -> Result<HashMap<String, RefDetails>> {
+        let repo = self.repo.borrow();
+        let patterns = namespace::local_patterns();
+        Ok(repo
+            .refs
+            .iter()
+            .filter(|(name, _)| patterns.iter().any(|pattern| name.starts_with(pattern)))
+            .filter_map(|(name, commit_id)| match repo.objects.get(commit_id)? {
+                Object::Commit(commit) => Some((
+                    name.clone(),
+                    RefDetails {
+                        short_id: commit_id[..7].to_string(),
+                        relative_date: relative_date(commit.date),
+                        subject: commit.subject.clone(),
+                    },
+                )),
+                Object::Blob(_) => None,
+            })
+            .collect())
+    }
+
+    fn current_branch(&self, _renderer: &mut impl Renderer) -> Result<Branch<'static>> {
+        match &self.repo.borrow().head {
+            Head::Branch(branch) => Ok(Branch::from(branch.clone())),
+            Head::Detached(_) => Err(anyhow::Error::new(NomadError::DetachedHead)
+                .context("Reading current branch: HEAD is not a symbolic ref")),
+        }
+    }
+
+    fn count_left_right(
+        &self,
+        _renderer: &mut impl Renderer,
+        left: &str,
+        right: &str,
+    ) -> Result<(usize, usize)> {
+        let repo = self.repo.borrow();
+        let left = ancestors(&repo.objects, &repo.resolve(left)?);
+        let right = ancestors(&repo.objects, &repo.resolve(right)?);
+        Ok((
+            left.difference(&right).count(),
+            right.difference(&left).count(),
+        ))
+    }
+
+    fn merge_base(&self, _renderer: &mut impl Renderer, left: &str, right: &str) -> Result<String> {
+        let repo = self.repo.borrow();
+        let left = ancestors(&repo.objects, &repo.resolve(left)?);
+        let right = ancestors(&repo.objects, &repo.resolve(right)?);
+        let common = left.intersection(&right).collect::<HashSet<_>>();
+
+        // The best common ancestors are the ones that aren't an ancestor of another.
+        let mut best = common
+            .iter()
+            .filter(|&&candidate| {
+                common.iter().all(|&other| {
+                    other == candidate || !ancestors(&repo.objects, other).contains(candidate)
+                })
+            })
+            .map(|candidate| candidate.to_string())
+            .collect::<Vec<_>>();
+        best.sort();
+        best.into_iter()
+            .next()
+            .context("Commits have no common ancestor")
+    }
+
+    fn checkout_branch(
+        &self,
+        _renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+        branch_name: &Branch,
+        commit_id: &str,
+    ) -> Result<()> {
+        let mut repo = self.repo.borrow_mut();
+        let commit_id = repo
+            .resolve(commit_id)
+            .context(description.as_ref().to_string())?;
+        repo.refs
+            .insert(format!("refs/heads/{}", branch_name.0), commit_id);
+        repo.head = Head::Branch(branch_name.0.to_string());
+        Ok(())
+    }
+
+    fn stash_entries(&self, _renderer: &mut impl Renderer) -> Result<Vec<String>> {
+        Ok(self.repo.borrow().stash.clone())
+    }
+
+    fn worktree_snapshot(&self, _renderer: &mut impl Renderer) -> Result<Option<String>> {
+        let mut repo = self.repo.borrow_mut();
+        let changes = match repo.changes.clone() {
+            Some(changes) => changes,
+            None => return Ok(None),
+        };
+        let parent = repo.head_commit();
+        Ok(Some(new_commit(
+            &mut repo.objects,
+            Commit {
+                parents: vec![parent],
+                date: now(),
+                subject: "git-nomad worktree snapshot".to_string(),
+                changes: Some(changes),
+            },
+        )))
+    }
+
+    fn has_worktree_changes(&self, _renderer: &mut impl Renderer) -> Result<bool> {
+        Ok(self.repo.borrow().changes.is_some())
+    }
+
+    fn apply_stash(
+        &self,
+        _renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+        commit_id: &str,
+    ) -> Result<()> {
+        let mut repo = self.repo.borrow_mut();
+        let changes = match repo.objects.get(commit_id) {
+            Some(Object::Commit(Commit {
+                changes: Some(changes),
+                ..
+            })) => changes.clone(),
+            _ => bail!(
+                "{}: {} is not a stash-like commit",
+                description.as_ref(),
+                commit_id
+            ),
+        };
+        repo.changes = Some(changes);
+        Ok(())
+    }
+
+    fn fetch_nomad_refs(
+        &self,
+        _renderer: &mut impl Renderer,
+        user: &User,
+        remote: &Remote,
+    ) -> Result<RemoteNomadRefSet> {
+        let mut repo = self.repo.borrow_mut();
+        let remote_repo = repo.remote(&format!("Fetching branches from {}", remote.0), remote)?;
+        for refspec in namespace::tracking_refspecs(user, remote)
+            .into_iter()
+            .chain([namespace::clone_marker_refspec(user, remote)])
+        {
+            repo.fetch_refspec(&remote_repo.borrow(), &refspec);
+        }
+
+        let nomad_refs = repo
+            .refs
+            .iter()
+            .filter_map(|(name, commit_id)| {
+                let git_ref = GitRef {
+                    commit_id: commit_id.clone(),
+                    name: name.clone(),
+                };
+                NomadRef::<GitRef>::from_git_tracking_ref(user, remote, git_ref).ok()
+            })
+            .collect::<Vec<_>>();
+        repo.refs.extend(nomad_refs.iter().map(|nomad_ref| {
+            (
+                nomad_ref.ref_.name.clone(),
+                nomad_ref.ref_.commit_id.clone(),
+            )
+        }));
+
+        Ok(nomad_refs.into_iter().collect())
+    }
+
+    fn list_nomad_refs(
+        &self,
+        _renderer: &mut impl Renderer,
+        user: &User,
+        remote: &Remote,
+    ) -> Result<Vec<NomadRef<'static, GitRef>>> {
+        let remote_repo = self
+            .repo
+            .borrow()
+            .remote(&format!("Listing branches at {}", remote.0), remote)?;
+        let refspecs = namespace::list_refspecs(user);
+
+        let remote_repo = remote_repo.borrow();
+        Ok(remote_repo
+            .refs
+            .iter()
+            .filter(|(name, _)| {
+                refspecs
+                    .iter()
+                    .any(|refspec| name.starts_with(refspec.trim_end_matches('*')))
+            })
+            .filter_map(|(name, commit_id)| {
+                NomadRef::<GitRef>::from_git_remote_ref(GitRef {
+                    commit_id: commit_id.clone(),
+                    name: name.clone(),
+                })
+                .ok()
+            })
+            .collect())
+    }
+
+    fn push_nomad_refs<'b>(
+        &self,
+        _renderer: &mut impl Renderer,
+        user: &User,
+        host: &Host,
+        remote: &Remote,
+        clone_id: &str,
+        branches: impl IntoIterator<Item = &'b Branch<'b>>,
+        extra_refs: &[(RefKind, Branch, String)],
+    ) -> Result<()> {
+        let mut repo = self.repo.borrow_mut();
+        let branches = branches.into_iter().collect::<Vec<_>>();
+        let mut refreshed = false;
+
+        let pushed = loop {
+            let is_tracking = refreshed
+                || repo
+                    .refs
+                    .keys()
+                    .any(|name| namespace::is_tracking_ref(name));
+
+            let mut leases = Vec::new();
+            let mut updates = Vec::new();
+            let mut pushed = Vec::new();
+
+            let local_branches = branches.iter().filter_map(|branch| {
+                let commit_id = repo.refs.get(&format!("refs/heads/{}", branch.0))?;
+                Some((RefKind::Branch, branch.always_borrow(), commit_id.clone()))
+            });
+            let extra_refs = extra_refs.iter().map(|(kind, branch, commit_id)| {
+                (*kind, branch.always_borrow(), commit_id.clone())
+            });
+
+            for (kind, branch, commit_id) in local_branches.chain(extra_refs) {
+                let nomad_ref = NomadRef {
+                    user: user.always_borrow(),
+                    host: host.always_borrow(),
+                    kind,
+                    branch,
+                    ref_: (),
+                };
+                let last_known = if is_tracking {
+                    nomad_ref.to_git_tracking_ref(remote)
+                } else {
+                    nomad_ref.to_git_local_ref()
+                };
+
+                leases.push((
+                    nomad_ref.to_git_remote_ref(),
+                    repo.refs.get(&last_known).cloned(),
+                ));
+                updates.push((nomad_ref.to_git_remote_ref(), Some(commit_id.clone())));
+                pushed.push((nomad_ref.to_git_tracking_ref(remote), commit_id));
+            }
+
+            if updates.is_empty() {
+                return Ok(());
+            }
+
+            let marker = new_blob(&mut repo.objects, clone_id);
+            let marker_ref = namespace::clone_marker_ref(user, host);
+            let tracked_marker_ref =
+                format!("{}/{}", namespace::clone_marker_namespace(remote), host.0);
+            let tracked_marker = repo.refs.get(&tracked_marker_ref).cloned();
+
+            if let Some(other) = tracked_marker.as_ref().filter(|&other| other != &marker) {
+                let other_clone_id = match repo.objects.get(other) {
+                    Some(Object::Blob(contents)) => contents.clone(),
+                    _ => bail!("Read clone ID: {} is not a blob", other),
+                };
+                return Err(host_collision(remote, host, &other_clone_id));
+            }
+
+            leases.push((marker_ref.clone(), tracked_marker));
+            updates.push((marker_ref, Some(marker.clone())));
+            pushed.push((tracked_marker_ref, marker));
+
+            match repo.push(
+                &format!("Pushing local branches to {}", remote.0),
+                remote,
+                &leases,
+                &updates,
+            ) {
+                Ok(()) => break pushed,
+                Err(error) if !refreshed && !stale_refs(&error).is_empty() => {
+                    let remote_repo = repo.remote(
+                        &format!("Fetching host {} from {}", host.0, remote.0),
+                        remote,
+                    )?;
+                    for refspec in namespace::host_tracking_refspecs(user, host, remote)
+                        .into_iter()
+                        .chain([namespace::clone_marker_refspec(user, remote)])
+                    {
+                        repo.fetch_refspec(&remote_repo.borrow(), &refspec);
+                    }
+                    refreshed = true;
+                }
+                Err(error) => return Err(explain_host_collision(error, remote, host)),
+            }
+        };
+
+        repo.refs.extend(pushed);
+        Ok(())
+    }
+
+    fn forget_clone_markers(
+        &self,
+        _renderer: &mut impl Renderer,
+        user: &User,
+        remote: &Remote,
+        is_forgotten: impl Fn(&Host) -> bool,
+    ) -> Result<()> {
+        let mut repo = self.repo.borrow_mut();
+        let namespace = namespace::clone_marker_namespace(remote);
+
+        let mut leases = Vec::new();
+        let mut updates = Vec::new();
+        let mut forgotten = Vec::new();
+
+        for (name, marker) in &repo.refs {
+            let host = match name
+                .strip_prefix(&namespace)
+                .and_then(|rest| rest.strip_prefix('/'))
+            {
+                Some(host) => Host::from(host.to_string()),
+                None => continue,
+            };
+
+            if is_forgotten(&host) {
+                let marker_ref = namespace::clone_marker_ref(user, &host);
+                leases.push((marker_ref.clone(), Some(marker.clone())));
+                updates.push((marker_ref, None));
+                forgotten.push(name.clone());
+            }
+        }
+
+        if updates.is_empty() {
+            return Ok(());
+        }
+
+        repo.push(
+            &format!("Forgetting hosts at {}", remote.0),
+            remote,
+            &leases,
+            &updates,
+        )?;
+        for name in forgotten {
+            repo.refs.remove(&name);
+        }
+        Ok(())
+    }
+
+    fn prune_nomad_refs<'a>(
+        &self,
+        renderer: &mut impl Renderer,
+        remote_nomad_refs: &[(&Remote, RemoteNomadRefSet)],
+        prune: impl Iterator<Item = PruneFrom<'a, GitRef>>,
+    ) -> Result<()> {
+        let mut repo = self.repo.borrow_mut();
+        let mut leases = vec![Vec::<(String, Option<String>)>::new(); remote_nomad_refs.len()];
+        let mut deletions = vec![Vec::<(String, Option<String>)>::new(); remote_nomad_refs.len()];
+        let mut refs = Vec::<GitRef>::new();
+        let mut tracking_ref_names = Vec::<String>::new();
+
+        for prune_from in prune {
+            if let PruneFrom::LocalAndRemote(ref nomad_ref) = prune_from {
+                for (((remote, set), leases), deletions) in remote_nomad_refs
+                    .iter()
+                    .zip(&mut leases)
+                    .zip(&mut deletions)
+                {
+                    if set.contains(nomad_ref) {
+                        let tracking_ref_name = nomad_ref.to_git_tracking_ref(remote);
+                        leases.push((
+                            nomad_ref.to_git_remote_ref(),
+                            Some(
+                                repo.refs
+                                    .get(&tracking_ref_name)
+                                    .unwrap_or(&nomad_ref.ref_.commit_id)
+                                    .clone(),
+                            ),
+                        ));
+                        deletions.push((nomad_ref.to_git_remote_ref(), None));
+                        tracking_ref_names.push(tracking_ref_name);
+                    }
+                }
+            }
+
+            refs.push(
+                match prune_from {
+                    PruneFrom::LocalOnly(nomad_ref) | PruneFrom::LocalAndRemote(nomad_ref) => {
+                        nomad_ref
+                    }
+                }
+                .ref_,
+            );
+        }
+
+        for (((remote, _), leases), deletions) in
+            remote_nomad_refs.iter().zip(leases).zip(deletions)
+        {
+            if !deletions.is_empty() {
+                repo.push(
+                    &format!("Pruning branches at {}", remote.0),
+                    remote,
+                    &leases,
+                    &deletions,
+                )?;
+            }
+        }
+
+        // Like `git update-ref --stdin`, either every ref still points where it is expected to
+        // and everything is deleted, or nothing is.
+        if let Some(stale) = refs
+            .iter()
+            .find(|git_ref| repo.refs.get(&git_ref.name) != Some(&git_ref.commit_id))
+        {
+            bail!(
+                "Pruning branches locally: cannot lock ref '{}': expected {}",
+                stale.name,
+                stale.commit_id
+            );
+        }
+        for name in refs
+            .iter()
+            .map(|git_ref| &git_ref.name)
+            .chain(&tracking_ref_names)
+        {
+            repo.refs.remove(name);
+        }
+
+        if self.is_output_allowed() {
+            renderer.writer(|w| {
+                for git_ref in &refs {
+                    writeln!(w, "  Delete {} (was {})", git_ref.name, git_ref.commit_id)?;
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Acts like a separate [`Host`] in memory.
+pub struct FakeClone {
+    pub remote: Remote<'static>,
+    pub user: User<'static>,
+    pub host: Host<'static>,
+    pub git: FakeGit,
+}
+
+impl FakeClone {
+    /// Get the commit ID at HEAD.
+    pub fn current_commit(&self) -> GitCommitId {
+        GitCommitId(self.git.repo.borrow().head_commit())
+    }
+
+    /// Switch to a new branch named `branch` at the current commit.
+    pub fn checkout_new_branch(&self, branch: &str) {
+        let mut repo = self.git.repo.borrow_mut();
+        let commit_id = repo.head_commit();
+        let previous = repo
+            .refs
+            .insert(format!("refs/heads/{}", branch), commit_id);
+        assert!(previous.is_none(), "branch {} already exists", branch);
+        repo.head = Head::Branch(branch.to_string());
+    }
+
+    /// Detach `HEAD` at the current commit.
+    pub fn detach_head(&self) {
+        let mut repo = self.git.repo.borrow_mut();
+        repo.head = Head::Detached(repo.head_commit());
+    }
+
+    /// Delete the branch named `branch`, which must not be checked out.
+    pub fn delete_branch(&self, branch: &str) {
+        let mut repo = self.git.repo.borrow_mut();
+        assert!(
+            !matches!(&repo.head, Head::Branch(head) if head == branch),
+            "cannot delete the checked out branch {}",
+            branch
+        );
+        repo.refs.remove(&format!("refs/heads/{}", branch)).unwrap();
+    }
+
+    /// Create an empty commit on the current branch.
+    pub fn commit(&self, message: &str) -> GitCommitId {
+        self.commit_at(message, now())
+    }
+
+    /// Create an empty commit on the current branch, committed at `date` seconds since the Unix
+    /// epoch.
+    pub fn commit_at(&self, message: &str, date: u64) -> GitCommitId {
+        GitCommitId(self.git.repo.borrow_mut().commit(message, date))
+    }
+
+    /// Tag the current commit as `name`.
+    pub fn tag(&self, name: &str) {
+        let mut repo = self.git.repo.borrow_mut();
+        let commit_id = repo.head_commit();
+        repo.refs.insert(format!("refs/tags/{}", name), commit_id);
+    }
+
+    /// Delete the tag named `name`.
+    pub fn delete_tag(&self, name: &str) {
+        self.git
+            .repo
+            .borrow_mut()
+            .refs
+            .remove(&format!("refs/tags/{}", name))
+            .unwrap();
+    }
+
+    /// Make uncommitted `changes` to the working tree, replacing any that were there.
+    pub fn write_changes(&self, changes: &str) {
+        self.git.repo.borrow_mut().changes = Some(changes.to_string());
+    }
+
+    /// The uncommitted changes in the working tree, if there are any.
+    pub fn changes(&self) -> Option<String> {
+        self.git.repo.borrow().changes.clone()
+    }
+
+    /// Stash uncommitted `changes` as a new `stash@{0}`.
+    pub fn stash(&self, changes: &str) {
+        let mut repo = self.git.repo.borrow_mut();
+        let parent = repo.head_commit();
+        let commit_id = new_commit(
+            &mut repo.objects,
+            Commit {
+                parents: vec![parent],
+                date: now(),
+                subject: "WIP".to_string(),
+                changes: Some(changes.to_string()),
+            },
+        );
+        repo.stash.insert(0, commit_id);
+    }
+
+    /// Drop every stash entry.
+    pub fn clear_stash(&self) {
+        self.git.repo.borrow_mut().stash.clear();
+    }
+
+    /// Add `other` as an additional remote named `name`, fetching its branches like `git clone`
+    /// or `git remote add -f` would.
+    pub fn add_remote(&self, name: &'static str, other: &FakeRemote) -> Remote<'static> {
+        let mut repo = self.git.repo.borrow_mut();
+        repo.remotes
+            .insert(name.to_string(), Rc::clone(&other.repo));
+        repo.fetch_refspec(
+            &other.repo.borrow(),
+            &format!("+refs/heads/*:refs/remotes/{}/*", name),
+        );
+        Remote::from(name)
+    }
+
+    /// Push all local branches as nomad managed refs to the remote.
+    pub fn push(&self) {
+        let local_branches = self
+            .git
+            .snapshot(&mut NoRenderer, &self.user)
+            .unwrap()
+            .local_branches;
+        self.git
+            .push_nomad_refs(
+                &mut NoRenderer,
+                &self.user,
+                &self.host,
+                &self.remote,
+                &self.git.clone_id(&mut NoRenderer).unwrap(),
+                &local_branches,
+                &[],
+            )
+            .unwrap();
+    }
+
+    /// Fetch all nomad managed refs from the remote.
+    pub fn fetch(&self) {
+        self.git
+            .fetch_nomad_refs(&mut NoRenderer, &self.user, &self.remote)
+            .unwrap();
+    }
+
+    /// Get all nomad managed refs in the local clone.
+    pub fn nomad_refs(&self) -> HashSet<NomadRef<'_, GitCommitId>> {
+        self.git
+            .list_refs(&mut NoRenderer, "")
+            .unwrap()
+            .into_iter()
+            .filter_map(|git_ref| {
+                NomadRef::<GitRef>::from_git_local_ref(&self.user, git_ref)
+                    .ok()
+                    .map(Into::into)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use crate::{
+        error::{exit_code, NomadError},
+        git_backend::GitBackend,
+        git_testing::{GitCommitId, GitRemote},
+        renderer::test::NoRenderer,
+        types::{Host, NomadRef},
+        workflow::{BranchPatterns, Filter, SyncExtras, Workflow},
+    };
+
+    use super::{FakeRemote, RemoteFailure};
+
+    /// The host and branch of every ref, since commit IDs differ between real and fake clones.
+    fn names<'a>(
+        nomad_refs: impl IntoIterator<Item = NomadRef<'a, GitCommitId>>,
+    ) -> BTreeSet<(String, String)> {
+        nomad_refs
+            .into_iter()
+            .map(|nomad_ref| (nomad_ref.host.0.to_string(), nomad_ref.branch.0.to_string()))
+            .collect()
+    }
+
+    fn sync(git: &impl GitBackend, host: &'static str) -> anyhow::Result<()> {
+        Workflow::Sync {
+            user: "user0".into(),
+            host: host.into(),
+            remotes: vec!["origin".into()],
+            branch_patterns: BranchPatterns::default(),
+            extras: SyncExtras::default(),
+            dry_run: false,
+        }
+        .execute(&mut NoRenderer, git)
+    }
+
+    fn purge(git: &impl GitBackend, host: &'static str) {
+        Workflow::Purge {
+            user: "user0".into(),
+            remotes: vec!["origin".into()],
+            host_filter: Filter::Allow([Host::from(host)].into()),
+            older_than: None,
+            dry_run: false,
+        }
+        .execute(&mut NoRenderer, git)
+        .unwrap();
+    }
+
+    /// The same scenario should leave the same refs behind whether it runs against real `git` or
+    /// the fake.
+    #[test]
+    fn matches_git_binary() {
+        let real = GitRemote::init(None);
+        let real0 = real.clone("user0", "host0");
+        let real1 = real.clone("user0", "host1");
+
+        let fake = FakeRemote::init();
+        let fake0 = fake.clone("user0", "host0");
+        let fake1 = fake.clone("user0", "host1");
+
+        let assert_same = || {
+            assert_eq!(names(real.nomad_refs()), names(fake.nomad_refs()));
+            assert_eq!(names(real0.nomad_refs()), names(fake0.nomad_refs()));
+            assert_eq!(names(real1.nomad_refs()), names(fake1.nomad_refs()));
+        };
+
+        real0.checkout_new_branch("feature");
+        fake0.checkout_new_branch("feature");
+        sync(&real0.git, "host0").unwrap();
+        sync(&fake0.git, "host0").unwrap();
+        sync(&real1.git, "host1").unwrap();
+        sync(&fake1.git, "host1").unwrap();
+        assert_same();
+
+        sync(&real0.git, "host0").unwrap();
+        sync(&fake0.git, "host0").unwrap();
+        assert_same();
+
+        real0
+            .git
+            .delete_branch(&mut NoRenderer, "", &"master".into())
+            .unwrap();
+        fake0.delete_branch("master");
+        sync(&real0.git, "host0").unwrap();
+        sync(&fake0.git, "host0").unwrap();
+        assert_same();
+
+        // A purged host that is still around should be able to sync again.
+        purge(&real0.git, "host1");
+        purge(&fake0.git, "host1");
+        assert_same();
+        sync(&real1.git, "host1").unwrap();
+        sync(&fake1.git, "host1").unwrap();
+        assert_same();
+        assert!(names(fake.nomad_refs())
+            .iter()
+            .any(|(host, _)| host == "host1"));
+    }
+
+    /// Leases should catch another clone pushing as the same host, like they do for real.
+    #[test]
+    fn host_collision() {
+        let remote = FakeRemote::init();
+        let host0 = remote.clone("user0", "host0");
+        let impostor = remote.clone("user0", "impostor");
+        sync(&host0.git, "host0").unwrap();
+
+        // Without fetching first, the lease sends it to fetch the clone marker, which gives it
+        // away.
+        let error = sync(&impostor.git, "host0").unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<NomadError>(),
+            Some(&NomadError::HostNameCollision)
+        );
+        assert!(error
+            .to_string()
+            .contains("is already being pushed by another clone"));
+
+        // After fetching, the clone marker gives it away before pushing at all.
+        impostor.fetch();
+        let error = sync(&impostor.git, "host0").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("is already being pushed by another clone"));
+    }
+
+    #[test]
+    fn remote_failures() {
+        let remote = FakeRemote::init();
+        let host0 = remote.clone("user0", "host0");
+
+        remote.fail_with(Some(RemoteFailure::Unreachable));
+        assert_eq!(exit_code(&sync(&host0.git, "host0").unwrap_err()), 6);
+
+        remote.fail_with(Some(RemoteFailure::RejectPushes));
+        assert_eq!(exit_code(&sync(&host0.git, "host0").unwrap_err()), 8);
+        assert!(remote.nomad_refs().is_empty());
+
+        remote.fail_with(None);
+        sync(&host0.git, "host0").unwrap();
+        assert_eq!(names(remote.nomad_refs()), names(host0.nomad_refs()));
+    }
+}
@@ -0,0 +1,383 @@
+//! See [`GixBackend`] for the primary entry point.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::{
+    error::NomadError,
+    git_backend::GitBackend,
+    git_binary::{ConfigEntry, ConfigScope, GitBinary, RefDetails},
+    git_ref::GitRef,
+    renderer::Renderer,
+    snapshot::PruneFrom,
+    types::{Branch, Host, NomadRef, RefKind, Remote, RemoteNomadRefSet, User},
+};
+
+/// Reads refs and `HEAD` in-process with [`gix`], instead of spawning `git show-ref` and `git
+/// symbolic-ref` for every [`GitBackend::snapshot`], which adds up in large repositories.
+///
+/// Everything else is delegated to the wrapped [`GitBinary`]. That includes everything that talks
+/// to a remote or writes to the repository, and reading config, which `git config` writes and
+/// `gix` would otherwise read from a snapshot taken when the repository was opened.
+pub struct GixBackend<'name> {
+    git: GitBinary<'name>,
+    repo: gix::Repository,
+}
+
+impl<'name> GixBackend<'name> {
+    /// Open the same repository that `git` operates on.
+    pub fn new(git: GitBinary<'name>) -> Result<Self> {
+        let repo = gix::open(git.git_dir())
+            .with_context(|| format!("Opening {} with gitoxide", git.git_dir().display()))?;
+        Ok(Self { git, repo })
+    }
+
+    /// The wrapped [`GitBinary`], for operations that aren't part of [`GitBackend`].
+    pub fn git_binary(&self) -> &GitBinary<'name> {
+        &self.git
+    }
+}
+
+/// Describe `reference` like `git show-ref` would, following symbolic refs but not peeling tags.
+///
+/// Returns `None` for broken symbolic refs, which `git show-ref` ignores.
+fn to_git_ref(mut reference: gix::Reference) -> Option<GitRef> {
+    // Following happens in place, after which the name is that of the last symbolic target.
+    let name = reference.name().as_bstr().to_string();
+    let commit_id = reference.follow_to_object().ok()?.to_string();
+    Some(GitRef { commit_id, name })
+}
+
+impl GitBackend for GixBackend<'_> {
+    fn is_output_allowed(&self) -> bool {
+        self.git.is_output_allowed()
+    }
+
+    fn get_config(&self, renderer: &mut impl Renderer, key: &str) -> Result<Option<String>> {
+        self.git.get_config(renderer, key)
+    }
+
+    fn get_config_bool(&self, renderer: &mut impl Renderer, key: &str) -> Result<bool> {
+        self.git.get_config_bool(renderer, key)
+    }
+
+    fn get_config_entries(
+        &self,
+        renderer: &mut impl Renderer,
+        scope: Option<ConfigScope>,
+        key: &str,
+    ) -> Result<Vec<ConfigEntry>> {
+        self.git.get_config_entries(renderer, scope, key)
+    }
+
+    fn set_config_all(
+        &self,
+        renderer: &mut impl Renderer,
+        scope: ConfigScope,
+        key: &str,
+        values: &[&str],
+    ) -> Result<()> {
+        self.git.set_config_all(renderer, scope, key, values)
+    }
+
+    fn unset_config(
+        &self,
+        renderer: &mut impl Renderer,
+        scope: ConfigScope,
+        key: &str,
+    ) -> Result<()> {
+        self.git.unset_config(renderer, scope, key)
+    }
+
+    fn clone_id(&self, renderer: &mut impl Renderer) -> Result<String> {
+        self.git.clone_id(renderer)
+    }
+
+    fn hooks_dir(&self, renderer: &mut impl Renderer) -> Result<PathBuf> {
+        self.git.hooks_dir(renderer)
+    }
+
+    fn get_ref(
+        &self,
+        _renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+        ref_name: impl AsRef<str>,
+    ) -> Result<GitRef> {
+        let ref_name = ref_name.as_ref();
+        let reference = self
+            .repo
+            .find_reference(ref_name)
+            .with_context(|| format!("{}: {}", description.as_ref(), ref_name))?;
+
+        // Like `git show-ref --verify`, only accept the full name rather than anything that
+        // could be expanded to one.
+        if reference.name().as_bstr() != ref_name {
+            bail!(
+                "{}: {} is not a full ref name",
+                description.as_ref(),
+                ref_name
+            );
+        }
+
+        to_git_ref(reference)
+            .with_context(|| format!("{}: {} is broken", description.as_ref(), ref_name))
+    }
+
+    fn list_refs(
+        &self,
+        _renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+    ) -> Result<Vec<GitRef>> {
+        let platform = self
+            .repo
+            .references()
+            .context(description.as_ref().to_string())?;
+        let mut refs = Vec::new();
+        for reference in platform.all().context(description.as_ref().to_string())? {
+            let reference =
+                reference.map_err(|err| anyhow!(err).context(description.as_ref().to_string()))?;
+            refs.extend(to_git_ref(reference));
+        }
+        Ok(refs)
+    }
+
+    fn nomad_committer_dates(&self, renderer: &mut impl Renderer) -> Result<HashMap<String, u64>> {
+        self.git.nomad_committer_dates(renderer)
+    }
+
+    fn nomad_ref_details(
+        &self,
+        renderer: &mut impl Renderer,
+    ) -> Result<HashMap<String, RefDetails>> {
+        self.git.nomad_ref_details(renderer)
+    }
+
+    fn current_branch(&self, _renderer: &mut impl Renderer) -> Result<Branch<'static>> {
+        match self.repo.head_name().context("Reading current branch")? {
+            Some(name) => Ok(Branch::from(name.shorten().to_string())),
+            None => Err(NomadError::DetachedHead).context("Reading current branch"),
+        }
+    }
+
+    fn count_left_right(
+        &self,
+        renderer: &mut impl Renderer,
+        left: &str,
+        right: &str,
+    ) -> Result<(usize, usize)> {
+        self.git.count_left_right(renderer, left, right)
+    }
+
+    fn merge_base(&self, renderer: &mut impl Renderer, left: &str, right: &str) -> Result<String> {
+        self.git.merge_base(renderer, left, right)
+    }
+
+    fn checkout_branch(
+        &self,
+        renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+        branch_name: &Branch,
+        commit_id: &str,
+    ) -> Result<()> {
+        self.git
+            .checkout_branch(renderer, description, branch_name, commit_id)
+    }
+
+    fn stash_entries(&self, renderer: &mut impl Renderer) -> Result<Vec<String>> {
+        self.git.stash_entries(renderer)
+    }
+
+    fn worktree_snapshot(&self, renderer: &mut impl Renderer) -> Result<Option<String>> {
+        self.git.worktree_snapshot(renderer)
+    }
+
+    fn has_worktree_changes(&self, renderer: &mut impl Renderer) -> Result<bool> {
+        self.git.has_worktree_changes(renderer)
+    }
+
+    fn apply_stash(
+        &self,
+        renderer: &mut impl Renderer,
+        description: impl AsRef<str>,
+        commit_id: &str,
+    ) -> Result<()> {
+        self.git.apply_stash(renderer, description, commit_id)
+    }
+
+    fn fetch_nomad_refs(
+        &self,
+        renderer: &mut impl Renderer,
+        user: &User,
+        remote: &Remote,
+    ) -> Result<RemoteNomadRefSet> {
+        self.git.fetch_nomad_refs(renderer, user, remote)
+    }
+
+    fn list_nomad_refs(
+        &self,
+        renderer: &mut impl Renderer,
+        user: &User,
+        remote: &Remote,
+    ) -> Result<Vec<NomadRef<'static, GitRef>>> {
+        GitBackend::list_nomad_refs(&self.git, renderer, user, remote)
+    }
+
+    fn push_nomad_refs<'b>(
+        &self,
+        renderer: &mut impl Renderer,
+        user: &User,
+        host: &Host,
+        remote: &Remote,
+        clone_id: &str,
+        branches: impl IntoIterator<Item = &'b Branch<'b>>,
+        extra_refs: &[(RefKind, Branch, String)],
+    ) -> Result<()> {
+        self.git
+            .push_nomad_refs(renderer, user, host, remote, clone_id, branches, extra_refs)
+    }
+
+    fn forget_clone_markers(
+        &self,
+        renderer: &mut impl Renderer,
+        user: &User,
+        remote: &Remote,
+        is_forgotten: impl Fn(&Host) -> bool,
+    ) -> Result<()> {
+        self.git
+            .forget_clone_markers(renderer, user, remote, is_forgotten)
+    }
+
+    fn prune_nomad_refs<'a>(
+        &self,
+        renderer: &mut impl Renderer,
+        remote_nomad_refs: &[(&Remote, RemoteNomadRefSet)],
+        prune: impl Iterator<Item = PruneFrom<'a, GitRef>>,
+    ) -> Result<()> {
+        self.git
+            .prune_nomad_refs(renderer, remote_nomad_refs, prune)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use crate::{
+        error::NomadError,
+        git_backend::GitBackend,
+        git_binary::GitBinary,
+        git_testing::{GitClone, GitRemote, INITIAL_BRANCH},
+        renderer::test::{MemoryRenderer, NoRenderer},
+        types::Branch,
+        workflow::{BranchPatterns, Filter, LsPrinter, SyncExtras, Workflow},
+    };
+
+    use super::GixBackend;
+
+    fn gix_backend(clone: &GitClone) -> GixBackend<'static> {
+        let git = GitBinary::new(
+            &mut NoRenderer,
+            None,
+            "git".into(),
+            clone.working_directory(),
+        )
+        .unwrap();
+        GixBackend::new(git).unwrap()
+    }
+
+    /// Reading refs in-process should agree exactly with `git show-ref`.
+    #[test]
+    fn list_refs_matches_git() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        host0.checkout_new_branch("feature/nested");
+        host0.commit("On the feature");
+        host0.tag("lightweight", None);
+        host0.tag("annotated", Some("An annotated tag"));
+        host0.stash("stashed", "contents");
+        host0.push();
+        host0.fetch();
+
+        let gix = gix_backend(&host0);
+        let expected = host0
+            .git
+            .list_refs(&mut NoRenderer, "")
+            .unwrap()
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let actual = GitBackend::list_refs(&gix, &mut NoRenderer, "")
+            .unwrap()
+            .into_iter()
+            .collect::<HashSet<_>>();
+        assert_eq!(actual, expected);
+
+        for git_ref in expected {
+            assert_eq!(
+                GitBackend::get_ref(&gix, &mut NoRenderer, "", &git_ref.name).unwrap(),
+                git_ref
+            );
+        }
+        assert!(GitBackend::get_ref(&gix, &mut NoRenderer, "", INITIAL_BRANCH).is_err());
+        assert!(GitBackend::get_ref(&gix, &mut NoRenderer, "", "refs/heads/missing").is_err());
+    }
+
+    #[test]
+    fn current_branch() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        let gix = gix_backend(&host0);
+
+        assert_eq!(
+            gix.current_branch(&mut NoRenderer).unwrap(),
+            Branch::from(INITIAL_BRANCH)
+        );
+
+        host0.checkout_new_branch("feature/nested");
+        assert_eq!(
+            gix.current_branch(&mut NoRenderer).unwrap(),
+            Branch::from("feature/nested")
+        );
+
+        host0.detach_head();
+        let error = gix.current_branch(&mut NoRenderer).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<NomadError>(),
+            Some(&NomadError::DetachedHead)
+        );
+    }
+
+    /// Workflows should work end to end, with refs written by `git` visible to `gix` right away.
+    #[test]
+    fn sync_and_ls() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        let host1 = remote.clone("user0", "host1");
+        let commit_id = host1.commit("On host1");
+
+        for clone in [&host1, &host0] {
+            Workflow::Sync {
+                user: clone.user.always_borrow(),
+                host: clone.host.always_borrow(),
+                remotes: vec![clone.remote.always_borrow()],
+                branch_patterns: BranchPatterns::default(),
+                extras: SyncExtras::default(),
+                dry_run: false,
+            }
+            .execute(&mut NoRenderer, &gix_backend(clone))
+            .unwrap();
+        }
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::Ls {
+            printer: LsPrinter::Commit,
+            user: host0.user.always_borrow(),
+            fetch_remotes: Vec::new(),
+            host_filter: Filter::Deny([host0.host.always_borrow()].into()),
+            branch_filter: Filter::All,
+        }
+        .execute(&mut renderer, &gix_backend(&host0))
+        .unwrap();
+        assert_eq!(renderer.as_str(), format!("{}\n", commit_id.0));
+    }
+}
@@ -1,18 +1,19 @@
 use std::{
     borrow::Cow,
     collections::HashSet,
-    fs::{create_dir, write},
+    fs::{create_dir, read_to_string, write},
     path::{Path, PathBuf},
 };
 
 use tempfile::{tempdir, TempDir};
 
 use crate::{
+    git_backend::GitBackend,
     git_binary::{git_command, GitBinary, LineArity},
     git_ref::GitRef,
     renderer::test::NoRenderer,
     snapshot::PruneFrom,
-    types::{Branch, Host, NomadRef, Remote, User},
+    types::{Branch, Host, NomadRef, RefKind, Remote, User},
     verbosity::{output_stdout, run_notable, Verbosity},
 };
 
@@ -41,6 +42,7 @@ impl<'a> From<NomadRef<'a, GitRef>> for NomadRef<'a, GitCommitId> {
         Self {
             user: nomad_ref.user,
             host: nomad_ref.host,
+            kind: nomad_ref.kind,
             branch: nomad_ref.branch,
             ref_: nomad_ref.ref_.into(),
         }
@@ -116,7 +118,7 @@ impl GitRemote {
             git_command(GIT)
                 .current_dir(&self.root_dir)
                 .arg("clone")
-                .args(&["--origin", ORIGIN])
+                .args(["--origin", ORIGIN])
                 .arg(&self.remote_dir)
                 .arg(&clone_dir),
         )
@@ -132,7 +134,7 @@ impl GitRemote {
 
         GitClone {
             git_remote: self,
-            _clone_dir: clone_dir,
+            clone_dir,
             remote: Remote::from(ORIGIN),
             user: User::from(user),
             host: Host::from(host),
@@ -141,7 +143,7 @@ impl GitRemote {
     }
 
     /// List all nomad managed refs in the remote.
-    pub fn nomad_refs(&self) -> HashSet<NomadRef<GitCommitId>> {
+    pub fn nomad_refs(&self) -> HashSet<NomadRef<'_, GitCommitId>> {
         self.git
             .list_refs(&mut NoRenderer, "")
             .unwrap()
@@ -158,7 +160,7 @@ impl GitRemote {
 /// Acts like a separate [`Host`] in a temporary directory.
 pub struct GitClone<'a> {
     git_remote: &'a GitRemote,
-    _clone_dir: PathBuf,
+    clone_dir: PathBuf,
     pub remote: Remote<'static>,
     pub user: User<'static>,
     pub host: Host<'static>,
@@ -166,6 +168,10 @@ pub struct GitClone<'a> {
 }
 
 impl<'a> GitClone<'a> {
+    pub fn working_directory(&self) -> &Path {
+        &self.clone_dir
+    }
+
     /// Get the commit ID at HEAD.
     pub fn current_commit(&self) -> GitCommitId {
         let commit_id = run_notable(
@@ -182,10 +188,169 @@ impl<'a> GitClone<'a> {
         GitCommitId(commit_id)
     }
 
-    /// Push all nomad managed refs to the remote.
+    /// Switch to a new branch named `branch` at the current commit.
+    pub fn checkout_new_branch(&self, branch: &str) {
+        run_notable(
+            &mut NoRenderer,
+            self.git_remote.verbosity(),
+            "Create and switch to branch",
+            self.git.command().args(["checkout", "-b", branch]),
+        )
+        .unwrap();
+    }
+
+    /// Detach `HEAD` at the current commit.
+    pub fn detach_head(&self) {
+        run_notable(
+            &mut NoRenderer,
+            self.git_remote.verbosity(),
+            "Detach HEAD",
+            self.git.command().args(["checkout", "--detach"]),
+        )
+        .unwrap();
+    }
+
+    /// Create an empty commit on the current branch.
+    pub fn commit(&self, message: &str) -> GitCommitId {
+        run_notable(
+            &mut NoRenderer,
+            self.git_remote.verbosity(),
+            "Create commit",
+            self.git
+                .command()
+                .args(["commit", "--allow-empty", "-m", message]),
+        )
+        .unwrap();
+
+        self.current_commit()
+    }
+
+    /// Create an empty commit on the current branch with a specific author and committer date.
+    pub fn commit_at(&self, message: &str, date: &str) -> GitCommitId {
+        run_notable(
+            &mut NoRenderer,
+            self.git_remote.verbosity(),
+            "Create commit",
+            self.git
+                .command()
+                .env("GIT_AUTHOR_DATE", date)
+                .env("GIT_COMMITTER_DATE", date)
+                .args(["commit", "--allow-empty", "-m", message]),
+        )
+        .unwrap();
+
+        self.current_commit()
+    }
+
+    /// Write `contents` to `file_name` in the working tree, without staging it.
+    pub fn write_file(&self, file_name: &str, contents: &str) {
+        write(self.clone_dir.join(file_name), contents).unwrap();
+    }
+
+    /// Write `contents` to `file_name` and commit it on the current branch.
+    pub fn commit_file(&self, file_name: &str, contents: &str) -> GitCommitId {
+        self.write_file(file_name, contents);
+        run_notable(
+            &mut NoRenderer,
+            self.git_remote.verbosity(),
+            "Stage file",
+            self.git.command().args(["add", file_name]),
+        )
+        .unwrap();
+        self.commit(file_name)
+    }
+
+    /// Tag the current commit as `name`, with an annotated tag if there is a `message`.
+    pub fn tag(&self, name: &str, message: Option<&str>) {
+        let mut command = self.git.command();
+        command.arg("tag");
+        if let Some(message) = message {
+            command.args(["-a", "-m", message]);
+        }
+        command.arg(name);
+        run_notable(
+            &mut NoRenderer,
+            self.git_remote.verbosity(),
+            "Create tag",
+            &mut command,
+        )
+        .unwrap();
+    }
+
+    /// Delete the tag named `name`.
+    pub fn delete_tag(&self, name: &str) {
+        run_notable(
+            &mut NoRenderer,
+            self.git_remote.verbosity(),
+            "Delete tag",
+            self.git.command().args(["tag", "-d", name]),
+        )
+        .unwrap();
+    }
+
+    /// Write an untracked file named `file_name` and stash it as a new `stash@{0}`.
+    pub fn stash(&self, file_name: &str, contents: &str) {
+        self.write_file(file_name, contents);
+        run_notable(
+            &mut NoRenderer,
+            self.git_remote.verbosity(),
+            "Stash changes",
+            self.git
+                .command()
+                .args(["stash", "push", "--include-untracked"]),
+        )
+        .unwrap();
+    }
+
+    /// Drop every stash entry.
+    pub fn clear_stash(&self) {
+        run_notable(
+            &mut NoRenderer,
+            self.git_remote.verbosity(),
+            "Clear stash",
+            self.git.command().args(["stash", "clear"]),
+        )
+        .unwrap();
+    }
+
+    /// Read `file_name` from the working tree, if it exists.
+    pub fn read_file(&self, file_name: &str) -> Option<String> {
+        read_to_string(self.clone_dir.join(file_name)).ok()
+    }
+
+    /// Add `other` as an additional remote named `name`.
+    pub fn add_remote(&self, name: &'static str, other: &GitRemote) -> Remote<'static> {
+        run_notable(
+            &mut NoRenderer,
+            self.git_remote.verbosity(),
+            "Add remote",
+            self.git
+                .command()
+                .args(["remote", "add", name])
+                .arg(other.working_directory()),
+        )
+        .unwrap();
+
+        Remote::from(name)
+    }
+
+    /// Push all local branches as nomad managed refs to the remote.
     pub fn push(&self) {
+        let local_branches = self
+            .git
+            .snapshot(&mut NoRenderer, &self.user)
+            .unwrap()
+            .local_branches;
         self.git
-            .push_nomad_refs(&mut NoRenderer, &self.user, &self.host, &self.remote)
+            .push_nomad_refs(
+                &mut NoRenderer,
+                &self.user,
+                &self.host,
+                &self.remote,
+                &self.git.clone_id(&mut NoRenderer).unwrap(),
+                &local_branches,
+                &[],
+            )
             .unwrap();
     }
 
@@ -193,11 +358,11 @@ impl<'a> GitClone<'a> {
     pub fn fetch(&self) {
         self.git
             .fetch_nomad_refs(&mut NoRenderer, &self.user, &self.remote)
-            .unwrap()
+            .unwrap();
     }
 
     /// List all nomad managed refs in the current clone.
-    pub fn list(&self) -> impl Iterator<Item = NomadRef<GitRef>> {
+    pub fn list(&self) -> impl Iterator<Item = NomadRef<'static, GitRef>> {
         self.git
             .list_nomad_refs(&mut NoRenderer, &self.user, &self.remote)
             .unwrap()
@@ -209,6 +374,7 @@ impl<'a> GitClone<'a> {
             let nomad_ref = NomadRef::<()> {
                 user: self.user.always_borrow(),
                 host: self.host.always_borrow(),
+                kind: RefKind::Branch,
                 branch: Branch::from(name),
                 ref_: (),
             };
@@ -218,6 +384,7 @@ impl<'a> GitClone<'a> {
             let nomad_ref = NomadRef {
                 user: nomad_ref.user,
                 host: nomad_ref.host,
+                kind: nomad_ref.kind,
                 branch: nomad_ref.branch,
                 ref_: self.git.get_ref(&mut NoRenderer, "", ref_name).unwrap(),
             };
@@ -225,8 +392,13 @@ impl<'a> GitClone<'a> {
             PruneFrom::LocalAndRemote(nomad_ref)
         });
 
+        let remote_nomad_refs = self.list().collect();
         self.git
-            .prune_nomad_refs(&mut NoRenderer, &self.remote, prune_from)
+            .prune_nomad_refs(
+                &mut NoRenderer,
+                &[(&self.remote, remote_nomad_refs)],
+                prune_from,
+            )
             .unwrap();
     }
 
@@ -238,13 +410,14 @@ impl<'a> GitClone<'a> {
             .map(|git_ref| NomadRef {
                 user: self.user.always_borrow(),
                 host: self.host.always_borrow(),
+                kind: RefKind::Branch,
                 branch: Branch::from(branch),
                 ref_: git_ref.into(),
             })
     }
 
     /// Get all nomad managed refs in the local clone.
-    pub fn nomad_refs(&self) -> HashSet<NomadRef<GitCommitId>> {
+    pub fn nomad_refs(&self) -> HashSet<NomadRef<'_, GitCommitId>> {
         self.git
             .list_refs(&mut NoRenderer, &self.host.0)
             .unwrap()
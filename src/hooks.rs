@@ -0,0 +1,356 @@
+//! Git hooks that push nomad managed refs as a side effect of normal git usage, so that
+//! forgetting to run `git nomad sync` before leaving a machine matters less.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+
+/// Identifies hooks written by nomad, so that they can be updated or removed without touching
+/// hooks that the user wrote.
+const MARKER: &str = "# Installed by git-nomad.";
+
+/// Suffix for an existing hook that has been moved aside so that the nomad hook can chain to it.
+const CHAINED_SUFFIX: &str = ".pre-nomad";
+
+/// The git hooks that nomad installs.
+///
+/// A commit also goes through a reference transaction, so some changes start more than one push
+/// for the same branch. Pushes wait for each other, so the later ones find nothing left to do.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Hook {
+    /// Pushes the branch that was committed to.
+    PostCommit,
+    /// Pushes the branch that was switched to, which may never have been pushed before.
+    PostCheckout,
+    /// Pushes branches updated any other way, like `rebase`, `reset`, or `branch --force`.
+    ReferenceTransaction,
+}
+
+impl Hook {
+    pub const ALL: [Hook; 3] = [
+        Hook::PostCommit,
+        Hook::PostCheckout,
+        Hook::ReferenceTransaction,
+    ];
+
+    /// The file name git looks for in the hooks directory.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PostCommit => "post-commit",
+            Self::PostCheckout => "post-checkout",
+            Self::ReferenceTransaction => "reference-transaction",
+        }
+    }
+
+    /// A POSIX shell script that runs the chained hook (if any) and then pushes the affected
+    /// branches in the background so that git is not slowed down.
+    fn script(self) -> String {
+        let mut script = format!(
+            concat!(
+                "#!/bin/sh\n",
+                "{marker}\n",
+                "# Pushes the affected branch as a nomad managed ref in the background.\n",
+                "# Remove with `git nomad hooks uninstall`, which restores any chained hook.\n",
+                "\n",
+            ),
+            marker = MARKER,
+        );
+
+        let push = "(git nomad push --quiet \"$@\" >/dev/null 2>&1 &)\n";
+
+        match self {
+            Self::PostCommit => {
+                script.push_str(&chain_to(&format!("\"$0{}\" \"$@\"", CHAINED_SUFFIX)));
+                script.push_str(concat!(
+                    "branch=$(git symbolic-ref --quiet --short HEAD) || exit 0\n",
+                    "set -- --branch \"$branch\"\n",
+                ));
+                script.push_str(push);
+            }
+            Self::PostCheckout => {
+                script.push_str(&chain_to(&format!("\"$0{}\" \"$@\"", CHAINED_SUFFIX)));
+                script.push_str(concat!(
+                    "# Only branch checkouts are interesting, not file checkouts.\n",
+                    "[ \"$3\" = 1 ] || exit 0\n",
+                    "branch=$(git symbolic-ref --quiet --short HEAD) || exit 0\n",
+                    "set -- --branch \"$branch\"\n",
+                ));
+                script.push_str(push);
+            }
+            Self::ReferenceTransaction => {
+                // The chained hook needs the same stdin, and can abort the transaction in the
+                // "prepared" state by failing. Empty stdin stays empty rather than becoming an
+                // empty line.
+                script.push_str("input=$(cat)\n");
+                script.push_str(&chain_to(&format!(
+                    "{{ [ -z \"$input\" ] || printf '%s\\n' \"$input\"; }} | \"$0{}\" \"$@\"",
+                    CHAINED_SUFFIX
+                )));
+                script.push_str(concat!(
+                    "[ \"$1\" = committed ] || exit 0\n",
+                    "\n",
+                    "set --\n",
+                    "while read -r old new ref; do\n",
+                    "    case \"$ref\" in refs/heads/*) ;; *) continue ;; esac\n",
+                    "    # Deleted branches are pruned by the next `git nomad sync`.\n",
+                    "    case \"$new\" in *[!0]*) ;; *) continue ;; esac\n",
+                    "    set -- \"$@\" --branch \"${ref#refs/heads/}\"\n",
+                    "done <<EOF\n",
+                    "$input\n",
+                    "EOF\n",
+                    "[ $# -gt 0 ] || exit 0\n",
+                ));
+                script.push_str(push);
+            }
+        }
+
+        script
+    }
+}
+
+/// Shell snippet that runs `invocation` if there is a chained hook, exiting if it fails.
+fn chain_to(invocation: &str) -> String {
+    format!(
+        concat!(
+            "if [ -x \"$0{suffix}\" ]; then\n",
+            "    {invocation} || exit $?\n",
+            "fi\n",
+            "\n",
+        ),
+        suffix = CHAINED_SUFFIX,
+        invocation = invocation,
+    )
+}
+
+/// What currently occupies the path for a [`Hook`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HookState {
+    /// No hook at all.
+    Missing,
+    /// A hook that nomad did not write.
+    Other,
+    /// A hook that nomad wrote, which may be chaining to a hook that was there before it.
+    Nomad { chained: bool },
+}
+
+/// The path to `hook` within `hooks_dir`.
+pub fn hook_path(hooks_dir: &Path, hook: Hook) -> PathBuf {
+    hooks_dir.join(hook.name())
+}
+
+/// The path that an existing hook is moved to when nomad chains to it.
+pub fn chained_path(hooks_dir: &Path, hook: Hook) -> PathBuf {
+    hooks_dir.join(format!("{}{}", hook.name(), CHAINED_SUFFIX))
+}
+
+/// Inspect what is currently installed for `hook`.
+pub fn state(hooks_dir: &Path, hook: Hook) -> Result<HookState> {
+    let path = hook_path(hooks_dir, hook);
+    let contents = match fs::read(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(HookState::Missing),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    };
+
+    if String::from_utf8_lossy(&contents).contains(MARKER) {
+        Ok(HookState::Nomad {
+            chained: chained_path(hooks_dir, hook).exists(),
+        })
+    } else {
+        Ok(HookState::Other)
+    }
+}
+
+/// Write the nomad version of `hook`, moving any hook the user wrote out of the way so that it
+/// keeps running. Returns the state before installation.
+pub fn install(hooks_dir: &Path, hook: Hook) -> Result<HookState> {
+    let before = state(hooks_dir, hook)?;
+    let path = hook_path(hooks_dir, hook);
+
+    if before == HookState::Other {
+        let chained = chained_path(hooks_dir, hook);
+        if chained.exists() {
+            bail!(
+                "Cannot chain to {} because {} already exists",
+                path.display(),
+                chained.display()
+            );
+        }
+
+        fs::rename(&path, &chained)
+            .with_context(|| format!("moving {} to {}", path.display(), chained.display()))?;
+    }
+
+    fs::create_dir_all(hooks_dir).with_context(|| format!("creating {}", hooks_dir.display()))?;
+    fs::write(&path, hook.script()).with_context(|| format!("writing {}", path.display()))?;
+    make_executable(&path)?;
+
+    Ok(before)
+}
+
+/// Remove the nomad version of `hook`, restoring any hook it was chaining to. Hooks that nomad
+/// did not write are left alone. Returns the state before uninstallation.
+pub fn uninstall(hooks_dir: &Path, hook: Hook) -> Result<HookState> {
+    let before = state(hooks_dir, hook)?;
+
+    if let HookState::Nomad { chained } = before {
+        let path = hook_path(hooks_dir, hook);
+        fs::remove_file(&path).with_context(|| format!("removing {}", path.display()))?;
+
+        if chained {
+            let chained = chained_path(hooks_dir, hook);
+            fs::rename(&chained, &path)
+                .with_context(|| format!("moving {} to {}", chained.display(), path.display()))?;
+        }
+    }
+
+    Ok(before)
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("making {} executable", path.display()))
+}
+
+/// Git for Windows runs hooks through its bundled shell regardless of permissions.
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::{chained_path, hook_path, install, state, uninstall, Hook, HookState};
+
+    /// Installing into an empty directory writes executable nomad hooks.
+    #[test]
+    fn install_fresh() {
+        let tmpdir = tempdir().unwrap();
+        let hooks_dir = tmpdir.path().join("hooks");
+
+        for hook in Hook::ALL {
+            assert_eq!(install(&hooks_dir, hook).unwrap(), HookState::Missing);
+            assert_eq!(
+                state(&hooks_dir, hook).unwrap(),
+                HookState::Nomad { chained: false }
+            );
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = fs::metadata(hook_path(&hooks_dir, hook))
+                    .unwrap()
+                    .permissions()
+                    .mode();
+                assert_eq!(mode & 0o111, 0o111);
+            }
+        }
+    }
+
+    /// Installing twice should update in place rather than chaining to itself.
+    #[test]
+    fn install_twice() {
+        let tmpdir = tempdir().unwrap();
+
+        install(tmpdir.path(), Hook::ReferenceTransaction).unwrap();
+        assert_eq!(
+            install(tmpdir.path(), Hook::ReferenceTransaction).unwrap(),
+            HookState::Nomad { chained: false }
+        );
+        assert!(!chained_path(tmpdir.path(), Hook::ReferenceTransaction).exists());
+    }
+
+    /// An existing hook should be chained on install and restored on uninstall.
+    #[test]
+    fn chain_and_restore() {
+        let tmpdir = tempdir().unwrap();
+        let path = hook_path(tmpdir.path(), Hook::ReferenceTransaction);
+        let existing = "#!/bin/sh\necho existing\n";
+        fs::write(&path, existing).unwrap();
+
+        assert_eq!(
+            install(tmpdir.path(), Hook::ReferenceTransaction).unwrap(),
+            HookState::Other
+        );
+        assert_eq!(
+            fs::read_to_string(chained_path(tmpdir.path(), Hook::ReferenceTransaction)).unwrap(),
+            existing
+        );
+
+        assert_eq!(
+            uninstall(tmpdir.path(), Hook::ReferenceTransaction).unwrap(),
+            HookState::Nomad { chained: true }
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), existing);
+        assert!(!chained_path(tmpdir.path(), Hook::ReferenceTransaction).exists());
+    }
+
+    /// The chained `reference-transaction` hook should get exactly the same stdin, even when it
+    /// is empty.
+    #[cfg(unix)]
+    #[test]
+    fn chain_forwards_stdin() {
+        use std::{
+            io::Write,
+            os::unix::fs::PermissionsExt,
+            process::{Command, Stdio},
+        };
+
+        let tmpdir = tempdir().unwrap();
+        let path = hook_path(tmpdir.path(), Hook::ReferenceTransaction);
+        let received = tmpdir.path().join("received");
+        fs::write(
+            &path,
+            format!("#!/bin/sh\ncat > '{}'\n", received.display()),
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        install(tmpdir.path(), Hook::ReferenceTransaction).unwrap();
+
+        for input in ["", "0000 1111 refs/heads/master\n"] {
+            let mut hook = Command::new(&path)
+                .arg("prepared")
+                .stdin(Stdio::piped())
+                .spawn()
+                .unwrap();
+            hook.stdin
+                .take()
+                .unwrap()
+                .write_all(input.as_bytes())
+                .unwrap();
+            assert!(hook.wait().unwrap().success());
+            assert_eq!(fs::read_to_string(&received).unwrap(), input);
+        }
+    }
+
+    /// Uninstalling should never touch hooks that nomad did not write.
+    #[test]
+    fn uninstall_leaves_other_hooks() {
+        let tmpdir = tempdir().unwrap();
+        let path = hook_path(tmpdir.path(), Hook::ReferenceTransaction);
+        fs::write(&path, "#!/bin/sh\n").unwrap();
+
+        assert_eq!(
+            uninstall(tmpdir.path(), Hook::ReferenceTransaction).unwrap(),
+            HookState::Other
+        );
+        assert!(path.exists());
+
+        let empty = tempdir().unwrap();
+        assert_eq!(
+            uninstall(empty.path(), Hook::ReferenceTransaction).unwrap(),
+            HookState::Missing
+        );
+    }
+}
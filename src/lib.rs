@@ -0,0 +1,82 @@
+//! Synchronize work-in-progress git branches in a light weight fashion.
+//!
+//! The `git-nomad` binary only adds command line parsing on top of this library, which embedders
+//! can skip entirely by building a [`Workflow`] and executing it against a [`GitBinary`]:
+//!
+//! ```no_run
+//! use std::{borrow::Cow, path::Path};
+//!
+//! use git_nomad::{
+//!     BranchPatterns, GitBinary, Host, Remote, SyncExtras, TerminalRenderer, User, Verbosity,
+//!     Workflow,
+//! };
+//!
+//! let mut renderer = TerminalRenderer::stdout();
+//! let git = GitBinary::new(
+//!     &mut renderer,
+//!     Some(Verbosity::default()),
+//!     Cow::from("git"),
+//!     Path::new("/path/to/clone"),
+//! )?;
+//!
+//! Workflow::Sync {
+//!     user: User::from("me"),
+//!     host: Host::from("laptop"),
+//!     remotes: vec![Remote::from("origin")],
+//!     branch_patterns: BranchPatterns::default(),
+//!     extras: SyncExtras::default(),
+//!     dry_run: false,
+//! }
+//! .execute(&mut renderer, &git)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! The items re-exported here are the stable API. The modules are public so that the types in
+//! their signatures can be named, but may change between minor versions.
+
+pub mod error;
+pub mod git_backend;
+pub mod git_binary;
+#[cfg(feature = "gix")]
+pub mod git_gix;
+pub mod git_ref;
+mod hooks;
+pub mod ls_format;
+pub mod renderer;
+pub mod snapshot;
+pub mod types;
+pub mod verbosity;
+pub mod workflow;
+
+#[cfg(test)]
+mod git_fake;
+#[cfg(test)]
+mod git_testing;
+
+pub use crate::{
+    error::NomadError,
+    git_backend::GitBackend,
+    git_binary::GitBinary,
+    renderer::{Renderer, TerminalRenderer},
+    snapshot::Snapshot,
+    types::{Branch, Host, NomadRef, Remote, User},
+    verbosity::Verbosity,
+    workflow::{BranchPatterns, SyncExtras, Workflow},
+};
+
+/// The `git config` key (within the `nomad` section) for the user that refs are pushed as.
+pub const CONFIG_USER: &str = "user";
+/// The `git config` key (within the `nomad` section) for the name of this host.
+pub const CONFIG_HOST: &str = "host";
+/// The `git config` key (within the `nomad` section) for the remotes to sync with, which can be
+/// set multiple times.
+pub const CONFIG_REMOTE: &str = "remote";
+/// The `git config` key (within the `nomad` section) for glob patterns of branches to push.
+pub const CONFIG_INCLUDE: &str = "include";
+/// The `git config` key (within the `nomad` section) for glob patterns of branches not to push.
+pub const CONFIG_EXCLUDE: &str = "exclude";
+/// The `git config` key (within the `nomad` section) for whether to sync `git stash` entries.
+pub const CONFIG_SYNC_STASH: &str = "syncStash";
+/// The `git config` key (within the `nomad` section) for whether to sync tags.
+pub const CONFIG_SYNC_TAGS: &str = "syncTags";
+pub use git_binary::CONFIG_CLONE_ID;
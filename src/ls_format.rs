@@ -0,0 +1,220 @@
+//! Templates for `ls --format`, modelled after `git for-each-ref --format`.
+
+use crate::{git_binary::RefDetails, git_ref::GitRef, types::NomadRef};
+
+/// The placeholders that can appear as `%(name)` in a template.
+const PLACEHOLDERS: &[(&str, Field)] = &[
+    ("user", Field::User),
+    ("host", Field::Host),
+    ("branch", Field::Branch),
+    ("refname", Field::RefName),
+    ("objectname", Field::ObjectName),
+    ("objectname:short", Field::ShortObjectName),
+    ("subject", Field::Subject),
+    ("committerdate:relative", Field::RelativeDate),
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Field {
+    User,
+    Host,
+    Branch,
+    RefName,
+    ObjectName,
+    ShortObjectName,
+    Subject,
+    RelativeDate,
+}
+
+impl Field {
+    /// Whether the field can only be filled in from [`RefDetails`].
+    fn needs_details(self) -> bool {
+        match self {
+            Self::User | Self::Host | Self::Branch | Self::RefName | Self::ObjectName => false,
+            Self::ShortObjectName | Self::Subject | Self::RelativeDate => true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+/// A parsed `ls --format` template, printed once per ref.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LsFormat(Vec<Segment>);
+
+impl LsFormat {
+    /// Parse a template where `%(name)` is replaced by a field of each ref and `%%` is a literal
+    /// `%`.
+    ///
+    /// Returns a `String` error to plug into `clap` as a value parser.
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut rest = template;
+
+        while let Some(index) = rest.find('%') {
+            literal.push_str(&rest[..index]);
+            rest = &rest[index..];
+
+            if let Some(after) = rest.strip_prefix("%%") {
+                literal.push('%');
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("%(") {
+                let end = after
+                    .find(')')
+                    .ok_or_else(|| format!("unterminated placeholder in {:?}", template))?;
+                let name = &after[..end];
+                let field = PLACEHOLDERS
+                    .iter()
+                    .find(|(placeholder, _)| *placeholder == name)
+                    .map(|(_, field)| *field)
+                    .ok_or_else(|| {
+                        let known = PLACEHOLDERS
+                            .iter()
+                            .map(|(placeholder, _)| format!("%({})", placeholder))
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        format!("unknown placeholder %({}), expected one of {}", name, known)
+                    })?;
+
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                segments.push(Segment::Field(field));
+                rest = &after[end + 1..];
+            } else {
+                literal.push('%');
+                rest = &rest[1..];
+            }
+        }
+
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Self(segments))
+    }
+
+    /// Whether [`LsFormat::render`] needs the [`RefDetails`] of each ref, which cost an extra
+    /// `git` invocation to look up.
+    pub fn needs_details(&self) -> bool {
+        self.0.iter().any(|segment| match segment {
+            Segment::Literal(_) => false,
+            Segment::Field(field) => field.needs_details(),
+        })
+    }
+
+    /// Fill in the template for a single ref.
+    ///
+    /// Fields that come from `details` are left empty if it is `None`.
+    pub fn render(&self, nomad_ref: &NomadRef<GitRef>, details: Option<&RefDetails>) -> String {
+        let mut rendered = String::new();
+
+        for segment in &self.0 {
+            let field = match segment {
+                Segment::Literal(literal) => {
+                    rendered.push_str(literal);
+                    continue;
+                }
+                Segment::Field(field) => field,
+            };
+
+            let value = match field {
+                Field::User => Some(nomad_ref.user.0.as_ref()),
+                Field::Host => Some(nomad_ref.host.0.as_ref()),
+                Field::Branch => Some(nomad_ref.branch.0.as_ref()),
+                Field::RefName => Some(nomad_ref.ref_.name.as_str()),
+                Field::ObjectName => Some(nomad_ref.ref_.commit_id.as_str()),
+                Field::ShortObjectName => details.map(|details| details.short_id.as_str()),
+                Field::Subject => details.map(|details| details.subject.as_str()),
+                Field::RelativeDate => details.map(|details| details.relative_date.as_str()),
+            };
+            rendered.push_str(value.unwrap_or_default());
+        }
+
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        git_binary::RefDetails,
+        git_ref::GitRef,
+        types::{Branch, Host, NomadRef, RefKind, User},
+    };
+
+    use super::LsFormat;
+
+    fn nomad_ref() -> NomadRef<'static, GitRef> {
+        NomadRef {
+            user: User::from("user0"),
+            host: Host::from("host0"),
+            kind: RefKind::Branch,
+            branch: Branch::from("feature/login"),
+            ref_: GitRef {
+                commit_id: "0123456789abcdef".to_string(),
+                name: "refs/nomad/host0/feature/login".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn render() {
+        let details = RefDetails {
+            short_id: "0123456".to_string(),
+            relative_date: "3 days ago".to_string(),
+            subject: "Add login page".to_string(),
+        };
+
+        for (template, expected) in [
+            ("", ""),
+            ("plain", "plain"),
+            ("%(host):%(branch)", "host0:feature/login"),
+            (
+                "%(user) %(refname) %(objectname)",
+                "user0 refs/nomad/host0/feature/login 0123456789abcdef",
+            ),
+            (
+                "%(objectname:short)\t%(committerdate:relative)\t%(subject)",
+                "0123456\t3 days ago\tAdd login page",
+            ),
+            ("100%% %(host) 5% off", "100% host0 5% off"),
+        ] {
+            let format = LsFormat::parse(template).unwrap();
+            assert_eq!(
+                format.render(&nomad_ref(), Some(&details)),
+                expected,
+                "{:?}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn render_without_details() {
+        let format = LsFormat::parse("%(branch) [%(subject)]").unwrap();
+        assert!(format.needs_details());
+        assert_eq!(format.render(&nomad_ref(), None), "feature/login []");
+    }
+
+    #[test]
+    fn needs_details() {
+        for template in ["", "%(host)", "%(objectname) %(refname)"] {
+            let format = LsFormat::parse(template).unwrap();
+            assert!(!format.needs_details(), "{:?}", template);
+        }
+    }
+
+    #[test]
+    fn parse_error() {
+        for template in ["%(", "%(host", "%()", "%(nope)", "%(objectname:long)"] {
+            assert!(LsFormat::parse(template).is_err(), "{:?}", template);
+        }
+    }
+}
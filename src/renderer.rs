@@ -13,6 +13,10 @@ pub trait Renderer {
         description: impl Into<Cow<'static, str>>,
         func: impl FnOnce() -> Result<T>,
     ) -> Result<T>;
+
+    /// Ask the user a question, where an empty answer (or not being able to ask at all) means
+    /// accepting `default`.
+    fn prompt(&mut self, question: &str, default: &str) -> Result<String>;
 }
 
 pub struct TerminalRenderer(Term);
@@ -59,6 +63,20 @@ impl Renderer for TerminalRenderer {
 
         ret
     }
+
+    fn prompt(&mut self, question: &str, default: &str) -> Result<String> {
+        // Reading from something that isn't a terminal always gives back an empty line.
+        if !self.0.is_term() {
+            return Ok(default.to_owned());
+        }
+
+        write!(self.0, "{} [{}]: ", question, default)?;
+        self.0.flush()?;
+
+        let answer = self.0.read_line()?;
+        let answer = answer.trim();
+        Ok(if answer.is_empty() { default } else { answer }.to_owned())
+    }
 }
 
 /// Adds a newline to separate output from spinners, but that's only necessary if spinners are even
@@ -75,7 +93,7 @@ pub fn add_newline_if_spinners_are_visible(renderer: &mut impl Renderer) -> Resu
 }
 
 #[cfg(test)]
-pub mod test_terminal {
+pub(crate) mod test_terminal {
     use anyhow::Context;
 
     use crate::renderer::{Renderer, TerminalRenderer};
@@ -108,29 +126,40 @@ pub mod test_terminal {
 }
 
 #[cfg(test)]
-pub mod test {
+pub(crate) mod test {
     use std::io::Write;
-    use std::{borrow::Cow, io};
+    use std::{borrow::Cow, collections::VecDeque, io};
 
     use anyhow::{Context, Result};
 
     use super::{add_newline_if_spinners_are_visible, Renderer};
 
-    pub struct MemoryRenderer(Vec<u8>);
+    pub struct MemoryRenderer {
+        output: Vec<u8>,
+        answers: VecDeque<String>,
+    }
 
     impl MemoryRenderer {
         pub fn new() -> Self {
-            Self(Vec::new())
+            Self::with_answers([] as [&str; 0])
+        }
+
+        /// Answer prompts with `answers` in order, falling back to the default once they run out.
+        pub fn with_answers<'a>(answers: impl IntoIterator<Item = &'a str>) -> Self {
+            Self {
+                output: Vec::new(),
+                answers: answers.into_iter().map(String::from).collect(),
+            }
         }
 
         pub fn as_str(&self) -> &str {
-            std::str::from_utf8(self.0.as_slice()).expect("tests should have utf8 output")
+            std::str::from_utf8(self.output.as_slice()).expect("tests should have utf8 output")
         }
     }
 
     impl Renderer for MemoryRenderer {
         fn writer<T>(&mut self, func: impl FnOnce(&mut dyn Write) -> Result<T>) -> Result<T> {
-            func(&mut self.0)
+            func(&mut self.output)
         }
 
         fn are_spinners_visible(&self) -> bool {
@@ -142,9 +171,19 @@ pub mod test {
             description: impl Into<Cow<'static, str>>,
             func: impl FnOnce() -> Result<T>,
         ) -> Result<T> {
-            writeln!(self.0, "{}...", description.into())?;
+            writeln!(self.output, "{}...", description.into())?;
             func()
         }
+
+        fn prompt(&mut self, question: &str, default: &str) -> Result<String> {
+            let answer = self
+                .answers
+                .pop_front()
+                .filter(|answer| !answer.is_empty())
+                .unwrap_or_else(|| default.to_owned());
+            writeln!(self.output, "{} [{}]: {}", question, default, answer)?;
+            Ok(answer)
+        }
     }
 
     pub struct NoRenderer;
@@ -165,6 +204,10 @@ pub mod test {
         ) -> Result<T> {
             func()
         }
+
+        fn prompt(&mut self, _question: &str, default: &str) -> Result<String> {
+            Ok(default.to_owned())
+        }
     }
 
     #[test]
@@ -191,6 +234,18 @@ pub mod test {
         assert!(func_called);
     }
 
+    #[test]
+    fn prompt() {
+        let mut renderer = MemoryRenderer::with_answers(["answer", ""]);
+        assert_eq!(renderer.prompt("First", "default").unwrap(), "answer");
+        assert_eq!(renderer.prompt("Second", "default").unwrap(), "default");
+        assert_eq!(renderer.prompt("Third", "default").unwrap(), "default");
+        assert_eq!(
+            renderer.as_str(),
+            "First [default]: answer\nSecond [default]: default\nThird [default]: default\n"
+        );
+    }
+
     #[test]
     fn add_newline() {
         let mut renderer = MemoryRenderer::new();
@@ -1,6 +1,6 @@
 use std::collections::{HashMap, HashSet};
 
-use crate::types::{Branch, Host, NomadRef, RemoteNomadRefSet, User};
+use crate::types::{Branch, Host, NomadRef, RefKind, RemoteNomadRefSet, User};
 
 /// A point in time view of refs we care about. [`Snapshot`] is only for local branches and refs
 /// and thus is scoped under a specific [`User`].
@@ -48,11 +48,16 @@ impl<Ref> Snapshot<'_, Ref> {
 impl<'a, Ref> Snapshot<'a, Ref> {
     /// Find nomad host branches that can be pruned because:
     /// 1. The local branch they were based on no longer exists.
-    /// 2. The remote branch they were based on no longer exists.
+    /// 2. The ref they were based on is rejected by `ref_filter`, given its [`RefKind`] and name.
+    /// 3. The remote branch they were based on no longer exists.
+    ///
+    /// Only refs of [`RefKind::Branch`] are checked against the local branches, so `ref_filter`
+    /// decides alone whether refs of other kinds still exist.
     pub fn prune_deleted_branches(
         self,
         host: &Host,
         remote_nomad_refs: &RemoteNomadRefSet,
+        ref_filter: impl Fn(RefKind, &Branch) -> bool,
     ) -> Vec<PruneFrom<'a, Ref>> {
         let Self {
             nomad_refs,
@@ -64,7 +69,9 @@ impl<'a, Ref> Snapshot<'a, Ref> {
 
         for nomad_ref in nomad_refs {
             if &nomad_ref.host == host {
-                if !local_branches.contains(&nomad_ref.branch) {
+                let is_deleted = nomad_ref.kind == RefKind::Branch
+                    && !local_branches.contains(&nomad_ref.branch);
+                if is_deleted || !ref_filter(nomad_ref.kind, &nomad_ref.branch) {
                     prune.push(PruneFrom::LocalAndRemote(nomad_ref));
                 }
             } else if !remote_nomad_refs.contains(&nomad_ref) {
@@ -105,7 +112,7 @@ impl<'a, Ref> Snapshot<'a, Ref> {
         let mut as_vec = by_host
             .into_iter()
             .map(|(host, mut branches)| {
-                branches.sort_by(|a, b| a.branch.cmp(&b.branch));
+                branches.sort_by(|a, b| (a.kind, &a.branch).cmp(&(b.kind, &b.branch)));
                 (host, branches)
             })
             .collect::<Vec<_>>();
@@ -119,7 +126,7 @@ impl<'a, Ref> Snapshot<'a, Ref> {
 mod tests {
     use std::iter::FromIterator;
 
-    use crate::types::{Host, RemoteNomadRefSet, User};
+    use crate::types::{Host, RefKind, RemoteNomadRefSet, User};
 
     use super::{Branch, NomadRef, PruneFrom, Snapshot};
 
@@ -134,18 +141,21 @@ mod tests {
                 NomadRef {
                     user: user.always_borrow(),
                     host: Host::from("host0"),
+                    kind: RefKind::Branch,
                     branch: Branch::from("branch0"),
                     ref_: (),
                 },
                 NomadRef {
                     user: user.always_borrow(),
                     host: Host::from("host0"),
+                    kind: RefKind::Branch,
                     branch: Branch::from("branch1"),
                     ref_: (),
                 },
                 NomadRef {
                     user: user.always_borrow(),
                     host: Host::from("host1"),
+                    kind: RefKind::Branch,
                     branch: Branch::from("branch1"),
                     ref_: (),
                 },
@@ -156,11 +166,14 @@ mod tests {
     fn remote_nomad_refs(
         collection: impl IntoIterator<Item = (&'static str, &'static str, &'static str)>,
     ) -> RemoteNomadRefSet {
-        RemoteNomadRefSet::from_iter(
-            collection.into_iter().map(|(user, host, branch)| {
-                (User::from(user), Host::from(host), Branch::from(branch))
-            }),
-        )
+        RemoteNomadRefSet::from_iter(collection.into_iter().map(|(user, host, branch)| {
+            (
+                User::from(user),
+                Host::from(host),
+                RefKind::Branch,
+                Branch::from(branch),
+            )
+        }))
     }
 
     /// Sets up the scenario where:
@@ -178,6 +191,7 @@ mod tests {
         let prune = snapshot(user, ["branch0", "branch1"]).prune_deleted_branches(
             &Host::from("host0"),
             &remote_nomad_refs([("user0", "host1", "branch1")]),
+            |_, _| true,
         );
 
         assert_eq!(prune, Vec::new());
@@ -202,6 +216,7 @@ mod tests {
                 ("user0", "host0", "branch1"),
                 ("user0", "host1", "branch1"),
             ]),
+            |_, _| true,
         );
 
         assert_eq!(prune, Vec::new());
@@ -234,6 +249,7 @@ mod tests {
                 ("user0", "host0", "branch1"),
                 ("user0", "host1", "branch1"),
             ]),
+            |_, _| true,
         );
 
         assert_eq!(
@@ -241,6 +257,7 @@ mod tests {
             vec![PruneFrom::LocalAndRemote(NomadRef {
                 user: User::from("user0"),
                 host: Host::from("host0"),
+                kind: RefKind::Branch,
                 branch: Branch::from("branch1"),
                 ref_: (),
             })]
@@ -268,6 +285,7 @@ mod tests {
                 // This remote nomad ref for another host has been removed
                 // ("user0", "host1", "branch1"),
             ]),
+            |_, _| true,
         );
 
         assert_eq!(
@@ -275,12 +293,75 @@ mod tests {
             vec![PruneFrom::LocalOnly(NomadRef {
                 user: User::from("user0"),
                 host: Host::from("host1"),
+                kind: RefKind::Branch,
+                branch: Branch::from("branch1"),
+                ref_: (),
+            })]
+        );
+    }
+
+    /// Sets up the scenario where:
+    ///
+    ///     There are local branches
+    ///     ... That have nomad refs
+    ///     ... But are excluded by the branch filter
+    ///
+    ///     There are local nomad refs from other hosts
+    ///     ... That have corresponding remote nomad refs
+    ///
+    /// In this case, we should remove the nomad refs for the excluded local branches, but leave
+    /// the same branch from other hosts alone.
+    #[test]
+    fn snapshot_prune_removes_excluded_branches() {
+        let user = &User::from("user0");
+        let prune = snapshot(user, ["branch0", "branch1"]).prune_deleted_branches(
+            &Host::from("host0"),
+            &remote_nomad_refs([
+                ("user0", "host0", "branch0"),
+                ("user0", "host0", "branch1"),
+                ("user0", "host1", "branch1"),
+            ]),
+            |_, branch| branch != &Branch::from("branch1"),
+        );
+
+        assert_eq!(
+            prune,
+            vec![PruneFrom::LocalAndRemote(NomadRef {
+                user: User::from("user0"),
+                host: Host::from("host0"),
+                kind: RefKind::Branch,
                 branch: Branch::from("branch1"),
                 ref_: (),
             })]
         );
     }
 
+    /// Refs of other kinds are not branches, so only the filter decides whether they are kept,
+    /// even when a local branch has the same name.
+    #[test]
+    fn snapshot_prune_other_kinds_by_filter() {
+        let user = &User::from("user0");
+        let stash_ref = |index: &'static str| NomadRef {
+            user: user.always_borrow(),
+            host: Host::from("host0"),
+            kind: RefKind::Stash,
+            branch: Branch::from(index),
+            ref_: (),
+        };
+        let prune = Snapshot::new(
+            user,
+            [Branch::from("1")].into_iter().collect(),
+            vec![stash_ref("0"), stash_ref("1")],
+        )
+        .prune_deleted_branches(
+            &Host::from("host0"),
+            &remote_nomad_refs([]),
+            |kind, branch| kind == RefKind::Stash && branch == &Branch::from("0"),
+        );
+
+        assert_eq!(prune, vec![PruneFrom::LocalAndRemote(stash_ref("1"))]);
+    }
+
     /// [`Snapshot::prune_all`] should remove all branches.
     #[test]
     fn snapshot_prune_all() {
@@ -292,18 +373,21 @@ mod tests {
                 PruneFrom::LocalAndRemote(NomadRef {
                     user: User::from("user0"),
                     host: Host::from("host0"),
+                    kind: RefKind::Branch,
                     branch: Branch::from("branch0"),
                     ref_: (),
                 }),
                 PruneFrom::LocalAndRemote(NomadRef {
                     user: User::from("user0"),
                     host: Host::from("host0"),
+                    kind: RefKind::Branch,
                     branch: Branch::from("branch1"),
                     ref_: (),
                 }),
                 PruneFrom::LocalAndRemote(NomadRef {
                     user: User::from("user0"),
                     host: Host::from("host1"),
+                    kind: RefKind::Branch,
                     branch: Branch::from("branch1"),
                     ref_: (),
                 }),
@@ -323,16 +407,183 @@ mod tests {
                 PruneFrom::LocalAndRemote(NomadRef {
                     user: User::from("user0"),
                     host: Host::from("host0"),
+                    kind: RefKind::Branch,
                     branch: Branch::from("branch0"),
                     ref_: (),
                 },),
                 PruneFrom::LocalAndRemote(NomadRef {
                     user: User::from("user0"),
                     host: Host::from("host0"),
+                    kind: RefKind::Branch,
                     branch: Branch::from("branch1"),
                     ref_: (),
                 },),
             ],
         );
     }
+
+    /// Property based tests over arbitrary combinations of local branches, local nomad refs and
+    /// remote nomad refs, drawn from small pools of names so that they overlap often.
+    mod properties {
+        use std::collections::HashSet;
+
+        use proptest::{collection::hash_set, prelude::*};
+
+        use crate::types::{Host, RefKind, RemoteNomadRefSet, User};
+
+        use super::{Branch, NomadRef, PruneFrom, Snapshot};
+
+        const HOSTS: usize = 4;
+        const BRANCHES: usize = 5;
+
+        fn host(index: usize) -> Host<'static> {
+            Host::from(format!("host{}", index))
+        }
+
+        fn branch(index: usize) -> Branch<'static> {
+            Branch::from(format!("branch{}", index))
+        }
+
+        /// Pairs of `(host, branch)` indices.
+        fn host_branches() -> impl Strategy<Value = HashSet<(usize, usize)>> {
+            hash_set((0..HOSTS, 0..BRANCHES), 0..HOSTS * BRANCHES)
+        }
+
+        /// Branch indices.
+        fn branches() -> impl Strategy<Value = HashSet<usize>> {
+            hash_set(0..BRANCHES, 0..=BRANCHES)
+        }
+
+        /// The `ref_` of each nomad ref is its `(host, branch)` indices, so that pruned refs can
+        /// be traced back to the inputs.
+        fn snapshot<'a>(
+            user: &'a User,
+            local_branches: &HashSet<usize>,
+            nomad_refs: &HashSet<(usize, usize)>,
+        ) -> Snapshot<'a, (usize, usize)> {
+            Snapshot::new(
+                user,
+                local_branches.iter().copied().map(branch).collect(),
+                nomad_refs
+                    .iter()
+                    .map(|&(h, b)| NomadRef {
+                        user: user.always_borrow(),
+                        host: host(h),
+                        kind: RefKind::Branch,
+                        branch: branch(b),
+                        ref_: (h, b),
+                    })
+                    .collect(),
+            )
+        }
+
+        fn remote_nomad_refs(user: &User, refs: &HashSet<(usize, usize)>) -> RemoteNomadRefSet {
+            refs.iter()
+                .map(|&(h, b)| (user.always_borrow(), host(h), RefKind::Branch, branch(b)))
+                .collect()
+        }
+
+        proptest! {
+            /// Each nomad ref is pruned at most once, from the right place, and exactly when its
+            /// local branch (for this host) or remote counterpart (for other hosts) is gone.
+            #[test]
+            fn prune_deleted_branches(
+                current in 0..HOSTS,
+                local_branches in branches(),
+                excluded in branches(),
+                nomad_refs in host_branches(),
+                remote in host_branches(),
+            ) {
+                let user = User::from("user0");
+                let prune = snapshot(&user, &local_branches, &nomad_refs).prune_deleted_branches(
+                    &host(current),
+                    &remote_nomad_refs(&user, &remote),
+                    |_, b| !excluded.iter().any(|&e| *b == branch(e)),
+                );
+
+                let mut pruned = HashSet::new();
+                for prune_from in prune {
+                    let (h, b) = match prune_from {
+                        PruneFrom::LocalOnly(nomad_ref) => {
+                            // Other hosts' refs are theirs to delete from the remote.
+                            prop_assert_ne!(&nomad_ref.host, &host(current));
+                            nomad_ref.ref_
+                        }
+                        PruneFrom::LocalAndRemote(nomad_ref) => {
+                            prop_assert_eq!(&nomad_ref.host, &host(current));
+                            nomad_ref.ref_
+                        }
+                    };
+                    prop_assert!(pruned.insert((h, b)), "pruned {:?} twice", (h, b));
+                }
+
+                for &(h, b) in &nomad_refs {
+                    let expected = if h == current {
+                        !local_branches.contains(&b) || excluded.contains(&b)
+                    } else {
+                        !remote.contains(&(h, b))
+                    };
+                    prop_assert_eq!(pruned.contains(&(h, b)), expected, "{:?}", (h, b));
+                }
+                prop_assert!(pruned.is_subset(&nomad_refs));
+            }
+
+            /// Whether the remote still has this host's refs never matters, since the local
+            /// branches are the source of truth for them.
+            #[test]
+            fn prune_deleted_branches_ignores_remote_for_current_host(
+                current in 0..HOSTS,
+                local_branches in branches(),
+                nomad_refs in host_branches(),
+                remote in host_branches(),
+            ) {
+                let user = User::from("user0");
+                let prune = |remote: &HashSet<(usize, usize)>| {
+                    snapshot(&user, &local_branches, &nomad_refs).prune_deleted_branches(
+                        &host(current),
+                        &remote_nomad_refs(&user, remote),
+                        |_, _| true,
+                    )
+                };
+
+                let other_hosts_only = remote
+                    .iter()
+                    .copied()
+                    .filter(|&(h, _)| h != current)
+                    .collect::<HashSet<_>>();
+                prop_assert_eq!(prune(&remote), prune(&other_hosts_only));
+            }
+
+            /// Exactly the nomad refs for matching hosts are pruned, everywhere, in their
+            /// original order.
+            #[test]
+            fn prune_by_hosts(
+                local_branches in branches(),
+                nomad_refs in host_branches(),
+                hosts in hash_set(0..HOSTS, 0..=HOSTS),
+            ) {
+                let user = User::from("user0");
+                let snapshot = snapshot(&user, &local_branches, &nomad_refs);
+                let expected = snapshot
+                    .nomad_refs
+                    .iter()
+                    .map(|nomad_ref| nomad_ref.ref_)
+                    .filter(|(h, _)| hosts.contains(h))
+                    .collect::<Vec<_>>();
+
+                let prune = snapshot.prune_by_hosts(|h| hosts.iter().any(|&i| *h == host(i)));
+
+                let mut actual = Vec::new();
+                for prune_from in prune {
+                    match prune_from {
+                        PruneFrom::LocalAndRemote(nomad_ref) => actual.push(nomad_ref.ref_),
+                        PruneFrom::LocalOnly(nomad_ref) => {
+                            prop_assert!(false, "unexpected LocalOnly {:?}", nomad_ref)
+                        }
+                    }
+                }
+                prop_assert_eq!(actual, expected);
+            }
+        }
+    }
 }
@@ -67,6 +67,33 @@ impl_str_from!(Branch);
 impl_str_possibly_clone!(Branch);
 impl_str_always_borrow!(Branch);
 
+/// What a nomad managed ref was published for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RefKind {
+    /// A local branch.
+    Branch,
+    /// A `git stash` entry, published under its index in a namespace of its own so that it never
+    /// clashes with a branch.
+    Stash,
+    /// A snapshot of the working tree, published under the name of the branch that was checked out
+    /// in a namespace of its own.
+    Worktree,
+    /// A local tag, published under its name in a namespace of its own.
+    Tag,
+}
+
+impl RefKind {
+    /// The name used for this kind in machine readable output.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Branch => "branch",
+            Self::Stash => "stash",
+            Self::Worktree => "worktree",
+            Self::Tag => "tag",
+        }
+    }
+}
+
 /// Represents "who" a given branch belongs to. This value should be shared by multiple git
 /// clones that belong to the same user.
 ///
@@ -99,6 +126,8 @@ pub struct NomadRef<'a, Ref> {
     pub user: User<'a>,
     /// The host this branch comes from.
     pub host: Host<'a>,
+    /// What this ref was published for, which decides the namespace it lives in.
+    pub kind: RefKind,
     /// The branch name.
     pub branch: Branch<'a>,
     /// Any additional internal data representing the underlying git ref.
@@ -107,7 +136,7 @@ pub struct NomadRef<'a, Ref> {
 
 /// A specialized container to represent nomad managed refs that a remote knows about.
 pub struct RemoteNomadRefSet {
-    set: HashSet<(User<'static>, Host<'static>, Branch<'static>)>,
+    set: HashSet<(User<'static>, Host<'static>, RefKind, Branch<'static>)>,
 }
 
 impl RemoteNomadRefSet {
@@ -115,7 +144,7 @@ impl RemoteNomadRefSet {
     ///
     /// Note that the `Ref` part of `NomadRef<Ref>` is completely ignored, since we don't care
     /// about the intrinsic git ref being pointed to, merely that the remote is still tracking a
-    /// nomad ref with the given user/host/branch.
+    /// nomad ref with the given user/host/kind/branch.
     pub fn contains<Ref>(&self, nomad_ref: &NomadRef<Ref>) -> bool {
         // Performs a lookup without allocating.
         //
@@ -124,17 +153,39 @@ impl RemoteNomadRefSet {
         self.set.contains(&(
             nomad_ref.user.always_borrow(),
             nomad_ref.host.always_borrow(),
+            nomad_ref.kind,
             nomad_ref.branch.always_borrow(),
         ))
     }
+
+    /// Every nomad ref that at least one of the given sets knows about.
+    pub fn union<'a>(sets: impl IntoIterator<Item = &'a RemoteNomadRefSet>) -> Self {
+        RemoteNomadRefSet {
+            set: sets
+                .into_iter()
+                .flat_map(|remote_nomad_refs| remote_nomad_refs.set.iter())
+                .map(|(user, host, kind, branch)| {
+                    (
+                        user.clone(),
+                        host.clone(),
+                        *kind,
+                        Branch::from(branch.0.to_string()),
+                    )
+                })
+                .collect(),
+        }
+    }
 }
 
-impl<'a> FromIterator<(User<'a>, Host<'a>, Branch<'a>)> for RemoteNomadRefSet {
-    fn from_iter<T: IntoIterator<Item = (User<'a>, Host<'a>, Branch<'a>)>>(iter: T) -> Self {
-        let set = HashSet::from_iter(iter.into_iter().map(|(user, host, branch)| {
+impl<'a> FromIterator<(User<'a>, Host<'a>, RefKind, Branch<'a>)> for RemoteNomadRefSet {
+    fn from_iter<T: IntoIterator<Item = (User<'a>, Host<'a>, RefKind, Branch<'a>)>>(
+        iter: T,
+    ) -> Self {
+        let set = HashSet::from_iter(iter.into_iter().map(|(user, host, kind, branch)| {
             (
                 user.possibly_clone(),
                 host.possibly_clone(),
+                kind,
                 branch.possibly_clone(),
             )
         }));
@@ -146,9 +197,13 @@ impl<'a, Ref> FromIterator<NomadRef<'a, Ref>> for RemoteNomadRefSet {
     fn from_iter<T: IntoIterator<Item = NomadRef<'a, Ref>>>(iter: T) -> Self {
         Self::from_iter(iter.into_iter().map(|nomad_ref| {
             let NomadRef {
-                user, host, branch, ..
+                user,
+                host,
+                kind,
+                branch,
+                ..
             } = nomad_ref;
-            (user, host, branch)
+            (user, host, kind, branch)
         }))
     }
 }
@@ -1,10 +1,13 @@
 //! Helpers for executing [`Command`]s and parsing their [`Output`].
 
-use std::process::{Command, Output};
+use std::{
+    io::ErrorKind,
+    process::{Command, Output},
+};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, Result};
 
-use crate::renderer::Renderer;
+use crate::{error::NomadError, renderer::Renderer};
 
 /// What commands to display during workflow execution.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -135,9 +138,16 @@ pub fn output_stdout(output: Output) -> Result<String> {
 
 /// Invoke a [`Command`] and check its exit code for success.
 fn run_silent<S: AsRef<str>>(description: S, command: &mut Command) -> Result<Output> {
-    let output = command
-        .output()
-        .with_context(|| format!("{}: {:?}", description.as_ref(), command))?;
+    let output = command.output().map_err(|err| {
+        let not_found = err.kind() == ErrorKind::NotFound;
+        let err = anyhow::Error::new(err);
+        if not_found {
+            err.context(NomadError::GitNotFound)
+        } else {
+            err
+        }
+        .context(format!("{}: {:?}", description.as_ref(), command))
+    })?;
 
     if !output.status.success() {
         return dump_command_failure(command, &output);
@@ -146,7 +156,49 @@ fn run_silent<S: AsRef<str>>(description: S, command: &mut Command) -> Result<Ou
     Ok(output)
 }
 
+/// Recognize well known failures from what a `git` command printed to `stderr`.
+fn classify_failure(stderr: &str) -> Option<NomadError> {
+    let contains_any = |needles: &[&str]| needles.iter().any(|needle| stderr.contains(needle));
+
+    // Authentication failures are checked first since they are often reported as being unable to
+    // access or read from the remote.
+    if contains_any(&[
+        "Authentication failed",
+        "Permission denied (publickey",
+        "could not read Username",
+        "could not read Password",
+        "terminal prompts disabled",
+        "The requested URL returned error: 401",
+        "The requested URL returned error: 403",
+    ]) {
+        Some(NomadError::AuthenticationFailed)
+    } else if contains_any(&[
+        "Could not read from remote repository",
+        "Could not resolve host",
+        "Connection refused",
+        "Connection timed out",
+        "unable to access",
+        "does not appear to be a git repository",
+    ]) {
+        Some(NomadError::RemoteUnreachable)
+    } else if contains_any(&[
+        "[rejected]",
+        "[remote rejected]",
+        "failed to push some refs",
+    ]) {
+        Some(NomadError::PushRejected)
+    } else if contains_any(&["not a git repository"]) {
+        Some(NomadError::NotARepository)
+    } else if contains_any(&["is not a symbolic ref"]) {
+        Some(NomadError::DetachedHead)
+    } else {
+        None
+    }
+}
+
 /// Make some effort to build a decent error message for commands that fail.
+///
+/// Well known failures are attached as a [`NomadError`], see [`classify_failure`].
 fn dump_command_failure<T>(command: &Command, output: &Output) -> Result<T> {
     let forward = |name: &str, stream: &[u8]| {
         if stream.is_empty() {
@@ -160,13 +212,20 @@ fn dump_command_failure<T>(command: &Command, output: &Output) -> Result<T> {
         }
     };
 
-    bail!(
+    let message = format!(
         "command failure\n$ {:?}\n# status: {}{}{}",
         command,
         output.status,
         forward("STDOUT", &output.stdout),
         forward("STDERR", &output.stderr)
     );
+
+    Err(
+        match classify_failure(&String::from_utf8_lossy(&output.stderr)) {
+            Some(error) => anyhow::Error::new(error).context(message),
+            None => anyhow!(message),
+        },
+    )
 }
 
 fn run_spinner(
@@ -231,11 +290,12 @@ mod test {
     };
 
     use crate::{
+        error::{exit_code, NomadError},
         renderer::test::NoRenderer,
         verbosity::{run_notable, run_silent},
     };
 
-    use super::{dump_command_failure, output_stdout, run_trivial, Verbosity};
+    use super::{classify_failure, dump_command_failure, output_stdout, run_trivial, Verbosity};
 
     const ALL_VERBOSITIES: &[Option<Verbosity>] = &[
         None,
@@ -311,6 +371,77 @@ mod test {
         assert!(displayed_dump.contains("some stderr"));
     }
 
+    /// A missing binary should be reported as [`NomadError::GitNotFound`].
+    #[test]
+    fn test_not_found() {
+        let error =
+            run_silent("missing", &mut Command::new("git-nomad-does-not-exist")).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<NomadError>(),
+            Some(&NomadError::GitNotFound)
+        );
+        assert!(format!("{:?}", error).contains("git-nomad-does-not-exist"));
+    }
+
+    #[test]
+    fn test_classify_failure() {
+        for (stderr, expected) in [
+            (
+                "fatal: not a git repository (or any of the parent directories): .git",
+                Some(NomadError::NotARepository),
+            ),
+            (
+                "fatal: ref HEAD is not a symbolic ref",
+                Some(NomadError::DetachedHead),
+            ),
+            (
+                "fatal: '/nowhere' does not appear to be a git repository\n\
+                 fatal: Could not read from remote repository.",
+                Some(NomadError::RemoteUnreachable),
+            ),
+            (
+                "fatal: unable to access 'https://example.com/repo.git/': Could not resolve host: \
+                 example.com",
+                Some(NomadError::RemoteUnreachable),
+            ),
+            (
+                "git@example.com: Permission denied (publickey).\n\
+                 fatal: Could not read from remote repository.",
+                Some(NomadError::AuthenticationFailed),
+            ),
+            (
+                "fatal: could not read Username for 'https://example.com': terminal prompts \
+                 disabled",
+                Some(NomadError::AuthenticationFailed),
+            ),
+            (
+                "error: failed to push some refs to '/remote'",
+                Some(NomadError::PushRejected),
+            ),
+            ("fatal: something else entirely", None),
+            ("", None),
+        ] {
+            assert_eq!(classify_failure(stderr), expected, "{:?}", stderr);
+        }
+    }
+
+    /// Classified failures should still print everything in [`dump_command_failure`], and
+    /// determine the exit code.
+    #[test]
+    fn test_dump_command_failure_classified() {
+        let command = Command::new("git");
+
+        let output = Output {
+            status: ExitStatus::from_raw(128),
+            stdout: Vec::new(),
+            stderr: "fatal: not a git repository".as_bytes().to_vec(),
+        };
+
+        let dump = dump_command_failure::<()>(&command, &output).unwrap_err();
+        assert!(dump.to_string().contains("fatal: not a git repository"));
+        assert_eq!(exit_code(&dump), NomadError::NotARepository.exit_code());
+    }
+
     /// [`dump_command_failure`] should elide stderr when it is empty.
     #[test]
     fn test_dump_command_failure_just_stdout() {
@@ -1,47 +1,214 @@
 //! High level user invoked workflows for nomad.
 
-use std::{collections::HashSet, hash::Hash, io::Write};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    hash::Hash,
+    io::Write,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
 use crate::{
-    git_binary::GitBinary,
+    git_backend::GitBackend,
+    git_binary::{ConfigScope, RefDetails},
     git_ref::GitRef,
+    hooks::{self, Hook, HookState},
+    ls_format::LsFormat,
     renderer::{add_newline_if_spinners_are_visible, Renderer},
-    types::{Branch, Host, NomadRef, Remote, User},
+    snapshot::{PruneFrom, Snapshot},
+    types::{Branch, Host, NomadRef, RefKind, Remote, RemoteNomadRefSet, User},
+    CONFIG_HOST, CONFIG_REMOTE, CONFIG_USER,
 };
 
 /// A boundary type that separates the CLI interface from high level nomad workflows.
 #[derive(Debug, PartialEq, Eq)]
 pub enum Workflow<'a> {
+    Init {
+        user: InitSetting<User<'a>>,
+        host: InitSetting<Host<'a>>,
+        remotes: InitSetting<Vec<Remote<'a>>>,
+        scope: ConfigScope,
+        dry_run: bool,
+    },
+    ConfigShow {
+        settings: Vec<Setting>,
+    },
+    ConfigGet {
+        key: String,
+    },
+    ConfigSet {
+        key: String,
+        values: Vec<String>,
+        scope: ConfigScope,
+        dry_run: bool,
+    },
+    ConfigUnset {
+        key: String,
+        scope: ConfigScope,
+        dry_run: bool,
+    },
     Sync {
         user: User<'a>,
         host: Host<'a>,
-        remote: Remote<'a>,
+        remotes: Vec<Remote<'a>>,
+        branch_patterns: BranchPatterns,
+        extras: SyncExtras,
+        dry_run: bool,
+    },
+    Push {
+        user: User<'a>,
+        host: Host<'a>,
+        remotes: Vec<Remote<'a>>,
+        branch_patterns: BranchPatterns,
+        branches: Vec<Branch<'a>>,
+        dry_run: bool,
+    },
+    InstallHooks {
+        dry_run: bool,
+    },
+    UninstallHooks {
+        dry_run: bool,
     },
     Ls {
         printer: LsPrinter,
         user: User<'a>,
-        fetch_remote: Option<Remote<'a>>,
+        fetch_remotes: Vec<Remote<'a>>,
         host_filter: Filter<Host<'a>>,
         branch_filter: Filter<Branch<'a>>,
     },
     Purge {
         user: User<'a>,
-        remote: Remote<'a>,
+        remotes: Vec<Remote<'a>>,
+        host_filter: Filter<Host<'a>>,
+        older_than: Option<Duration>,
+        dry_run: bool,
+    },
+    Checkout {
+        user: User<'a>,
+        fetch_remotes: Vec<Remote<'a>>,
+        host_filter: Filter<Host<'a>>,
+        branch: Branch<'a>,
+        force: bool,
+        dry_run: bool,
+    },
+    StashApply {
+        user: User<'a>,
+        host: Host<'a>,
+        index: usize,
+        dry_run: bool,
+    },
+    WorktreeApply {
+        user: User<'a>,
+        host: Host<'a>,
+        branch: Branch<'a>,
+        dry_run: bool,
+    },
+    Status {
+        user: User<'a>,
+        fetch_remotes: Vec<Remote<'a>>,
         host_filter: Filter<Host<'a>>,
+        branch_filter: Filter<Branch<'a>>,
     },
 }
 
 impl Workflow<'_> {
     /// Imperatively execute the workflow.
-    pub fn execute(self, renderer: &mut impl Renderer, git: &GitBinary) -> Result<()> {
+    pub fn execute(self, renderer: &mut impl Renderer, git: &impl GitBackend) -> Result<()> {
         match self {
-            Self::Sync { user, host, remote } => sync(renderer, git, &user, &host, &remote),
+            Self::Init {
+                user,
+                host,
+                remotes,
+                scope,
+                dry_run,
+            } => init(renderer, git, user, host, remotes, scope, dry_run),
+            Self::ConfigShow { settings } => config_show(renderer, &settings),
+            Self::ConfigGet { key } => config_get(renderer, git, &key),
+            Self::ConfigSet {
+                key,
+                values,
+                scope,
+                dry_run,
+            } => config_set(
+                renderer,
+                git,
+                &key,
+                &values.iter().map(String::as_str).collect::<Vec<_>>(),
+                scope,
+                dry_run,
+            ),
+            Self::ConfigUnset {
+                key,
+                scope,
+                dry_run,
+            } => config_unset(renderer, git, &key, scope, dry_run),
+            Self::Sync {
+                user,
+                host,
+                remotes,
+                branch_patterns,
+                extras,
+                dry_run,
+            } => {
+                if dry_run {
+                    sync_dry_run(
+                        renderer,
+                        git,
+                        &user,
+                        &host,
+                        &remotes,
+                        &branch_patterns,
+                        extras,
+                    )
+                } else {
+                    sync(
+                        renderer,
+                        git,
+                        &user,
+                        &host,
+                        &remotes,
+                        &branch_patterns,
+                        extras,
+                    )
+                }
+            }
+            Self::Push {
+                user,
+                host,
+                remotes,
+                branch_patterns,
+                branches,
+                dry_run,
+            } => {
+                if dry_run {
+                    push_dry_run(
+                        renderer,
+                        git,
+                        &user,
+                        &host,
+                        &remotes,
+                        &branch_patterns,
+                        branches,
+                    )
+                } else {
+                    push(
+                        renderer,
+                        git,
+                        &user,
+                        &host,
+                        &remotes,
+                        &branch_patterns,
+                        branches,
+                    )
+                }
+            }
+            Self::InstallHooks { dry_run } => install_hooks(renderer, git, dry_run),
+            Self::UninstallHooks { dry_run } => uninstall_hooks(renderer, git, dry_run),
             Self::Ls {
                 printer,
                 user,
-                fetch_remote,
+                fetch_remotes,
                 host_filter,
                 branch_filter,
             } => ls(
@@ -49,19 +216,118 @@ impl Workflow<'_> {
                 git,
                 printer,
                 &user,
-                fetch_remote,
+                &fetch_remotes,
                 host_filter,
                 branch_filter,
             ),
             Self::Purge {
                 user,
-                remote,
+                remotes,
+                host_filter,
+                older_than,
+                dry_run,
+            } => {
+                if dry_run {
+                    purge_dry_run(renderer, git, &user, &remotes, host_filter, older_than)
+                } else {
+                    purge(renderer, git, &user, &remotes, host_filter, older_than)
+                }
+            }
+            Self::Checkout {
+                user,
+                fetch_remotes,
+                host_filter,
+                branch,
+                force,
+                dry_run,
+            } => checkout(
+                renderer,
+                git,
+                &user,
+                &fetch_remotes,
+                host_filter,
+                &branch,
+                force,
+                dry_run,
+            ),
+            Self::StashApply {
+                user,
+                host,
+                index,
+                dry_run,
+            } => apply_synced(
+                renderer,
+                git,
+                &user,
+                &host,
+                RefKind::Stash,
+                &Branch::from(index.to_string()),
+                &format!("stash entry {}", index),
+                "--stash",
+                dry_run,
+            ),
+            Self::WorktreeApply {
+                user,
+                host,
+                branch,
+                dry_run,
+            } => apply_synced(
+                renderer,
+                git,
+                &user,
+                &host,
+                RefKind::Worktree,
+                &branch,
+                &format!("working tree snapshot for branch {}", branch.0),
+                "--include-worktree",
+                dry_run,
+            ),
+            Self::Status {
+                user,
+                fetch_remotes,
                 host_filter,
-            } => purge(renderer, git, &user, &remote, host_filter),
+                branch_filter,
+            } => status(
+                renderer,
+                git,
+                &user,
+                &fetch_remotes,
+                host_filter,
+                branch_filter,
+            ),
         }
     }
 }
 
+/// A value that [`Workflow::Init`] will save, which the user is asked to confirm or override if
+/// they haven't explicitly chosen it already.
+#[derive(Debug, PartialEq, Eq)]
+pub struct InitSetting<T> {
+    pub value: T,
+    pub ask: bool,
+}
+
+/// A resolved setting for [`Workflow::ConfigShow`], like the host name.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Setting {
+    /// The name of the setting, which is also its key within `nomad.*` in git config.
+    pub name: &'static str,
+    pub values: Vec<String>,
+    pub source: SettingSource,
+}
+
+/// Where the value of a [`Setting`] came from, in decreasing order of precedence.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SettingSource {
+    CommandLine,
+    /// The named environment variable.
+    Env(&'static str),
+    /// The given config files, as reported by `git config --show-origin`.
+    GitConfig(Vec<String>),
+    /// Nothing was specified, with a description of where the default came from.
+    Default(&'static str),
+}
+
 /// Declarative representation of a limited filter function.
 #[derive(Debug, PartialEq, Eq)]
 pub enum Filter<T: PartialEq + Eq + Hash> {
@@ -83,48 +349,414 @@ impl<T: PartialEq + Eq + Hash> Filter<T> {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// Glob patterns that decide which local branches get pushed as nomad managed refs.
+///
+/// A branch is pushed when it matches at least one `include` pattern (or there are none) and
+/// does not match any `exclude` pattern. `*` matches across `/`, so `wip/*` covers every branch
+/// under `wip/`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BranchPatterns {
+    include: Vec<glob::Pattern>,
+    exclude: Vec<glob::Pattern>,
+}
+
+impl BranchPatterns {
+    pub fn new(
+        include: impl IntoIterator<Item = impl AsRef<str>>,
+        exclude: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<Self> {
+        fn compile(
+            patterns: impl IntoIterator<Item = impl AsRef<str>>,
+        ) -> Result<Vec<glob::Pattern>> {
+            patterns
+                .into_iter()
+                .map(|pattern| {
+                    let pattern = pattern.as_ref();
+                    glob::Pattern::new(pattern)
+                        .with_context(|| format!("Invalid branch pattern: {}", pattern))
+                })
+                .collect()
+        }
+
+        Ok(Self {
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    pub fn contains(&self, branch: &Branch) -> bool {
+        let matches = |pattern: &glob::Pattern| pattern.matches(&branch.0);
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+
+    /// The local branches that should be pushed, in sorted order.
+    fn select(&self, local_branches: &HashSet<Branch<'static>>) -> Vec<Branch<'static>> {
+        let mut branches = local_branches
+            .iter()
+            .filter(|branch| self.contains(branch))
+            .map(|branch| Branch::from(branch.0.to_string()))
+            .collect::<Vec<_>>();
+        branches.sort();
+        branches
+    }
+}
+
+/// Refs that [`Workflow::Sync`] can publish alongside local branches, which are opt-in since they
+/// aren't branches.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncExtras {
+    /// Every `git stash` entry, see [`RefKind::Stash`].
+    pub stash: bool,
+    /// A snapshot of uncommitted changes to the current branch, see [`RefKind::Worktree`].
+    pub worktree: bool,
+    /// Lightweight and annotated tags, see [`RefKind::Tag`].
+    pub tags: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum LsPrinter {
     Grouped,
+    /// Like [`LsPrinter::Grouped`], with the abbreviated commit ID, age, and subject of each ref.
+    Long,
     Ref,
     Commit,
+    /// A single JSON array of objects, one per ref.
+    Json,
+    /// One JSON object per line, one per ref.
+    JsonLines,
+    /// One line per ref, filled in from a template.
+    Format(LsFormat),
 }
 
 impl LsPrinter {
-    pub fn print_host(self, output: &mut dyn Write, host: &Host) -> Result<()> {
+    /// Print every listed ref, which has already been grouped by host.
+    ///
+    /// Synced tags are printed after the branches of each host, under a separate heading when
+    /// grouped.
+    ///
+    /// `details` are only used if [`LsPrinter::needs_details`].
+    pub fn print(
+        &self,
+        output: &mut dyn Write,
+        listing: &[(Host, Vec<NomadRef<GitRef>>)],
+        details: &HashMap<String, RefDetails>,
+    ) -> Result<()> {
+        if *self == Self::Json {
+            let refs = listing
+                .iter()
+                .flat_map(|(_, nomad_refs)| nomad_refs)
+                .map(to_json)
+                .collect::<Vec<_>>();
+            serde_json::to_writer(&mut *output, &refs).context("printing json")?;
+            return writeln!(output).context("printing json");
+        }
+
+        for (host, nomad_refs) in listing {
+            let (tags, branches) = nomad_refs
+                .iter()
+                .partition::<Vec<_>, _>(|nomad_ref| nomad_ref.kind == RefKind::Tag);
+
+            self.print_heading(output, &host.0)?;
+            for nomad_ref in branches {
+                self.print_ref(output, nomad_ref, details)?;
+            }
+
+            if !tags.is_empty() {
+                self.print_heading(output, &format!("{} tags", host.0))?;
+                for nomad_ref in tags {
+                    self.print_ref(output, nomad_ref, details)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether [`LsPrinter::print`] should be given the [`RefDetails`] of each ref.
+    pub fn needs_details(&self) -> bool {
+        match self {
+            Self::Long => true,
+            Self::Format(format) => format.needs_details(),
+            Self::Grouped | Self::Ref | Self::Commit | Self::Json | Self::JsonLines => false,
+        }
+    }
+
+    fn print_heading(&self, output: &mut dyn Write, heading: &str) -> Result<()> {
         match self {
-            Self::Grouped => writeln!(output, "{}", host.0).context("printing grouped host"),
-            Self::Ref | Self::Commit => Ok(()),
+            Self::Grouped | Self::Long => {
+                writeln!(output, "{}", heading).context("printing grouped host")
+            }
+            Self::Ref | Self::Commit | Self::Json | Self::JsonLines | Self::Format(_) => Ok(()),
         }
     }
 
-    pub fn print_ref(self, output: &mut dyn Write, ref_: &GitRef) -> Result<()> {
+    fn print_ref(
+        &self,
+        output: &mut dyn Write,
+        nomad_ref: &NomadRef<GitRef>,
+        details: &HashMap<String, RefDetails>,
+    ) -> Result<()> {
+        let ref_ = &nomad_ref.ref_;
         match self {
             Self::Grouped => writeln!(output, "  {} -> {}", ref_.name, ref_.commit_id)
                 .context("printing ref and commit"),
+            Self::Long => match details.get(&ref_.name) {
+                Some(details) => writeln!(
+                    output,
+                    "  {} {} ({}) {}",
+                    ref_.name, details.short_id, details.relative_date, details.subject
+                ),
+                // The ref changed between listing and describing it.
+                None => writeln!(output, "  {} -> {}", ref_.name, ref_.commit_id),
+            }
+            .context("printing ref details"),
             Self::Ref => writeln!(output, "{}", ref_.name).context("printing ref"),
             Self::Commit => writeln!(output, "{}", ref_.commit_id).context("printing commit"),
+            Self::JsonLines => {
+                serde_json::to_writer(&mut *output, &to_json(nomad_ref))
+                    .context("printing json line")?;
+                writeln!(output).context("printing json line")
+            }
+            Self::Format(format) => writeln!(
+                output,
+                "{}",
+                format.render(nomad_ref, details.get(&ref_.name))
+            )
+            .context("printing formatted ref"),
+            Self::Json => unreachable!("printed all at once by LsPrinter::print"),
+        }
+    }
+}
+
+/// The machine readable representation of a [`NomadRef`].
+fn to_json(nomad_ref: &NomadRef<GitRef>) -> serde_json::Value {
+    serde_json::json!({
+        "user": nomad_ref.user.0,
+        "host": nomad_ref.host.0,
+        "branch": nomad_ref.branch.0,
+        "kind": nomad_ref.kind.as_str(),
+        "ref": nomad_ref.ref_.name,
+        "commit_id": nomad_ref.ref_.commit_id,
+    })
+}
+
+/// Confirm or override the user, host, and remotes, then save them in git config so that they
+/// don't have to be discovered or passed to every invocation.
+fn init(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    user: InitSetting<User>,
+    host: InitSetting<Host>,
+    remotes: InitSetting<Vec<Remote>>,
+    scope: ConfigScope,
+    dry_run: bool,
+) -> Result<()> {
+    let mut ask = |question: &str, setting: InitSetting<String>| {
+        if setting.ask {
+            renderer.prompt(question, &setting.value)
+        } else {
+            Ok(setting.value)
+        }
+    };
+
+    let user = ask(
+        "User name, shared by multiple clones",
+        InitSetting {
+            value: user.value.0.into_owned(),
+            ask: user.ask,
+        },
+    )?;
+    let host = ask(
+        "Host name, unique per clone",
+        InitSetting {
+            value: host.value.0.into_owned(),
+            ask: host.ask,
+        },
+    )?;
+    let remotes = ask(
+        "Remotes, separated by spaces",
+        InitSetting {
+            value: remotes
+                .value
+                .iter()
+                .map(|remote| remote.0.as_ref())
+                .collect::<Vec<_>>()
+                .join(" "),
+            ask: remotes.ask,
+        },
+    )?;
+    let remotes = remotes
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|remote| !remote.is_empty())
+        .collect::<Vec<_>>();
+
+    let settings = [
+        (CONFIG_USER, vec![user.as_str()]),
+        (CONFIG_HOST, vec![host.as_str()]),
+        (CONFIG_REMOTE, remotes),
+    ];
+
+    for (key, values) in &settings {
+        if values.is_empty() {
+            bail!("nomad.{} needs at least one value", key);
+        }
+    }
+
+    for (key, values) in settings {
+        config_set(renderer, git, key, &values, scope, dry_run)?;
+    }
+
+    Ok(())
+}
+
+/// Print every setting along with where its value came from.
+fn config_show(renderer: &mut impl Renderer, settings: &[Setting]) -> Result<()> {
+    renderer.writer(|w| {
+        for setting in settings {
+            let values = if setting.values.is_empty() {
+                "(none)".to_string()
+            } else {
+                setting.values.join(", ")
+            };
+
+            let source = match &setting.source {
+                SettingSource::CommandLine => "command line".to_string(),
+                SettingSource::Env(var) => var.to_string(),
+                SettingSource::GitConfig(origins) => {
+                    format!("nomad.{} in {}", setting.name, origins.join(", "))
+                }
+                SettingSource::Default(description) => description.to_string(),
+            };
+
+            writeln!(w, "{} = {} ({})", setting.name, values, source)?;
+        }
+        Ok(())
+    })
+}
+
+/// Print every value of `nomad.<key>`, failing if there are none.
+fn config_get(renderer: &mut impl Renderer, git: &impl GitBackend, key: &str) -> Result<()> {
+    let values = git.get_config_all(renderer, key)?;
+    if values.is_empty() {
+        bail!("nomad.{} is not set", key);
+    }
+
+    renderer.writer(|w| {
+        for value in values {
+            writeln!(w, "{}", value)?;
+        }
+        Ok(())
+    })
+}
+
+/// Replace every value of `nomad.<key>` in `scope` with `values`.
+fn config_set(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    key: &str,
+    values: &[&str],
+    scope: ConfigScope,
+    dry_run: bool,
+) -> Result<()> {
+    if !dry_run {
+        git.set_config_all(renderer, scope, key, values)?;
+    }
+
+    if dry_run || git.is_output_allowed() {
+        renderer.writer(|w| {
+            writeln!(
+                w,
+                "{} nomad.{} = {} in {} git config",
+                if dry_run { "Would set" } else { "Set" },
+                key,
+                values.join(", "),
+                scope.name(),
+            )?;
+            Ok(())
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Remove every value of `nomad.<key>` from `scope`.
+fn config_unset(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    key: &str,
+    scope: ConfigScope,
+    dry_run: bool,
+) -> Result<()> {
+    if dry_run {
+        if git
+            .get_config_entries(renderer, Some(scope), key)?
+            .is_empty()
+        {
+            bail!("nomad.{} is not set in {} git config", key, scope.name());
         }
+    } else {
+        git.unset_config(renderer, scope, key)?;
+    }
+
+    if dry_run || git.is_output_allowed() {
+        renderer.writer(|w| {
+            writeln!(
+                w,
+                "{} nomad.{} in {} git config",
+                if dry_run { "Would unset" } else { "Unset" },
+                key,
+                scope.name(),
+            )?;
+            Ok(())
+        })?;
     }
+
+    Ok(())
 }
 
-/// Synchronize current local branches with nomad managed refs in the given remote.
+/// Synchronize current local branches with nomad managed refs in the given remotes.
+///
+/// Refs from other hosts are only pruned locally once none of the remotes know about them, so
+/// that a host which is still present on one remote is not repeatedly deleted and fetched again.
+///
+/// The refs from [`extra_refs`] that `extras` opts into are pushed as well, and ones that no
+/// longer exist are pruned like deleted branches.
 fn sync(
     renderer: &mut impl Renderer,
-    git: &GitBinary,
+    git: &impl GitBackend,
     user: &User,
     host: &Host,
-    remote: &Remote,
+    remotes: &[Remote],
+    branch_patterns: &BranchPatterns,
+    extras: SyncExtras,
 ) -> Result<()> {
-    git.push_nomad_refs(renderer, user, host, remote)?;
-    git.fetch_nomad_refs(renderer, user, remote)?;
-    let remote_nomad_refs = git.list_nomad_refs(renderer, user, remote)?.collect();
+    let clone_id = git.clone_id(renderer)?;
+    let local_branches = git.snapshot(renderer, user)?.local_branches;
+    let extra_refs = extra_refs(renderer, git, branch_patterns, extras, false)?;
+    let pushed_branches = branch_patterns.select(&local_branches);
+    for remote in remotes {
+        git.push_nomad_refs(
+            renderer,
+            user,
+            host,
+            remote,
+            &clone_id,
+            &pushed_branches,
+            &extra_refs,
+        )?;
+    }
+    let remote_nomad_refs = fetch_remote_nomad_refs(renderer, git, user, remotes)?;
     let snapshot = git.snapshot(renderer, user)?;
     git.prune_nomad_refs(
         renderer,
-        remote,
+        &remote_nomad_refs,
         snapshot
-            .prune_deleted_branches(host, &remote_nomad_refs)
+            .prune_deleted_branches(
+                host,
+                &RemoteNomadRefSet::union(remote_nomad_refs.iter().map(|(_, set)| set)),
+                |kind, branch| is_pushed(branch_patterns, &extra_refs, kind, branch),
+            )
             .into_iter(),
     )?;
 
@@ -136,7 +768,7 @@ fn sync(
             git,
             LsPrinter::Grouped,
             user,
-            None,
+            &[],
             Filter::All,
             Filter::All,
         )?
@@ -145,76 +777,1010 @@ fn sync(
     Ok(())
 }
 
-/// List all nomad managed refs organized by host.
+/// Refs of other [`RefKind`]s that [`sync`] publishes alongside local branches, with the commit
+/// IDs to publish them at.
 ///
-/// Does not respect [`GitBinary::is_output_allowed`] because output is the whole point of this
-/// command.
-fn ls(
+/// The snapshot of the working tree is only published if it has changes and the current branch
+/// is pushed at all. For a `dry_run`, the snapshot is not actually made, which leaves its commit
+/// ID empty.
+fn extra_refs(
     renderer: &mut impl Renderer,
-    git: &GitBinary,
-    printer: LsPrinter,
+    git: &impl GitBackend,
+    branch_patterns: &BranchPatterns,
+    extras: SyncExtras,
+    dry_run: bool,
+) -> Result<Vec<(RefKind, Branch<'static>, String)>> {
+    let mut extra_refs = Vec::new();
+
+    if extras.stash {
+        extra_refs.extend(git.stash_entries(renderer)?.into_iter().enumerate().map(
+            |(index, commit_id)| (RefKind::Stash, Branch::from(index.to_string()), commit_id),
+        ));
+    }
+
+    if extras.worktree {
+        let branch = git
+            .current_branch(renderer)
+            .context("Snapshotting the working tree requires a checked out branch")?;
+        if branch_patterns.contains(&branch) {
+            if dry_run {
+                if git.has_worktree_changes(renderer)? {
+                    extra_refs.push((RefKind::Worktree, branch, String::new()));
+                }
+            } else if let Some(commit_id) = git.worktree_snapshot(renderer)? {
+                extra_refs.push((RefKind::Worktree, branch, commit_id));
+            }
+        }
+    }
+
+    if extras.tags {
+        extra_refs.extend(
+            git.list_refs(renderer, "Listing tags")?
+                .into_iter()
+                .filter_map(|git_ref| {
+                    let name = git_ref.name.strip_prefix("refs/tags/")?;
+                    Some((
+                        RefKind::Tag,
+                        Branch::from(name.to_string()),
+                        git_ref.commit_id,
+                    ))
+                }),
+        );
+    }
+
+    Ok(extra_refs)
+}
+
+/// Whether the ref of `kind` named `branch` should stay pushed.
+///
+/// Branches follow `branch_patterns`, while anything else only stays pushed for as long as it is
+/// amongst the [`extra_refs`] being published.
+fn is_pushed(
+    branch_patterns: &BranchPatterns,
+    extra_refs: &[(RefKind, Branch, String)],
+    kind: RefKind,
+    branch: &Branch,
+) -> bool {
+    match kind {
+        RefKind::Branch => branch_patterns.contains(branch),
+        RefKind::Stash | RefKind::Worktree | RefKind::Tag => extra_refs
+            .iter()
+            .any(|(extra_kind, name, _)| *extra_kind == kind && name == branch),
+    }
+}
+
+/// Where a ref of `kind` named `branch` comes from, for humans.
+fn ref_source(kind: RefKind, branch: &Branch) -> String {
+    match kind {
+        RefKind::Branch => format!("refs/heads/{}", branch.0),
+        RefKind::Stash => format!("stash@{{{}}}", branch.0),
+        RefKind::Worktree => "working tree".to_string(),
+        RefKind::Tag => format!("refs/tags/{}", branch.0),
+    }
+}
+
+/// Fetch from each remote, returning which nomad managed refs it has.
+fn fetch_remote_nomad_refs<'r>(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
     user: &User,
-    fetch_remote: Option<Remote>,
-    host_filter: Filter<Host>,
-    branch_filter: Filter<Branch>,
+    remotes: &'r [Remote<'r>],
+) -> Result<Vec<(&'r Remote<'r>, RemoteNomadRefSet)>> {
+    remotes
+        .iter()
+        .map(|remote| Ok((remote, git.fetch_nomad_refs(renderer, user, remote)?)))
+        .collect()
+}
+
+/// Push specific local branches without fetching or pruning, which is cheap enough to run from
+/// git hooks.
+///
+/// Branches that no longer exist or are rejected by `branch_patterns` are skipped.
+fn push(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    user: &User,
+    host: &Host,
+    remotes: &[Remote],
+    branch_patterns: &BranchPatterns,
+    branches: Vec<Branch>,
 ) -> Result<()> {
-    if let Some(remote) = fetch_remote {
-        git.fetch_nomad_refs(renderer, user, &remote)?;
+    let branches = pushable_branches(renderer, git, user, branch_patterns, branches)?;
+
+    let clone_id = git.clone_id(renderer)?;
+    for remote in remotes {
+        git.push_nomad_refs(renderer, user, host, remote, &clone_id, &branches, &[])?;
     }
 
-    let snapshot = git.snapshot(renderer, user)?;
+    Ok(())
+}
 
-    for (host, branches) in snapshot.sorted_hosts_and_branches() {
-        if !host_filter.contains(&host) {
-            continue;
-        }
+/// Print what [`push`] would push without touching the remotes.
+fn push_dry_run(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    user: &User,
+    host: &Host,
+    remotes: &[Remote],
+    branch_patterns: &BranchPatterns,
+    branches: Vec<Branch>,
+) -> Result<()> {
+    let branches = pushable_branches(renderer, git, user, branch_patterns, branches)?;
 
-        renderer.writer(|w| {
-            printer.print_host(w, &host)?;
+    renderer.writer(|w| {
+        writeln!(w, "Dry run, nothing will be changed")?;
 
-            for NomadRef { ref_, branch, .. } in branches {
-                if branch_filter.contains(&branch) {
-                    printer.print_ref(w, &ref_)?;
+        if !branches.is_empty() {
+            for remote in remotes {
+                writeln!(w, "Would push local branches to {}", remote.0)?;
+                for branch in &branches {
+                    let nomad_ref = NomadRef {
+                        user: user.always_borrow(),
+                        host: host.always_borrow(),
+                        kind: RefKind::Branch,
+                        branch: branch.always_borrow(),
+                        ref_: (),
+                    };
+                    writeln!(
+                        w,
+                        "  refs/heads/{} -> {}",
+                        branch.0,
+                        nomad_ref.to_git_remote_ref()
+                    )?;
                 }
             }
+        }
 
-            Ok(())
-        })?;
+        Ok(())
+    })
+}
+
+/// The subset of `branches` that [`push`] pushes, which skips branches that no longer exist or are
+/// rejected by `branch_patterns`.
+fn pushable_branches<'b>(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    user: &User,
+    branch_patterns: &BranchPatterns,
+    branches: Vec<Branch<'b>>,
+) -> Result<Vec<Branch<'b>>> {
+    let local_branches = git.snapshot(renderer, user)?.local_branches;
+    Ok(branches
+        .into_iter()
+        .filter(|branch| local_branches.contains(branch) && branch_patterns.contains(branch))
+        .collect())
+}
+
+/// Install git hooks that invoke [`push`] in the background whenever a branch changes.
+fn install_hooks(renderer: &mut impl Renderer, git: &impl GitBackend, dry_run: bool) -> Result<()> {
+    let hooks_dir = git.hooks_dir(renderer)?;
+    let would = if dry_run {
+        "Would install"
+    } else {
+        "Installed"
+    };
+
+    for hook in Hook::ALL {
+        let path = hooks::hook_path(&hooks_dir, hook);
+        let before = if dry_run {
+            hooks::state(&hooks_dir, hook)?
+        } else {
+            hooks::install(&hooks_dir, hook)?
+        };
+
+        if dry_run || git.is_output_allowed() {
+            renderer.writer(|w| {
+                match before {
+                    HookState::Missing | HookState::Nomad { .. } => {
+                        writeln!(w, "{} {}", would, path.display())?
+                    }
+                    HookState::Other => writeln!(
+                        w,
+                        "{} {}, chaining to existing hook moved to {}",
+                        would,
+                        path.display(),
+                        hooks::chained_path(&hooks_dir, hook).display(),
+                    )?,
+                }
+                Ok(())
+            })?;
+        }
     }
 
     Ok(())
 }
 
-/// Delete nomad managed refs returned by `to_prune`.
-fn purge(
+/// Remove the git hooks written by [`install_hooks`], restoring any hooks they were chaining to.
+fn uninstall_hooks(
     renderer: &mut impl Renderer,
-    git: &GitBinary,
-    user: &User,
-    remote: &Remote,
-    host_filter: Filter<Host>,
+    git: &impl GitBackend,
+    dry_run: bool,
 ) -> Result<()> {
-    git.fetch_nomad_refs(renderer, user, remote)?;
-    let snapshot = git.snapshot(renderer, user)?;
-    let prune = snapshot.prune_by_hosts(|h| host_filter.contains(h));
-    git.prune_nomad_refs(renderer, remote, prune.into_iter())?;
+    let hooks_dir = git.hooks_dir(renderer)?;
+    let would = if dry_run {
+        "Would uninstall"
+    } else {
+        "Uninstalled"
+    };
+
+    for hook in Hook::ALL {
+        let path = hooks::hook_path(&hooks_dir, hook);
+        let before = if dry_run {
+            hooks::state(&hooks_dir, hook)?
+        } else {
+            hooks::uninstall(&hooks_dir, hook)?
+        };
+
+        if dry_run || git.is_output_allowed() {
+            renderer.writer(|w| {
+                match before {
+                    HookState::Missing | HookState::Other => {
+                        writeln!(w, "Skipped {}, not installed by nomad", path.display())?
+                    }
+                    HookState::Nomad { chained: false } => {
+                        writeln!(w, "{} {}", would, path.display())?
+                    }
+                    HookState::Nomad { chained: true } => writeln!(
+                        w,
+                        "{} {}, restoring chained hook from {}",
+                        would,
+                        path.display(),
+                        hooks::chained_path(&hooks_dir, hook).display(),
+                    )?,
+                }
+                Ok(())
+            })?;
+        }
+    }
+
     Ok(())
 }
 
-#[cfg(test)]
-mod test {
-    use crate::{
-        git_testing::GitRemote,
-        renderer::test::{MemoryRenderer, NoRenderer},
-        workflow::sync,
-    };
+/// Print everything [`sync`] would do without changing the local clone or the remotes.
+///
+/// The remotes are listed instead of fetched, and the result of the fetch is simulated so that
+/// the pruning plan matches what a real sync would do.
+fn sync_dry_run(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    user: &User,
+    host: &Host,
+    remotes: &[Remote],
+    branch_patterns: &BranchPatterns,
+    extras: SyncExtras,
+) -> Result<()> {
+    let remote_refs = remotes
+        .iter()
+        .map(|remote| git.list_nomad_refs(renderer, user, remote))
+        .collect::<Result<Vec<_>>>()?;
+    let snapshot = git.snapshot(renderer, user)?;
+    let extra_refs = extra_refs(renderer, git, branch_patterns, extras, true)?;
 
-    use super::{Filter, LsPrinter, Workflow};
+    let pushed_refs = branch_patterns
+        .select(&snapshot.local_branches)
+        .into_iter()
+        .map(|branch| (RefKind::Branch, branch))
+        .chain(
+            extra_refs
+                .iter()
+                .map(|(kind, branch, _)| (*kind, branch.always_borrow())),
+        )
+        .map(|(kind, branch)| {
+            (
+                ref_source(kind, &branch),
+                NomadRef {
+                    user: user.always_borrow(),
+                    host: host.always_borrow(),
+                    kind,
+                    branch,
+                    ref_: (),
+                },
+            )
+        })
+        .collect::<Vec<_>>();
 
-    #[test]
-    fn ls_one_host() {
-        let remote = GitRemote::init(None);
+    // After the push, each remote would know about every pushed local branch in addition to what
+    // it already has.
+    let pushed_set = pushed_refs
+        .iter()
+        .map(|(_, nomad_ref)| {
+            (
+                nomad_ref.user.always_borrow(),
+                nomad_ref.host.always_borrow(),
+                nomad_ref.kind,
+                nomad_ref.branch.always_borrow(),
+            )
+        })
+        .collect();
+    let remote_nomad_refs = remotes
+        .iter()
+        .zip(&remote_refs)
+        .map(|(remote, refs)| {
+            (
+                remote,
+                RemoteNomadRefSet::union([&to_remote_set(refs), &pushed_set]),
+            )
+        })
+        .collect::<Vec<_>>();
 
-        let clone = remote.clone("user0", "host0");
+    let prune = simulate_fetch(user, snapshot, remote_refs.into_iter().flatten().collect())
+        .prune_deleted_branches(
+            host,
+            &RemoteNomadRefSet::union(remote_nomad_refs.iter().map(|(_, set)| set)),
+            |kind, branch| is_pushed(branch_patterns, &extra_refs, kind, branch),
+        );
+
+    renderer.writer(|w| {
+        writeln!(w, "Dry run, nothing will be changed")?;
+
+        if !pushed_refs.is_empty() {
+            for remote in remotes {
+                writeln!(w, "Would push local branches to {}", remote.0)?;
+                for (source, nomad_ref) in &pushed_refs {
+                    writeln!(w, "  {} -> {}", source, nomad_ref.to_git_remote_ref())?;
+                }
+            }
+        }
+
+        for remote in remotes {
+            writeln!(w, "Would fetch branches from {}", remote.0)?;
+        }
+        print_prune_plan(w, &remote_nomad_refs, &prune)
+    })
+}
+
+/// The [`RemoteNomadRefSet`] for refs that are only borrowed.
+fn to_remote_set<Ref>(nomad_refs: &[NomadRef<Ref>]) -> RemoteNomadRefSet {
+    nomad_refs
+        .iter()
+        .map(|nomad_ref| {
+            (
+                nomad_ref.user.always_borrow(),
+                nomad_ref.host.always_borrow(),
+                nomad_ref.kind,
+                nomad_ref.branch.always_borrow(),
+            )
+        })
+        .collect()
+}
+
+/// Replace the nomad refs in `snapshot` with the ones the remotes have, like fetching would.
+///
+/// Refs are applied in order, so when several remotes have the same ref the last one wins. Local
+/// nomad refs that the remotes no longer know about are left alone, since fetching does not
+/// delete them either.
+fn simulate_fetch<'a>(
+    user: &'a User,
+    snapshot: Snapshot<'a, GitRef>,
+    remote_refs: Vec<NomadRef<'a, GitRef>>,
+) -> Snapshot<'a, GitRef> {
+    let Snapshot {
+        local_branches,
+        nomad_refs,
+        ..
+    } = snapshot;
+
+    let mut by_name = nomad_refs
+        .into_iter()
+        .map(|nomad_ref| (nomad_ref.ref_.name.clone(), nomad_ref))
+        .collect::<HashMap<_, _>>();
+
+    for remote_ref in remote_refs {
+        let name = remote_ref.to_git_local_ref();
+        let nomad_ref = NomadRef {
+            user: remote_ref.user,
+            host: remote_ref.host,
+            kind: remote_ref.kind,
+            branch: remote_ref.branch,
+            ref_: GitRef {
+                commit_id: remote_ref.ref_.commit_id,
+                name: name.clone(),
+            },
+        };
+        by_name.insert(name, nomad_ref);
+    }
+
+    let mut nomad_refs = by_name.into_values().collect::<Vec<_>>();
+    nomad_refs.sort_by(|a, b| a.ref_.name.cmp(&b.ref_.name));
+
+    Snapshot::new(user, local_branches, nomad_refs)
+}
+
+/// Describe the refs that [`GitBackend::prune_nomad_refs`] would delete.
+fn print_prune_plan(
+    output: &mut dyn Write,
+    remote_nomad_refs: &[(&Remote, RemoteNomadRefSet)],
+    prune: &[PruneFrom<GitRef>],
+) -> Result<()> {
+    if prune.is_empty() {
+        writeln!(output, "Would not prune any branches")?;
+        return Ok(());
+    }
+
+    writeln!(output, "Would prune branches")?;
+    for prune_from in prune {
+        let (nomad_ref, location) = match prune_from {
+            PruneFrom::LocalOnly(nomad_ref) => (nomad_ref, "locally".to_string()),
+            PruneFrom::LocalAndRemote(nomad_ref) => {
+                let remotes = remote_nomad_refs
+                    .iter()
+                    .filter(|(_, set)| set.contains(nomad_ref))
+                    .map(|(remote, _)| remote.0.as_ref())
+                    .collect::<Vec<_>>();
+
+                if remotes.is_empty() {
+                    (nomad_ref, "locally".to_string())
+                } else {
+                    (nomad_ref, format!("locally and at {}", remotes.join(", ")))
+                }
+            }
+        };
+
+        writeln!(
+            output,
+            "  Delete {} (was {}) {}",
+            nomad_ref.ref_.name, nomad_ref.ref_.commit_id, location
+        )?;
+    }
+
+    Ok(())
+}
+
+/// List all nomad managed refs organized by host.
+///
+/// Does not respect [`GitBackend::is_output_allowed`] because output is the whole point of this
+/// command.
+fn ls(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    printer: LsPrinter,
+    user: &User,
+    fetch_remotes: &[Remote],
+    host_filter: Filter<Host>,
+    branch_filter: Filter<Branch>,
+) -> Result<()> {
+    for remote in fetch_remotes {
+        git.fetch_nomad_refs(renderer, user, remote)?;
+    }
+
+    let snapshot = git.snapshot(renderer, user)?;
+
+    let listing = snapshot
+        .sorted_hosts_and_branches()
+        .into_iter()
+        .filter(|(host, _)| host_filter.contains(host))
+        .map(|(host, branches)| {
+            let branches = branches
+                .into_iter()
+                .filter(|nomad_ref| branch_filter.contains(&nomad_ref.branch))
+                .collect::<Vec<_>>();
+            (host, branches)
+        })
+        .collect::<Vec<_>>();
+
+    let details = if printer.needs_details() {
+        git.nomad_ref_details(renderer)?
+    } else {
+        HashMap::new()
+    };
+
+    renderer.writer(|w| printer.print(w, &listing, &details))
+}
+
+/// Delete nomad managed refs for hosts matching `host_filter`, further restricted to hosts that
+/// have been inactive for longer than `older_than` if specified.
+fn purge(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    user: &User,
+    remotes: &[Remote],
+    host_filter: Filter<Host>,
+    older_than: Option<Duration>,
+) -> Result<()> {
+    let remote_nomad_refs = fetch_remote_nomad_refs(renderer, git, user, remotes)?;
+    let snapshot = git.snapshot(renderer, user)?;
+    let (expired, prune) = purge_plan(renderer, git, snapshot, &host_filter, older_than)?;
+
+    if git.is_output_allowed() {
+        renderer.writer(|w| print_expired_hosts(w, "Expiring", &expired))?;
+    }
+
+    git.prune_nomad_refs(renderer, &remote_nomad_refs, prune.into_iter())?;
+
+    // Forget which clone was pushing as each purged host, so that another clone can take over
+    // the host name.
+    let is_purged = |host: &Host| match older_than {
+        None => host_filter.contains(host),
+        Some(_) => expired.iter().any(|(expired, _)| expired == host),
+    };
+    for remote in remotes {
+        git.forget_clone_markers(renderer, user, remote, is_purged)?;
+    }
+
+    Ok(())
+}
+
+/// A host along with how long ago its newest nomad managed ref was committed.
+type InactiveHost = (Host<'static>, Duration);
+
+/// Decide what [`purge`] should delete, returning the hosts that were found to be inactive (if
+/// `older_than` is specified) along with the refs to prune.
+fn purge_plan<'a>(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    snapshot: Snapshot<'a, GitRef>,
+    host_filter: &Filter<Host>,
+    older_than: Option<Duration>,
+) -> Result<(Vec<InactiveHost>, Vec<PruneFrom<'a, GitRef>>)> {
+    let expired = match older_than {
+        None => None,
+        Some(older_than) => Some(
+            inactive_hosts(renderer, git, &snapshot.nomad_refs, older_than)?
+                .into_iter()
+                .filter(|(host, _)| host_filter.contains(host))
+                .collect::<Vec<_>>(),
+        ),
+    };
+
+    let prune = snapshot.prune_by_hosts(|h| match &expired {
+        None => host_filter.contains(h),
+        Some(expired) => expired.iter().any(|(host, _)| host == h),
+    });
+
+    Ok((expired.unwrap_or_default(), prune))
+}
+
+/// Hosts whose newest nomad managed ref was committed longer than `older_than` ago, along with how
+/// long ago that was, in sorted order.
+///
+/// Refs pointing to commits that are not available locally are ignored, so a host is never
+/// considered inactive based on incomplete information.
+fn inactive_hosts(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    nomad_refs: &[NomadRef<GitRef>],
+    older_than: Duration,
+) -> Result<Vec<InactiveHost>> {
+    let committer_dates = git.nomad_committer_dates(renderer)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("system clock is before the Unix epoch")?
+        .as_secs();
+
+    let mut newest = BTreeMap::<&str, u64>::new();
+    for nomad_ref in nomad_refs {
+        if let Some(&date) = committer_dates.get(&nomad_ref.ref_.commit_id) {
+            let entry = newest.entry(nomad_ref.host.0.as_ref()).or_default();
+            *entry = (*entry).max(date);
+        }
+    }
+
+    Ok(newest
+        .into_iter()
+        .map(|(host, date)| {
+            (
+                Host::from(host.to_string()),
+                Duration::from_secs(now.saturating_sub(date)),
+            )
+        })
+        .filter(|(_, age)| *age > older_than)
+        .collect())
+}
+
+/// Describe the hosts found by [`inactive_hosts`].
+fn print_expired_hosts(output: &mut dyn Write, verb: &str, expired: &[InactiveHost]) -> Result<()> {
+    for (host, age) in expired {
+        writeln!(
+            output,
+            "{} {}, last active {} days ago",
+            verb,
+            host.0,
+            age.as_secs() / (24 * 60 * 60)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Print the refs that [`purge`] would delete without changing the local clone or the remotes.
+fn purge_dry_run(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    user: &User,
+    remotes: &[Remote],
+    host_filter: Filter<Host>,
+    older_than: Option<Duration>,
+) -> Result<()> {
+    let remote_refs = remotes
+        .iter()
+        .map(|remote| git.list_nomad_refs(renderer, user, remote))
+        .collect::<Result<Vec<_>>>()?;
+    let remote_nomad_refs = remotes
+        .iter()
+        .zip(&remote_refs)
+        .map(|(remote, refs)| (remote, to_remote_set(refs)))
+        .collect::<Vec<_>>();
+    let snapshot = simulate_fetch(
+        user,
+        git.snapshot(renderer, user)?,
+        remote_refs.into_iter().flatten().collect(),
+    );
+    let (expired, prune) = purge_plan(renderer, git, snapshot, &host_filter, older_than)?;
+
+    renderer.writer(|w| {
+        writeln!(w, "Dry run, nothing will be changed")?;
+        print_expired_hosts(w, "Would expire", &expired)?;
+        print_prune_plan(w, &remote_nomad_refs, &prune)
+    })
+}
+
+/// Create or update a local branch from the nomad managed ref of another host.
+///
+/// Refuses to overwrite a local branch that has commits the nomad ref does not, unless `force` is
+/// specified. Only refs of [`RefKind::Branch`] are considered, so synced stash entries, working
+/// tree snapshots, and tags are never mistaken for branches.
+#[allow(clippy::too_many_arguments)]
+fn checkout(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    user: &User,
+    fetch_remotes: &[Remote],
+    host_filter: Filter<Host>,
+    branch: &Branch,
+    force: bool,
+    dry_run: bool,
+) -> Result<()> {
+    if !dry_run {
+        for remote in fetch_remotes {
+            git.fetch_nomad_refs(renderer, user, remote)?;
+        }
+    }
+
+    let snapshot = git.snapshot(renderer, user)?;
+    let has_local_branch = snapshot.local_branches.contains(branch);
+
+    let mut candidates = snapshot
+        .nomad_refs
+        .into_iter()
+        .filter(|nomad_ref| {
+            nomad_ref.kind == RefKind::Branch
+                && &nomad_ref.branch == branch
+                && host_filter.contains(&nomad_ref.host)
+        })
+        .collect::<Vec<_>>();
+
+    let NomadRef { host, ref_, .. } = match candidates.len() {
+        0 => bail!("No host has a nomad managed ref for branch {}", branch.0),
+        1 => candidates.pop().expect("has one candidate"),
+        _ => {
+            let hosts = candidates
+                .iter()
+                .map(|nomad_ref| nomad_ref.host.0.as_ref())
+                .collect::<Vec<_>>();
+            bail!(
+                "Branch {} exists on multiple hosts ({}), specify which one to check out",
+                branch.0,
+                hosts.join(", ")
+            );
+        }
+    };
+
+    if has_local_branch && !force {
+        let local_ref = git.get_ref(
+            renderer,
+            format!("Reading local branch {}", branch.0),
+            format!("refs/heads/{}", branch.0),
+        )?;
+        let (local_only, _) =
+            git.count_left_right(renderer, &local_ref.commit_id, &ref_.commit_id)?;
+
+        if local_only > 0 {
+            bail!(
+                "Local branch {} has {} commit(s) not in {}, use --force to overwrite it",
+                branch.0,
+                local_only,
+                ref_.name
+            );
+        }
+    }
+
+    if dry_run {
+        return renderer.writer(|w| {
+            writeln!(w, "Dry run, nothing will be changed")?;
+            writeln!(
+                w,
+                "Would check out {} at {} from {}",
+                branch.0, ref_.commit_id, ref_.name
+            )?;
+            Ok(())
+        });
+    }
+
+    git.checkout_branch(
+        renderer,
+        format!("Checking out {} from {}", branch.0, host.0),
+        branch,
+        &ref_.commit_id,
+    )
+}
+
+/// Apply the stash-like commit that `host` synced as the ref of `kind` named `branch`, like a
+/// `git stash` entry or a working tree snapshot, leaving the local stash alone.
+///
+/// `what` describes the ref for humans, and `sync_flag` is what `host` should sync with to
+/// publish it.
+#[allow(clippy::too_many_arguments)]
+fn apply_synced(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    user: &User,
+    host: &Host,
+    kind: RefKind,
+    branch: &Branch,
+    what: &str,
+    sync_flag: &str,
+    dry_run: bool,
+) -> Result<()> {
+    let ref_ = git
+        .snapshot(renderer, user)?
+        .nomad_refs
+        .into_iter()
+        .find(|nomad_ref| {
+            &nomad_ref.host == host && nomad_ref.kind == kind && &nomad_ref.branch == branch
+        })
+        .map(|nomad_ref| nomad_ref.ref_);
+
+    let ref_ = match ref_ {
+        Some(ref_) => ref_,
+        None => bail!(
+            "Host {} has no synced {}, run `git nomad sync {}` on it first",
+            host.0,
+            what,
+            sync_flag
+        ),
+    };
+
+    if dry_run {
+        return renderer.writer(|w| {
+            writeln!(w, "Dry run, nothing will be changed")?;
+            writeln!(
+                w,
+                "Would apply {} from {} at {}",
+                what, host.0, ref_.commit_id
+            )?;
+            Ok(())
+        });
+    }
+
+    git.apply_stash(
+        renderer,
+        format!("Applying {} from {}", what, host.0),
+        &ref_.commit_id,
+    )
+}
+
+/// How one copy of a branch relates to the copy it is being compared against.
+struct Divergence {
+    /// Commits only this copy has.
+    ahead: usize,
+    /// Commits only the compared against copy has.
+    behind: usize,
+    /// Only computed when both copies have commits the other does not.
+    merge_base: Option<String>,
+}
+
+/// One copy of a branch, either the local branch or a nomad managed ref from some host.
+struct BranchCopy {
+    /// The full ref name, like `refs/heads/feature` or `refs/nomad/desktop/feature`.
+    name: String,
+    commit_id: String,
+    /// [`None`] for the copy that everything else is compared against.
+    divergence: Option<Divergence>,
+}
+
+/// Report how copies of the same branch on different hosts relate to each other.
+///
+/// Only branches that exist in at least two places (counting the local branch) are reported. Each
+/// copy is compared against the local branch if there is one, or else the first host in sorted
+/// order. Synced stash entries, working tree snapshots, and tags are not branches, so they are
+/// left out.
+fn status(
+    renderer: &mut impl Renderer,
+    git: &impl GitBackend,
+    user: &User,
+    fetch_remotes: &[Remote],
+    host_filter: Filter<Host>,
+    branch_filter: Filter<Branch>,
+) -> Result<()> {
+    for remote in fetch_remotes {
+        git.fetch_nomad_refs(renderer, user, remote)?;
+    }
+
+    let Snapshot {
+        local_branches,
+        nomad_refs,
+        ..
+    } = git.snapshot(renderer, user)?;
+
+    let mut by_branch = BTreeMap::<Branch, Vec<NomadRef<GitRef>>>::new();
+    for nomad_ref in nomad_refs {
+        if nomad_ref.kind == RefKind::Branch
+            && host_filter.contains(&nomad_ref.host)
+            && branch_filter.contains(&nomad_ref.branch)
+        {
+            by_branch
+                .entry(nomad_ref.branch.always_borrow().possibly_clone())
+                .or_default()
+                .push(nomad_ref);
+        }
+    }
+
+    let mut report = Vec::<(Branch, Vec<BranchCopy>)>::new();
+
+    for (branch, mut host_refs) in by_branch {
+        let has_local_branch = local_branches.contains(&branch);
+        if host_refs.len() + usize::from(has_local_branch) < 2 {
+            continue;
+        }
+
+        host_refs.sort_by(|a, b| a.host.cmp(&b.host));
+        let mut copies = host_refs
+            .into_iter()
+            .map(|nomad_ref| nomad_ref.ref_)
+            .collect::<Vec<_>>();
+
+        if has_local_branch {
+            copies.insert(
+                0,
+                git.get_ref(
+                    renderer,
+                    format!("Reading local branch {}", branch.0),
+                    format!("refs/heads/{}", branch.0),
+                )?,
+            );
+        }
+
+        let base_commit_id = copies[0].commit_id.clone();
+        let mut branch_copies = Vec::with_capacity(copies.len());
+
+        for (index, GitRef { commit_id, name }) in copies.into_iter().enumerate() {
+            let divergence = if index == 0 {
+                None
+            } else {
+                let (behind, ahead) =
+                    git.count_left_right(renderer, &base_commit_id, &commit_id)?;
+                let merge_base = if ahead > 0 && behind > 0 {
+                    Some(git.merge_base(renderer, &base_commit_id, &commit_id)?)
+                } else {
+                    None
+                };
+
+                Some(Divergence {
+                    ahead,
+                    behind,
+                    merge_base,
+                })
+            };
+
+            branch_copies.push(BranchCopy {
+                name,
+                commit_id,
+                divergence,
+            });
+        }
+
+        report.push((branch, branch_copies));
+    }
+
+    renderer.writer(|w| {
+        for (branch, copies) in &report {
+            let diverged = copies.iter().any(|copy| {
+                copy.divergence
+                    .as_ref()
+                    .is_some_and(|d| d.merge_base.is_some())
+            });
+
+            if diverged {
+                writeln!(w, "{} (diverged)", branch.0)?;
+            } else {
+                writeln!(w, "{}", branch.0)?;
+            }
+
+            for copy in copies {
+                let summary = match &copy.divergence {
+                    None => "compared against".to_string(),
+                    Some(Divergence {
+                        ahead: 0,
+                        behind: 0,
+                        ..
+                    }) => "up to date".to_string(),
+                    Some(Divergence {
+                        ahead,
+                        behind,
+                        merge_base: Some(merge_base),
+                    }) => format!(
+                        "ahead {}, behind {}, diverged from {}",
+                        ahead, behind, merge_base
+                    ),
+                    Some(Divergence {
+                        ahead, behind: 0, ..
+                    }) => format!("ahead {}", ahead),
+                    Some(Divergence { behind, .. }) => format!("behind {}", behind),
+                };
+
+                writeln!(w, "  {} -> {} ({})", copy.name, copy.commit_id, summary)?;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashSet,
+        fs,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use crate::{
+        error::{exit_code, NomadError},
+        git_backend::GitBackend,
+        git_binary::ConfigScope,
+        git_testing::{GitClone, GitCommitId, GitRemote, INITIAL_BRANCH},
+        hooks::{self, Hook, HookState},
+        ls_format::LsFormat,
+        renderer::test::{MemoryRenderer, NoRenderer},
+        types::{Branch, Host, NomadRef, RefKind, Remote, User},
+        verbosity::{run_notable, Verbosity},
+        workflow::sync,
+        CONFIG_HOST, CONFIG_REMOTE, CONFIG_USER,
+    };
+
+    use super::{
+        BranchPatterns, Filter, InitSetting, LsPrinter, Setting, SettingSource, SyncExtras,
+        Workflow,
+    };
+
+    fn sync_host(clone: &GitClone) {
+        sync(
+            &mut NoRenderer,
+            &clone.git,
+            &clone.user,
+            &clone.host,
+            &[clone.remote.always_borrow()],
+            &BranchPatterns::default(),
+            SyncExtras::default(),
+        )
+        .unwrap();
+    }
+
+    fn checkout(
+        clone: &GitClone,
+        source_host: Option<&Host>,
+        branch: &str,
+        force: bool,
+    ) -> anyhow::Result<()> {
+        Workflow::Checkout {
+            user: clone.user.always_borrow(),
+            fetch_remotes: vec![clone.remote.always_borrow()],
+            host_filter: match source_host {
+                Some(host) => Filter::Allow([host.always_borrow()].into()),
+                None => Filter::Deny([clone.host.always_borrow()].into()),
+            },
+            branch: Branch::from(branch),
+            force,
+            dry_run: false,
+        }
+        .execute(&mut NoRenderer, &clone.git)
+    }
+
+    #[test]
+    fn ls_one_host() {
+        let remote = GitRemote::init(None);
+
+        let clone = remote.clone("user0", "host0");
         let commit_id = clone.current_commit();
 
         sync(
@@ -222,7 +1788,9 @@ mod test {
             &clone.git,
             &clone.user,
             &clone.host,
-            &clone.remote,
+            &[clone.remote.always_borrow()],
+            &BranchPatterns::default(),
+            SyncExtras::default(),
         )
         .unwrap();
 
@@ -239,13 +1807,35 @@ mod test {
                 format!("refs/nomad/{}/master\n", clone.host.0),
             ),
             (LsPrinter::Commit, format!("{}\n", commit_id.0)),
+            (
+                LsPrinter::Json,
+                format!(
+                    concat!(
+                        r#"[{{"branch":"master","commit_id":"{}","host":"{}","#,
+                        r#""kind":"branch","ref":"refs/nomad/{}/master","user":"{}"}}]"#,
+                        "\n"
+                    ),
+                    commit_id.0, clone.host.0, clone.host.0, clone.user.0
+                ),
+            ),
+            (
+                LsPrinter::JsonLines,
+                format!(
+                    concat!(
+                        r#"{{"branch":"master","commit_id":"{}","host":"{}","#,
+                        r#""kind":"branch","ref":"refs/nomad/{}/master","user":"{}"}}"#,
+                        "\n"
+                    ),
+                    commit_id.0, clone.host.0, clone.host.0, clone.user.0
+                ),
+            ),
         ] {
             let mut renderer = MemoryRenderer::new();
 
             Workflow::Ls {
                 printer,
                 user: clone.user.clone(),
-                fetch_remote: Some(clone.remote.clone()),
+                fetch_remotes: vec![clone.remote.clone()],
                 host_filter: Filter::All,
                 branch_filter: Filter::All,
             }
@@ -256,6 +1846,85 @@ mod test {
         }
     }
 
+    /// `LsPrinter::Long` should describe each ref from a single batched lookup.
+    #[test]
+    fn ls_long() {
+        let remote = GitRemote::init(None);
+        let clone = remote.clone("user0", "host0");
+
+        let three_days_ago = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            - 3 * 24 * 60 * 60;
+        let commit_id = clone.commit_at("Remember me", &format!("@{} +0000", three_days_ago));
+        sync_host(&clone);
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::Ls {
+            printer: LsPrinter::Long,
+            user: clone.user.always_borrow(),
+            fetch_remotes: Vec::new(),
+            host_filter: Filter::All,
+            branch_filter: Filter::All,
+        }
+        .execute(&mut renderer, &clone.git)
+        .unwrap();
+
+        assert_eq!(
+            renderer.as_str(),
+            format!(
+                "host0\n  refs/nomad/host0/master {} (3 days ago) Remember me\n",
+                &commit_id.0[..7]
+            ),
+        );
+    }
+
+    /// `LsPrinter::Format` should print one line per ref, including synced tags.
+    #[test]
+    fn ls_format() {
+        let remote = GitRemote::init(None);
+        let clone = remote.clone("user0", "host0");
+
+        let commit_id = clone.commit("Remember me");
+        clone.tag("v1", None);
+        sync(
+            &mut NoRenderer,
+            &clone.git,
+            &clone.user,
+            &clone.host,
+            &[clone.remote.always_borrow()],
+            &BranchPatterns::default(),
+            SyncExtras {
+                tags: true,
+                ..SyncExtras::default()
+            },
+        )
+        .unwrap();
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::Ls {
+            printer: LsPrinter::Format(
+                LsFormat::parse("%(host)\t%(branch)\t%(objectname:short)\t%(subject)").unwrap(),
+            ),
+            user: clone.user.always_borrow(),
+            fetch_remotes: Vec::new(),
+            host_filter: Filter::All,
+            branch_filter: Filter::All,
+        }
+        .execute(&mut renderer, &clone.git)
+        .unwrap();
+
+        let short_id = &commit_id.0[..7];
+        assert_eq!(
+            renderer.as_str(),
+            format!(
+                "host0\tmaster\t{}\tRemember me\nhost0\tv1\t{}\tRemember me\n",
+                short_id, short_id
+            ),
+        );
+    }
+
     /// Exercise `LsPrinter::Grouped` with a bunch of `Filter::Deny`s.
     #[test]
     fn ls_two_hosts() {
@@ -269,7 +1938,9 @@ mod test {
             &host0.git,
             &host0.user,
             &host0.host,
-            &host0.remote,
+            &[host0.remote.always_borrow()],
+            &BranchPatterns::default(),
+            SyncExtras::default(),
         )
         .unwrap();
 
@@ -278,7 +1949,9 @@ mod test {
             &host1.git,
             &host1.user,
             &host1.host,
-            &host1.remote,
+            &[host1.remote.always_borrow()],
+            &BranchPatterns::default(),
+            SyncExtras::default(),
         )
         .unwrap();
 
@@ -286,7 +1959,7 @@ mod test {
         Workflow::Ls {
             printer: LsPrinter::Grouped,
             user: host1.user,
-            fetch_remote: Some(host1.remote),
+            fetch_remotes: vec![host1.remote],
             host_filter: Filter::Deny([host0.host].into()),
             branch_filter: Filter::Deny([host1.git.current_branch(&mut renderer).unwrap()].into()),
         }
@@ -296,16 +1969,1978 @@ mod test {
         assert_eq!(renderer.as_str(), "host1\n");
     }
 
+    /// `LsPrinter::Json` should always print a valid array, even when nothing matches.
     #[test]
-    fn filter_does_filtering() {
-        for (filter, expected) in [
-            (Filter::All, vec!["foo", "bar"]),
-            (Filter::Allow(["foo"].into()), vec!["foo"]),
-            (Filter::Deny(["foo"].into()), vec!["bar"]),
-        ] {
-            let mut got = vec!["foo", "bar"];
-            got.retain(|i| filter.contains(i));
-            assert_eq!(got, expected);
-        }
+    fn ls_json_empty() {
+        let remote = GitRemote::init(None);
+        let clone = remote.clone("user0", "host0");
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::Ls {
+            printer: LsPrinter::Json,
+            user: clone.user.clone(),
+            fetch_remotes: Vec::new(),
+            host_filter: Filter::All,
+            branch_filter: Filter::All,
+        }
+        .execute(&mut renderer, &clone.git)
+        .unwrap();
+
+        assert_eq!(renderer.as_str(), "[]\n");
+    }
+
+    /// A dry run sync should describe the push and prune without changing anything.
+    #[test]
+    fn sync_dry_run() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        let feature = Branch::from("feature");
+
+        host0
+            .git
+            .create_branch(&mut NoRenderer, "Start feature", &feature)
+            .unwrap();
+        sync_host(&host0);
+        let feature_ref = host0.get_nomad_ref("feature").unwrap();
+        host0
+            .git
+            .delete_branch(&mut NoRenderer, "Abandon feature", &feature)
+            .unwrap();
+
+        let origin_before = remote.nomad_refs();
+        let local_before = host0.nomad_refs();
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::Sync {
+            user: host0.user.always_borrow(),
+            host: host0.host.always_borrow(),
+            remotes: vec![host0.remote.always_borrow()],
+            branch_patterns: BranchPatterns::default(),
+            extras: SyncExtras::default(),
+            dry_run: true,
+        }
+        .execute(&mut renderer, &host0.git)
+        .unwrap();
+
+        assert_eq!(
+            renderer.as_str(),
+            format!(
+                concat!(
+                    "Dry run, nothing will be changed\n",
+                    "Would push local branches to origin\n",
+                    "  refs/heads/master -> refs/nomad/user0/host0/master\n",
+                    "Would fetch branches from origin\n",
+                    "Would prune branches\n",
+                    "  Delete refs/nomad/host0/feature (was {}) locally and at origin\n",
+                ),
+                feature_ref.ref_.0,
+            )
+        );
+
+        assert_eq!(remote.nomad_refs(), origin_before);
+        assert_eq!(host0.nomad_refs(), local_before);
+    }
+
+    /// A dry run should report the working tree snapshot it would push without making it, which
+    /// would write the index and objects.
+    #[test]
+    fn sync_dry_run_worktree() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        host0.commit_file("notes", "committed");
+        host0.write_file("notes", "uncommitted");
+
+        let objects = || {
+            fs::read_dir(host0.git.git_dir().join("objects"))
+                .unwrap()
+                .flat_map(|dir| fs::read_dir(dir.unwrap().path()).into_iter().flatten())
+                .count()
+        };
+        let index = host0.git.git_dir().join("index");
+        let index_before = fs::read(&index).unwrap();
+        let objects_before = objects();
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::Sync {
+            user: host0.user.always_borrow(),
+            host: host0.host.always_borrow(),
+            remotes: vec![host0.remote.always_borrow()],
+            branch_patterns: BranchPatterns::default(),
+            extras: SyncExtras {
+                worktree: true,
+                ..SyncExtras::default()
+            },
+            dry_run: true,
+        }
+        .execute(&mut renderer, &host0.git)
+        .unwrap();
+
+        assert_eq!(
+            renderer.as_str(),
+            concat!(
+                "Dry run, nothing will be changed\n",
+                "Would push local branches to origin\n",
+                "  refs/heads/master -> refs/nomad/user0/host0/master\n",
+                "  working tree -> refs/nomad-worktree/user0/host0/master\n",
+                "Would fetch branches from origin\n",
+                "Would not prune any branches\n",
+            )
+        );
+        assert_eq!(fs::read(&index).unwrap(), index_before);
+        assert_eq!(objects(), objects_before);
+        assert!(remote.nomad_refs().is_empty());
+    }
+
+    /// Excluded branches should never be pushed, and should be removed from the remote if they
+    /// were pushed before being excluded.
+    #[test]
+    fn sync_branch_patterns() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+
+        for branch in ["feature/login", "wip/huge"] {
+            host0
+                .git
+                .create_branch(&mut NoRenderer, "Create branch", &Branch::from(branch))
+                .unwrap();
+        }
+
+        let sync_with_exclude = |exclude: &[&str]| {
+            Workflow::Sync {
+                user: host0.user.always_borrow(),
+                host: host0.host.always_borrow(),
+                remotes: vec![host0.remote.always_borrow()],
+                branch_patterns: BranchPatterns::new([] as [&str; 0], exclude).unwrap(),
+                extras: SyncExtras::default(),
+                dry_run: false,
+            }
+            .execute(&mut NoRenderer, &host0.git)
+            .unwrap();
+        };
+
+        let remote_branches = || {
+            let mut branches = remote
+                .nomad_refs()
+                .into_iter()
+                .map(|nomad_ref| nomad_ref.branch.0.into_owned())
+                .collect::<Vec<_>>();
+            branches.sort();
+            branches
+        };
+
+        sync_with_exclude(&["wip/*"]);
+        assert_eq!(remote_branches(), vec!["feature/login", INITIAL_BRANCH]);
+
+        sync_with_exclude(&["wip/*", "feature/*"]);
+        assert_eq!(remote_branches(), vec![INITIAL_BRANCH]);
+        assert_eq!(
+            host0
+                .nomad_refs()
+                .into_iter()
+                .map(|nomad_ref| nomad_ref.branch.0.into_owned())
+                .collect::<Vec<_>>(),
+            vec![INITIAL_BRANCH]
+        );
+    }
+
+    /// Syncing with several remotes should push to and prune from all of them, and only prune refs
+    /// from other hosts once no remote has them.
+    #[test]
+    fn sync_multiple_remotes() {
+        let origin = GitRemote::init(None);
+        let mirror = GitRemote::init(None);
+
+        let host0 = origin.clone("user0", "host0");
+        let host1 = origin.clone("user0", "host1");
+        let remotes0 = [
+            host0.remote.always_borrow(),
+            host0.add_remote("mirror", &mirror),
+        ];
+        let remotes1 = [
+            host1.remote.always_borrow(),
+            host1.add_remote("mirror", &mirror),
+        ];
+
+        let sync = |clone: &GitClone, remotes: &[Remote]| {
+            sync(
+                &mut NoRenderer,
+                &clone.git,
+                &clone.user,
+                &clone.host,
+                remotes,
+                &BranchPatterns::default(),
+                SyncExtras::default(),
+            )
+            .unwrap();
+        };
+        let purge = |clone: &GitClone, remotes: &[Remote]| {
+            Workflow::Purge {
+                user: clone.user.always_borrow(),
+                remotes: remotes.iter().map(Remote::always_borrow).collect(),
+                host_filter: Filter::Allow([clone.host.always_borrow()].into()),
+                older_than: None,
+                dry_run: false,
+            }
+            .execute(&mut NoRenderer, &clone.git)
+            .unwrap();
+        };
+        let hosts = |nomad_refs: HashSet<NomadRef<'_, GitCommitId>>| {
+            let mut hosts = nomad_refs
+                .into_iter()
+                .map(|nomad_ref| nomad_ref.host.0.into_owned())
+                .collect::<Vec<_>>();
+            hosts.sort();
+            hosts.dedup();
+            hosts
+        };
+
+        let feature = Branch::from("feature");
+        host0
+            .git
+            .create_branch(&mut NoRenderer, "Start feature", &feature)
+            .unwrap();
+        sync(&host0, &remotes0);
+        sync(&host1, &remotes1);
+        sync(&host0, &remotes0);
+
+        assert_eq!(hosts(origin.nomad_refs()), vec!["host0", "host1"]);
+        assert_eq!(hosts(mirror.nomad_refs()), vec!["host0", "host1"]);
+        assert_eq!(hosts(host0.nomad_refs()), vec!["host0", "host1"]);
+
+        // Deleted branches are pruned from every remote
+        host0
+            .git
+            .delete_branch(&mut NoRenderer, "Abandon feature", &feature)
+            .unwrap();
+        sync(&host0, &remotes0);
+        for remote in [&origin, &mirror] {
+            assert!(remote
+                .nomad_refs()
+                .iter()
+                .all(|nomad_ref| nomad_ref.branch != feature));
+        }
+
+        // host1 is still on the mirror, so it should not be pruned locally yet
+        purge(&host1, &remotes1[..1]);
+        sync(&host0, &remotes0);
+        assert_eq!(hosts(origin.nomad_refs()), vec!["host0"]);
+        assert_eq!(hosts(host0.nomad_refs()), vec!["host0", "host1"]);
+
+        purge(&host1, &remotes1[1..]);
+        sync(&host0, &remotes0);
+        assert_eq!(hosts(mirror.nomad_refs()), vec!["host0"]);
+        assert_eq!(hosts(host0.nomad_refs()), vec!["host0"]);
+    }
+
+    /// `purge` with `older_than` should only delete refs for hosts that have been inactive.
+    #[test]
+    fn purge_older_than() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        let host1 = remote.clone("user0", "host1");
+
+        host1.commit_at("Ancient history", "2000-01-01T00:00:00Z");
+        sync_host(&host1);
+        sync_host(&host0);
+
+        let purge = |renderer: &mut MemoryRenderer, dry_run: bool| {
+            Workflow::Purge {
+                user: host0.user.always_borrow(),
+                remotes: vec![host0.remote.always_borrow()],
+                host_filter: Filter::All,
+                older_than: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+                dry_run,
+            }
+            .execute(renderer, &host0.git)
+            .unwrap();
+        };
+
+        let mut renderer = MemoryRenderer::new();
+        purge(&mut renderer, true);
+        let output = renderer.as_str();
+        assert!(output.contains("Would expire host1, last active "));
+        assert!(!output.contains("host0"));
+
+        purge(&mut MemoryRenderer::new(), false);
+        for nomad_refs in [remote.nomad_refs(), host0.nomad_refs()] {
+            assert!(nomad_refs
+                .iter()
+                .all(|nomad_ref| nomad_ref.host == host0.host));
+            assert!(!nomad_refs.is_empty());
+        }
+    }
+
+    /// A clone pushing as a host that another clone is using should be refused until that host is
+    /// purged.
+    #[test]
+    fn sync_host_collision() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        sync_host(&host0);
+
+        let mut impostor = remote.clone("user0", "impostor");
+        impostor.host = Host::from("host0");
+        let sync_impostor = || {
+            sync(
+                &mut NoRenderer,
+                &impostor.git,
+                &impostor.user,
+                &impostor.host,
+                &[impostor.remote.always_borrow()],
+                &BranchPatterns::default(),
+                SyncExtras::default(),
+            )
+        };
+
+        impostor.fetch();
+        let error = sync_impostor().unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("is already being pushed by another clone"));
+
+        Workflow::Purge {
+            user: impostor.user.always_borrow(),
+            remotes: vec![impostor.remote.always_borrow()],
+            host_filter: Filter::Allow([Host::from("host0")].into()),
+            older_than: None,
+            dry_run: false,
+        }
+        .execute(&mut NoRenderer, &impostor.git)
+        .unwrap();
+
+        sync_impostor().unwrap();
+        assert!(!remote.nomad_refs().is_empty());
+    }
+
+    /// Stash entries should be synced to other hosts, applied from there, and pruned once dropped.
+    #[test]
+    fn sync_stash() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        let host1 = remote.clone("user0", "host1");
+
+        let sync_stash = |clone: &GitClone| {
+            sync(
+                &mut NoRenderer,
+                &clone.git,
+                &clone.user,
+                &clone.host,
+                &[clone.remote.always_borrow()],
+                &BranchPatterns::new(["some-other-branch"], Vec::<String>::new()).unwrap(),
+                SyncExtras {
+                    stash: true,
+                    ..SyncExtras::default()
+                },
+            )
+            .unwrap();
+        };
+        let stash_apply = |clone: &GitClone, index| {
+            Workflow::StashApply {
+                user: clone.user.always_borrow(),
+                host: Host::from("host0"),
+                index,
+                dry_run: false,
+            }
+            .execute(&mut NoRenderer, &clone.git)
+        };
+
+        host0.stash("older", "older contents");
+        host0.stash("newer", "newer contents");
+        sync_stash(&host0);
+        sync_stash(&host1);
+
+        stash_apply(&host1, 1).unwrap();
+        assert_eq!(host1.read_file("older").as_deref(), Some("older contents"));
+        assert_eq!(host1.read_file("newer"), None);
+
+        let error = stash_apply(&host1, 2).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Host host0 has no synced stash entry 2, run `git nomad sync --stash` on it first"
+        );
+
+        host0.clear_stash();
+        sync_stash(&host0);
+        sync_stash(&host1);
+        assert!(stash_apply(&host1, 0).is_err());
+        assert!(remote.nomad_refs().is_empty());
+    }
+
+    /// Stash entries live apart from branches, so a local branch under `stash/` should still be
+    /// synced alongside them.
+    #[test]
+    fn sync_stash_alongside_stash_branch() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        host0.checkout_new_branch("stash/experiment");
+        host0.stash("stashed", "stashed contents");
+
+        sync(
+            &mut NoRenderer,
+            &host0.git,
+            &host0.user,
+            &host0.host,
+            &[host0.remote.always_borrow()],
+            &BranchPatterns::default(),
+            SyncExtras {
+                stash: true,
+                ..SyncExtras::default()
+            },
+        )
+        .unwrap();
+
+        let mut synced = remote
+            .nomad_refs()
+            .into_iter()
+            .map(|nomad_ref| (nomad_ref.kind, nomad_ref.branch.0.into_owned()))
+            .collect::<Vec<_>>();
+        synced.sort();
+        assert_eq!(
+            synced,
+            vec![
+                (RefKind::Branch, INITIAL_BRANCH.to_string()),
+                (RefKind::Branch, "stash/experiment".to_string()),
+                (RefKind::Stash, "0".to_string()),
+            ]
+        );
+    }
+
+    /// Tags should be synced, listed separately from branches, and pruned once deleted.
+    #[test]
+    fn sync_tags() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        let host1 = remote.clone("user0", "host1");
+
+        let sync_tags = |clone: &GitClone| {
+            sync(
+                &mut NoRenderer,
+                &clone.git,
+                &clone.user,
+                &clone.host,
+                &[clone.remote.always_borrow()],
+                &BranchPatterns::default(),
+                SyncExtras {
+                    tags: true,
+                    ..SyncExtras::default()
+                },
+            )
+            .unwrap();
+        };
+        let ls = |clone: &GitClone| {
+            let mut renderer = MemoryRenderer::new();
+            Workflow::Ls {
+                printer: LsPrinter::Grouped,
+                user: clone.user.always_borrow(),
+                fetch_remotes: Vec::new(),
+                host_filter: Filter::Allow([Host::from("host0")].into()),
+                branch_filter: Filter::All,
+            }
+            .execute(&mut renderer, &clone.git)
+            .unwrap();
+            renderer.as_str().to_string()
+        };
+
+        let commit_id = host0.current_commit().0;
+        host0.tag("bisect-good", None);
+        host0.tag("v1.0-rc1", Some("release candidate"));
+        sync_tags(&host0);
+        sync_tags(&host1);
+
+        let listing = ls(&host1);
+        let lines = listing.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "host0");
+        assert_eq!(
+            lines[1],
+            format!("  refs/nomad/host0/{} -> {}", INITIAL_BRANCH, commit_id)
+        );
+        assert_eq!(lines[2], "host0 tags");
+        assert_eq!(
+            lines[3],
+            format!("  refs/nomad-tags/host0/bisect-good -> {}", commit_id)
+        );
+        // Annotated tags keep pointing at the tag object.
+        assert!(lines[4].starts_with("  refs/nomad-tags/host0/v1.0-rc1 -> "));
+        assert!(!lines[4].ends_with(&commit_id));
+        assert_eq!(lines.len(), 5);
+
+        host0.delete_tag("bisect-good");
+        sync_tags(&host0);
+        sync_tags(&host1);
+        assert!(!ls(&host1).contains("bisect-good"));
+    }
+
+    /// Uncommitted changes should be synced without touching HEAD or the index, and applied on
+    /// another host.
+    #[test]
+    fn sync_worktree() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        let host1 = remote.clone("user0", "host1");
+
+        let sync_worktree = |clone: &GitClone| {
+            sync(
+                &mut NoRenderer,
+                &clone.git,
+                &clone.user,
+                &clone.host,
+                &[clone.remote.always_borrow()],
+                &BranchPatterns::default(),
+                SyncExtras {
+                    worktree: true,
+                    ..SyncExtras::default()
+                },
+            )
+            .unwrap();
+        };
+        let worktree_apply = |clone: &GitClone| {
+            Workflow::WorktreeApply {
+                user: clone.user.always_borrow(),
+                host: Host::from("host0"),
+                branch: Branch::from(INITIAL_BRANCH),
+                dry_run: false,
+            }
+            .execute(&mut NoRenderer, &clone.git)
+        };
+
+        // A committed file that is rewritten with the same contents looks modified until the index
+        // is refreshed, as long as the index recorded an mtime that is old enough not to be racy.
+        host0.write_file("unchanged", "same");
+        fs::File::options()
+            .write(true)
+            .open(host0.working_directory().join("unchanged"))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+        run_notable(
+            &mut NoRenderer,
+            None,
+            "Stage file",
+            host0.git.command().args(["add", "unchanged"]),
+        )
+        .unwrap();
+        let committed = host0.commit_file("notes", "committed");
+        host0.write_file("notes", "uncommitted");
+        host0.write_file("unchanged", "same");
+        let index = host0.git.git_dir().join("index");
+        let index_before = fs::read(&index).unwrap();
+        sync_worktree(&host0);
+        assert_eq!(host0.current_commit(), committed);
+        assert_eq!(fs::read(&index).unwrap(), index_before);
+        assert_eq!(host0.read_file("notes").as_deref(), Some("uncommitted"));
+
+        sync_worktree(&host1);
+        checkout(&host1, Some(&Host::from("host0")), INITIAL_BRANCH, false).unwrap();
+        worktree_apply(&host1).unwrap();
+        assert_eq!(host1.read_file("notes").as_deref(), Some("uncommitted"));
+
+        // Once the working tree is clean, the snapshot is pruned.
+        host0.write_file("notes", "committed");
+        sync_worktree(&host0);
+        sync_worktree(&host1);
+        let error = worktree_apply(&host1).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Host host0 has no synced working tree snapshot for branch master, run \
+             `git nomad sync --include-worktree` on it first"
+        );
+
+        host0.detach_head();
+        let error = sync(
+            &mut NoRenderer,
+            &host0.git,
+            &host0.user,
+            &host0.host,
+            &[host0.remote.always_borrow()],
+            &BranchPatterns::default(),
+            SyncExtras {
+                worktree: true,
+                ..SyncExtras::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(exit_code(&error), NomadError::DetachedHead.exit_code());
+    }
+
+    /// `init` should save confirmed or overridden answers, skipping the prompt for values that
+    /// were already chosen.
+    #[test]
+    fn init_saves_answers() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+
+        let mut renderer = MemoryRenderer::with_answers(["", "origin, mirror"]);
+        Workflow::Init {
+            user: InitSetting {
+                value: User::from("user0"),
+                ask: true,
+            },
+            host: InitSetting {
+                value: Host::from("host0"),
+                ask: false,
+            },
+            remotes: InitSetting {
+                value: vec![Remote::from("origin")],
+                ask: true,
+            },
+            scope: ConfigScope::Local,
+            dry_run: false,
+        }
+        .execute(&mut renderer, &host0.git)
+        .unwrap();
+
+        assert_eq!(
+            renderer.as_str(),
+            concat!(
+                "User name, shared by multiple clones [user0]: user0\n",
+                "Remotes, separated by spaces [origin]: origin, mirror\n",
+            ),
+        );
+        assert_eq!(
+            host0.git.get_config(&mut NoRenderer, CONFIG_USER).unwrap(),
+            Some("user0".to_string()),
+        );
+        assert_eq!(
+            host0.git.get_config(&mut NoRenderer, CONFIG_HOST).unwrap(),
+            Some("host0".to_string()),
+        );
+        assert_eq!(
+            host0
+                .git
+                .get_config_all(&mut NoRenderer, CONFIG_REMOTE)
+                .unwrap(),
+            vec!["origin".to_string(), "mirror".to_string()],
+        );
+    }
+
+    /// `init --dry-run` should describe what would be saved without saving it.
+    #[test]
+    fn init_dry_run() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::Init {
+            user: InitSetting {
+                value: User::from("user0"),
+                ask: false,
+            },
+            host: InitSetting {
+                value: Host::from("host0"),
+                ask: false,
+            },
+            remotes: InitSetting {
+                value: vec![Remote::from("origin")],
+                ask: false,
+            },
+            scope: ConfigScope::Global,
+            dry_run: true,
+        }
+        .execute(&mut renderer, &host0.git)
+        .unwrap();
+
+        assert_eq!(
+            renderer.as_str(),
+            concat!(
+                "Would set nomad.user = user0 in global git config\n",
+                "Would set nomad.host = host0 in global git config\n",
+                "Would set nomad.remote = origin in global git config\n",
+            ),
+        );
+        assert_eq!(
+            host0.git.get_config(&mut NoRenderer, CONFIG_USER).unwrap(),
+            None
+        );
+    }
+
+    /// `config show` should print each value alongside where it came from.
+    #[test]
+    fn config_show() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::ConfigShow {
+            settings: vec![
+                Setting {
+                    name: CONFIG_USER,
+                    values: vec!["user0".to_string()],
+                    source: SettingSource::Env("GIT_NOMAD_USER"),
+                },
+                Setting {
+                    name: CONFIG_HOST,
+                    values: vec!["host0".to_string()],
+                    source: SettingSource::CommandLine,
+                },
+                Setting {
+                    name: CONFIG_REMOTE,
+                    values: vec!["origin".to_string(), "mirror".to_string()],
+                    source: SettingSource::GitConfig(vec![
+                        "/etc/gitconfig".to_string(),
+                        ".git/config".to_string(),
+                    ]),
+                },
+                Setting {
+                    name: "include",
+                    values: vec![],
+                    source: SettingSource::Default("default"),
+                },
+            ],
+        }
+        .execute(&mut renderer, &host0.git)
+        .unwrap();
+
+        assert_eq!(
+            renderer.as_str(),
+            concat!(
+                "user = user0 (GIT_NOMAD_USER)\n",
+                "host = host0 (command line)\n",
+                "remote = origin, mirror (nomad.remote in /etc/gitconfig, .git/config)\n",
+                "include = (none) (default)\n",
+            ),
+        );
+    }
+
+    /// `config set`, `get`, and `unset` should round trip through git config.
+    #[test]
+    fn config_set_get_unset() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+
+        Workflow::ConfigSet {
+            key: CONFIG_REMOTE.to_string(),
+            values: vec!["origin".to_string(), "mirror".to_string()],
+            scope: ConfigScope::Local,
+            dry_run: false,
+        }
+        .execute(&mut NoRenderer, &host0.git)
+        .unwrap();
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::ConfigGet {
+            key: CONFIG_REMOTE.to_string(),
+        }
+        .execute(&mut renderer, &host0.git)
+        .unwrap();
+        assert_eq!(renderer.as_str(), "origin\nmirror\n");
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::ConfigUnset {
+            key: CONFIG_REMOTE.to_string(),
+            scope: ConfigScope::Local,
+            dry_run: true,
+        }
+        .execute(&mut renderer, &host0.git)
+        .unwrap();
+        assert_eq!(
+            renderer.as_str(),
+            "Would unset nomad.remote in local git config\n"
+        );
+
+        Workflow::ConfigUnset {
+            key: CONFIG_REMOTE.to_string(),
+            scope: ConfigScope::Local,
+            dry_run: false,
+        }
+        .execute(&mut NoRenderer, &host0.git)
+        .unwrap();
+
+        let error = Workflow::ConfigGet {
+            key: CONFIG_REMOTE.to_string(),
+        }
+        .execute(&mut NoRenderer, &host0.git)
+        .unwrap_err();
+        assert_eq!(error.to_string(), "nomad.remote is not set");
+
+        let error = Workflow::ConfigUnset {
+            key: CONFIG_REMOTE.to_string(),
+            scope: ConfigScope::Local,
+            dry_run: false,
+        }
+        .execute(&mut NoRenderer, &host0.git)
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "nomad.remote is not set in local git config"
+        );
+    }
+
+    /// `push` should only push the named branches, skipping ones that are missing or excluded.
+    #[test]
+    fn push_named_branches() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+
+        for branch in ["feature", "wip/huge"] {
+            host0
+                .git
+                .create_branch(&mut NoRenderer, "Create branch", &Branch::from(branch))
+                .unwrap();
+        }
+
+        Workflow::Push {
+            user: host0.user.always_borrow(),
+            host: host0.host.always_borrow(),
+            remotes: vec![host0.remote.always_borrow()],
+            branch_patterns: BranchPatterns::new([] as [&str; 0], ["wip/*"]).unwrap(),
+            branches: vec![
+                Branch::from("feature"),
+                Branch::from("wip/huge"),
+                Branch::from("deleted"),
+            ],
+            dry_run: false,
+        }
+        .execute(&mut NoRenderer, &host0.git)
+        .unwrap();
+
+        assert_eq!(
+            remote
+                .nomad_refs()
+                .into_iter()
+                .map(|nomad_ref| nomad_ref.branch.0.into_owned())
+                .collect::<Vec<_>>(),
+            vec!["feature"]
+        );
+    }
+
+    /// A dry run push should describe what would be pushed without touching the remote, not even
+    /// to claim the host with a clone marker.
+    #[test]
+    fn push_dry_run() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        host0
+            .git
+            .create_branch(&mut NoRenderer, "Create branch", &Branch::from("feature"))
+            .unwrap();
+
+        let remote_refs = || {
+            remote
+                .git
+                .list_refs(&mut NoRenderer, "")
+                .unwrap()
+                .into_iter()
+                .collect::<HashSet<_>>()
+        };
+        let before = remote_refs();
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::Push {
+            user: host0.user.always_borrow(),
+            host: host0.host.always_borrow(),
+            remotes: vec![host0.remote.always_borrow()],
+            branch_patterns: BranchPatterns::default(),
+            branches: vec![Branch::from("feature"), Branch::from("deleted")],
+            dry_run: true,
+        }
+        .execute(&mut renderer, &host0.git)
+        .unwrap();
+
+        assert_eq!(
+            renderer.as_str(),
+            concat!(
+                "Dry run, nothing will be changed\n",
+                "Would push local branches to origin\n",
+                "  refs/heads/feature -> refs/nomad/user0/host0/feature\n",
+            )
+        );
+        assert_eq!(remote_refs(), before);
+    }
+
+    /// Hooks should be installed into `core.hooksPath` when it is configured.
+    #[test]
+    fn install_hooks_honors_hooks_path() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        run_notable(
+            &mut NoRenderer,
+            None,
+            "Set core.hooksPath",
+            host0
+                .git
+                .command()
+                .args(["config", "core.hooksPath", ".githooks"]),
+        )
+        .unwrap();
+
+        let hooks_dir = host0.git.hooks_dir(&mut NoRenderer).unwrap();
+        assert!(hooks_dir.ends_with(".githooks"));
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::InstallHooks { dry_run: true }
+            .execute(&mut renderer, &host0.git)
+            .unwrap();
+        assert!(!hooks_dir.exists());
+        assert_eq!(
+            renderer.as_str(),
+            Hook::ALL
+                .iter()
+                .map(|hook| format!(
+                    "Would install {}\n",
+                    hooks::hook_path(&hooks_dir, *hook).display()
+                ))
+                .collect::<String>()
+        );
+
+        Workflow::InstallHooks { dry_run: false }
+            .execute(&mut NoRenderer, &host0.git)
+            .unwrap();
+        for hook in Hook::ALL {
+            assert_eq!(
+                hooks::state(&hooks_dir, hook).unwrap(),
+                HookState::Nomad { chained: false }
+            );
+        }
+
+        Workflow::UninstallHooks { dry_run: false }
+            .execute(&mut NoRenderer, &host0.git)
+            .unwrap();
+        for hook in Hook::ALL {
+            assert_eq!(hooks::state(&hooks_dir, hook).unwrap(), HookState::Missing);
+        }
+    }
+
+    /// The installed hooks should run any existing hook and then invoke `git nomad push` with just
+    /// the affected branch.
+    #[cfg(unix)]
+    #[test]
+    fn hooks_invoke_push() {
+        use std::{fs, os::unix::fs::PermissionsExt, thread::sleep, time::Duration};
+
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        let bin_dir = tempfile::tempdir().unwrap();
+        let log = bin_dir.path().join("log");
+
+        let write_script = |path: &std::path::Path, output: &str| {
+            fs::write(
+                path,
+                format!("#!/bin/sh\necho {} >> '{}'\n", output, log.display()),
+            )
+            .unwrap();
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+        };
+
+        let hooks_dir = host0.git.hooks_dir(&mut NoRenderer).unwrap();
+        fs::create_dir_all(&hooks_dir).unwrap();
+        write_script(
+            &hooks::hook_path(&hooks_dir, Hook::ReferenceTransaction),
+            "existing",
+        );
+        Workflow::InstallHooks { dry_run: false }
+            .execute(&mut NoRenderer, &host0.git)
+            .unwrap();
+
+        // Stand in for the real binary, which git finds on `$PATH` for `git nomad`.
+        write_script(&bin_dir.path().join("git-nomad"), "\"$@\"");
+        let path = format!(
+            "{}:{}",
+            bin_dir.path().display(),
+            std::env::var("PATH").unwrap()
+        );
+
+        for args in [
+            &["checkout", "-b", "feature/login"] as &[&str],
+            &["commit", "--allow-empty", "-m", "message"],
+        ] {
+            run_notable(
+                &mut NoRenderer,
+                None,
+                "Run git with hooks",
+                host0.git.command().env("PATH", &path).args(args),
+            )
+            .unwrap();
+        }
+
+        // The pushes happen in the background, so give them a chance to finish. The existing hook
+        // runs for every state of every transaction, so only distinct invocations are compared.
+        let read_log = || {
+            let mut lines = fs::read_to_string(&log)
+                .unwrap_or_default()
+                .lines()
+                .map(String::from)
+                .collect::<Vec<_>>();
+            lines.sort();
+            lines.dedup();
+            lines
+        };
+        let expected = ["existing", "push --quiet --branch feature/login"];
+        for _ in 0..100 {
+            if read_log() == expected {
+                break;
+            }
+            sleep(Duration::from_millis(50));
+        }
+
+        assert_eq!(read_log(), expected);
+    }
+
+    /// `BranchPatterns` should require an include match (if any) and reject exclude matches.
+    #[test]
+    fn branch_patterns_contains() {
+        let patterns = BranchPatterns::new(["feature/*", "master"], ["feature/huge*"]).unwrap();
+        assert!(patterns.contains(&Branch::from("master")));
+        assert!(patterns.contains(&Branch::from("feature/login")));
+        assert!(!patterns.contains(&Branch::from("feature/huge-refactor")));
+        assert!(!patterns.contains(&Branch::from("scratch")));
+
+        let everything = BranchPatterns::default();
+        assert!(everything.contains(&Branch::from("scratch")));
+    }
+
+    /// A dry run purge should list every ref that would be deleted without deleting them.
+    #[test]
+    fn purge_dry_run() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        sync_host(&host0);
+
+        // host1 has never fetched, so the plan has to come from the remote
+        let host1 = remote.clone("user0", "host1");
+        let commit_id = host0.current_commit();
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::Purge {
+            user: host1.user.always_borrow(),
+            remotes: vec![host1.remote.always_borrow()],
+            host_filter: Filter::All,
+            older_than: None,
+            dry_run: true,
+        }
+        .execute(&mut renderer, &host1.git)
+        .unwrap();
+
+        assert_eq!(
+            renderer.as_str(),
+            format!(
+                concat!(
+                    "Dry run, nothing will be changed\n",
+                    "Would prune branches\n",
+                    "  Delete refs/nomad/host0/master (was {}) locally and at origin\n",
+                ),
+                commit_id.0,
+            )
+        );
+
+        assert_eq!(
+            remote.nomad_refs(),
+            [host0.get_nomad_ref(INITIAL_BRANCH).unwrap()].into()
+        );
+        assert_eq!(host1.nomad_refs(), [].into());
+    }
+
+    fn status(clone: &GitClone) -> String {
+        let mut renderer = MemoryRenderer::new();
+        Workflow::Status {
+            user: clone.user.always_borrow(),
+            fetch_remotes: vec![clone.remote.always_borrow()],
+            host_filter: Filter::Deny([clone.host.always_borrow()].into()),
+            branch_filter: Filter::All,
+        }
+        .execute(&mut renderer, &clone.git)
+        .unwrap();
+        renderer.as_str().to_string()
+    }
+
+    /// Branches that are identical everywhere should be reported as up to date.
+    #[test]
+    fn status_up_to_date() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        sync_host(&host0);
+        let host1 = remote.clone("user0", "host1");
+        let commit_id = host1.current_commit();
+
+        assert_eq!(
+            status(&host1),
+            format!(
+                concat!(
+                    "master\n",
+                    "  refs/heads/master -> {0} (compared against)\n",
+                    "  refs/nomad/host0/master -> {0} (up to date)\n",
+                ),
+                commit_id.0
+            )
+        );
+    }
+
+    /// Hosts with extra commits should be reported as ahead, and local commits as behind.
+    #[test]
+    fn status_ahead_and_behind() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        let host1 = remote.clone("user0", "host1");
+        let host2 = remote.clone("user0", "host2");
+        let initial_commit = host2.current_commit();
+
+        host0.commit("host0 work");
+        let host0_commit = host0.commit("more host0 work");
+        sync_host(&host0);
+
+        sync_host(&host1);
+
+        let host2_commit = host2.commit("host2 work");
+
+        assert_eq!(
+            status(&host2),
+            format!(
+                concat!(
+                    "master (diverged)\n",
+                    "  refs/heads/master -> {2} (compared against)\n",
+                    "  refs/nomad/host0/master -> {1} (ahead 2, behind 1, diverged from {0})\n",
+                    "  refs/nomad/host1/master -> {0} (behind 1)\n",
+                ),
+                initial_commit.0, host0_commit.0, host2_commit.0
+            )
+        );
+    }
+
+    /// Branches that only exist in one place have nothing to compare against.
+    #[test]
+    fn status_single_copy() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        host0
+            .git
+            .create_branch(&mut NoRenderer, "Start feature", &Branch::from("feature"))
+            .unwrap();
+        sync_host(&host0);
+
+        let host1 = remote.clone("user0", "host1");
+        let host1_commit = host1.current_commit();
+
+        assert_eq!(
+            status(&host1),
+            format!(
+                concat!(
+                    "master\n",
+                    "  refs/heads/master -> {0} (compared against)\n",
+                    "  refs/nomad/host0/master -> {0} (up to date)\n",
+                ),
+                host1_commit.0
+            )
+        );
+    }
+
+    #[test]
+    fn filter_does_filtering() {
+        for (filter, expected) in [
+            (Filter::All, vec!["foo", "bar"]),
+            (Filter::Allow(["foo"].into()), vec!["foo"]),
+            (Filter::Deny(["foo"].into()), vec!["bar"]),
+        ] {
+            let mut got = vec!["foo", "bar"];
+            got.retain(|i| filter.contains(i));
+            assert_eq!(got, expected);
+        }
+    }
+
+    /// Checking out a branch that only exists on another host should create it locally.
+    #[test]
+    fn checkout_new_branch() {
+        let remote = GitRemote::init(None);
+
+        let host0 = remote.clone("user0", "host0");
+        host0.checkout_new_branch("feature");
+        let feature_commit = host0.commit("feature work");
+        sync_host(&host0);
+
+        let host1 = remote.clone("user0", "host1");
+        checkout(&host1, None, "feature", false).unwrap();
+
+        assert_eq!(
+            host1.git.current_branch(&mut NoRenderer).unwrap(),
+            Branch::from("feature")
+        );
+        assert_eq!(host1.current_commit(), feature_commit);
+    }
+
+    /// Checking out a branch that is behind the other host should fast-forward it.
+    #[test]
+    fn checkout_fast_forward() {
+        let remote = GitRemote::init(None);
+
+        let host0 = remote.clone("user0", "host0");
+        let host1 = remote.clone("user0", "host1");
+        let new_commit = host0.commit("more work");
+        sync_host(&host0);
+
+        checkout(&host1, Some(&host0.host), INITIAL_BRANCH, false).unwrap();
+        assert_eq!(host1.current_commit(), new_commit);
+    }
+
+    /// Checking out a branch that has diverged should require `force`.
+    #[test]
+    fn checkout_diverged() {
+        let remote = GitRemote::init(None);
+
+        let host0 = remote.clone("user0", "host0");
+        let host1 = remote.clone("user0", "host1");
+        let host0_commit = host0.commit("host0 work");
+        sync_host(&host0);
+        let host1_commit = host1.commit("host1 work");
+
+        assert!(checkout(&host1, Some(&host0.host), INITIAL_BRANCH, false).is_err());
+        assert_eq!(host1.current_commit(), host1_commit);
+
+        checkout(&host1, Some(&host0.host), INITIAL_BRANCH, true).unwrap();
+        assert_eq!(host1.current_commit(), host0_commit);
+    }
+
+    /// Omitting the host is an error when multiple other hosts have the branch.
+    #[test]
+    fn checkout_ambiguous_host() {
+        let remote = GitRemote::init(None);
+
+        let host0 = remote.clone("user0", "host0");
+        sync_host(&host0);
+        let host1 = remote.clone("user0", "host1");
+        sync_host(&host1);
+
+        let host2 = remote.clone("user0", "host2");
+        assert!(checkout(&host2, None, INITIAL_BRANCH, false).is_err());
+        checkout(&host2, Some(&host1.host), INITIAL_BRANCH, false).unwrap();
+    }
+
+    /// Checking out a branch that no other host has is an error.
+    #[test]
+    fn checkout_missing_branch() {
+        let remote = GitRemote::init(None);
+
+        let host0 = remote.clone("user0", "host0");
+        sync_host(&host0);
+
+        assert!(checkout(&host0, None, INITIAL_BRANCH, false).is_err());
+        assert!(checkout(&host0, None, "nonexistent", false).is_err());
+    }
+
+    /// Synced stash entries should neither be checked out nor reported as branches.
+    #[test]
+    fn synced_stash_is_not_a_branch() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        host0.stash("stashed", "stashed contents");
+        sync(
+            &mut NoRenderer,
+            &host0.git,
+            &host0.user,
+            &host0.host,
+            &[host0.remote.always_borrow()],
+            &BranchPatterns::default(),
+            SyncExtras {
+                stash: true,
+                ..SyncExtras::default()
+            },
+        )
+        .unwrap();
+
+        let host1 = remote.clone("user0", "host1");
+        let error = checkout(&host1, None, "0", false).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No host has a nomad managed ref for branch 0"
+        );
+        assert!(!host1
+            .git
+            .snapshot(&mut NoRenderer, &host1.user)
+            .unwrap()
+            .local_branches
+            .contains(&Branch::from("0")));
+
+        assert!(!status(&host1).contains("stash"));
+    }
+
+    /// Tags live apart from branches, so a branch under `tags/` should be synced alongside them
+    /// and checked out like any other branch.
+    #[test]
+    fn sync_tags_alongside_tags_branch() {
+        let remote = GitRemote::init(None);
+        let host0 = remote.clone("user0", "host0");
+        host0.checkout_new_branch("tags/v2-work");
+        host0.tag("v1", None);
+        sync(
+            &mut NoRenderer,
+            &host0.git,
+            &host0.user,
+            &host0.host,
+            &[host0.remote.always_borrow()],
+            &BranchPatterns::default(),
+            SyncExtras {
+                tags: true,
+                ..SyncExtras::default()
+            },
+        )
+        .unwrap();
+
+        let mut synced = remote
+            .nomad_refs()
+            .into_iter()
+            .map(|nomad_ref| (nomad_ref.kind, nomad_ref.branch.0.into_owned()))
+            .collect::<Vec<_>>();
+        synced.sort();
+        assert_eq!(
+            synced,
+            vec![
+                (RefKind::Branch, INITIAL_BRANCH.to_string()),
+                (RefKind::Branch, "tags/v2-work".to_string()),
+                (RefKind::Tag, "v1".to_string()),
+            ]
+        );
+
+        let host1 = remote.clone("user0", "host1");
+        checkout(&host1, None, "tags/v2-work", false).unwrap();
+        assert!(status(&host1).contains("tags/v2-work"));
+        assert!(checkout(&host1, None, "v1", false).is_err());
+    }
+
+    /// Syncing should pick up nomad refs from other hosts.
+    ///
+    /// When the other host deletes their branch (and thus deletes their nomad ref on the remote),
+    /// the equivalent local nomad ref for that host should also be deleted.
+    ///
+    /// See https://github.com/rraval/git-nomad/issues/1
+    #[test]
+    fn issue_1() {
+        let origin = GitRemote::init(None);
+        let feature = &Branch::from("feature");
+
+        let host0 = origin.clone("user0", "host0");
+        sync_host(&host0);
+
+        let host1 = origin.clone("user0", "host1");
+        host1
+            .git
+            .create_branch(&mut NoRenderer, "Start feature branch", feature)
+            .unwrap();
+        sync_host(&host1);
+
+        // both hosts have synced, the origin should have refs from both (including the one for the
+        // feature branch on host1)
+        assert_eq!(
+            origin.nomad_refs(),
+            HashSet::from_iter([
+                host0.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+                host1.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+                host1.get_nomad_ref("feature").unwrap(),
+            ])
+        );
+
+        // host0 hasn't observed host1 yet
+        assert_eq!(
+            host0.nomad_refs(),
+            HashSet::from_iter([host0.get_nomad_ref(INITIAL_BRANCH).unwrap(),])
+        );
+
+        // sync host0, which should observe host1 refs
+        sync_host(&host0);
+        assert_eq!(
+            host0.nomad_refs(),
+            HashSet::from_iter([
+                host0.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+                host1.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+                host1.get_nomad_ref("feature").unwrap(),
+            ])
+        );
+
+        // host1 deletes the branch and syncs, removing it from origin
+        host1
+            .git
+            .delete_branch(&mut NoRenderer, "Abandon feature branch", feature)
+            .unwrap();
+        sync_host(&host1);
+
+        assert_eq!(
+            origin.nomad_refs(),
+            HashSet::from_iter([
+                host0.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+                host1.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+            ])
+        );
+
+        // host0 syncs and removes the ref for the deleted feature branch
+        sync_host(&host0);
+        assert_eq!(
+            host0.nomad_refs(),
+            HashSet::from_iter([
+                host0.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+                host1.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+            ])
+        );
+    }
+
+    /// Branches with slashes in their names should be synced, listed, pruned, and purged just like
+    /// any other branch.
+    #[test]
+    fn nested_branch_names() {
+        let origin = GitRemote::init(None);
+        let nested = &Branch::from("feature/login");
+
+        let host0 = origin.clone("user0", "host0");
+        host0
+            .git
+            .create_branch(&mut NoRenderer, "Start nested branch", nested)
+            .unwrap();
+        sync_host(&host0);
+
+        let host1 = origin.clone("user0", "host1");
+        sync_host(&host1);
+
+        // host1 should observe the nested branch from host0
+        assert_eq!(
+            host1.nomad_refs(),
+            HashSet::from_iter([
+                host0.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+                host0.get_nomad_ref("feature/login").unwrap(),
+                host1.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+            ])
+        );
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::Ls {
+            printer: LsPrinter::Ref,
+            user: host1.user.always_borrow(),
+            fetch_remotes: Vec::new(),
+            host_filter: Filter::All,
+            branch_filter: Filter::Allow([nested.always_borrow()].into()),
+        }
+        .execute(&mut renderer, &host1.git)
+        .unwrap();
+        assert_eq!(renderer.as_str(), "refs/nomad/host0/feature/login\n");
+
+        // host0 deletes the nested branch, which should prune it from the origin and then host1
+        host0
+            .git
+            .delete_branch(&mut NoRenderer, "Abandon nested branch", nested)
+            .unwrap();
+        sync_host(&host0);
+        assert_eq!(
+            origin.nomad_refs(),
+            HashSet::from_iter([
+                host0.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+                host1.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+            ])
+        );
+
+        sync_host(&host1);
+        assert_eq!(
+            host1.nomad_refs(),
+            HashSet::from_iter([
+                host0.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+                host1.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+            ])
+        );
+
+        // host1 starts its own nested branch, which purging should clean up
+        host1
+            .git
+            .create_branch(&mut NoRenderer, "Start nested branch", nested)
+            .unwrap();
+        sync_host(&host1);
+        assert!(origin
+            .nomad_refs()
+            .contains(&host1.get_nomad_ref("feature/login").unwrap()));
+
+        Workflow::Purge {
+            user: host1.user.always_borrow(),
+            remotes: vec![host1.remote.always_borrow()],
+            host_filter: Filter::All,
+            older_than: None,
+            dry_run: false,
+        }
+        .execute(&mut NoRenderer, &host1.git)
+        .unwrap();
+        assert_eq!(origin.nomad_refs(), HashSet::new());
+        assert_eq!(host1.nomad_refs(), HashSet::new());
+    }
+
+    /// Explicitly pruning other hosts should delete both local and remote nomad refs for that
+    /// host.
+    ///
+    /// See https://github.com/rraval/git-nomad/issues/2
+    #[test]
+    fn issue_2_other_host() {
+        let origin = GitRemote::init(None);
+
+        let host0 = origin.clone("user0", "host0");
+        sync_host(&host0);
+
+        let host1 = origin.clone("user0", "host1");
+        sync_host(&host1);
+
+        // both hosts have synced, the origin should have both refs
+        assert_eq!(
+            origin.nomad_refs(),
+            HashSet::from_iter([
+                host0.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+                host1.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+            ])
+        );
+
+        // pruning refs for host0 from host1
+        Workflow::Purge {
+            user: host1.user.always_borrow(),
+            remotes: vec![host1.remote.always_borrow()],
+            host_filter: Filter::Allow(HashSet::from_iter([host0.host.always_borrow()])),
+            older_than: None,
+            dry_run: false,
+        }
+        .execute(&mut NoRenderer, &host1.git)
+        .unwrap();
+
+        // the origin should only have refs for host1
+        assert_eq!(
+            origin.nomad_refs(),
+            HashSet::from_iter([host1.get_nomad_ref(INITIAL_BRANCH).unwrap(),])
+        );
+    }
+
+    /// Explicitly pruning everything should delete both local and remote refs for both the current
+    /// and other host on the remote.
+    ///
+    /// See https://github.com/rraval/git-nomad/issues/2
+    #[test]
+    fn issue_2_all() {
+        let origin = GitRemote::init(Some(Verbosity::max()));
+
+        let host0 = origin.clone("user0", "host0");
+        sync_host(&host0);
+
+        let host1 = origin.clone("user0", "host1");
+        sync_host(&host1);
+
+        // both hosts have synced, the origin should have both refs
+        assert_eq!(
+            origin.nomad_refs(),
+            HashSet::from_iter([
+                host0.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+                host1.get_nomad_ref(INITIAL_BRANCH).unwrap(),
+            ])
+        );
+
+        // pruning refs for all hosts from host1
+        Workflow::Purge {
+            user: host1.user.always_borrow(),
+            remotes: vec![host1.remote],
+            host_filter: Filter::All,
+            older_than: None,
+            dry_run: false,
+        }
+        .execute(&mut NoRenderer, &host1.git)
+        .unwrap();
+
+        // the origin should have no refs
+        assert_eq!(origin.nomad_refs(), HashSet::new(),);
+    }
+}
+
+/// Scenarios that run against [`crate::git_fake`] instead of real `git`, which keeps them fast
+/// enough to cover edge cases and injected failures exhaustively.
+///
+/// The fake has its own model of leases and atomic pushes, so anything that relies on how `git`
+/// enforces them is also covered against real `git` in [`test`], like `sync_host_collision`.
+#[cfg(test)]
+mod test_fake {
+    use std::{
+        collections::HashSet,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use crate::{
+        error::exit_code,
+        git_fake::{FakeClone, FakeRemote, RemoteFailure},
+        git_testing::{GitCommitId, INITIAL_BRANCH},
+        renderer::test::{MemoryRenderer, NoRenderer},
+        types::{Branch, Host, NomadRef, RefKind, Remote},
+    };
+
+    use super::{sync, BranchPatterns, Filter, LsPrinter, SyncExtras, Workflow};
+
+    fn sync_with(clone: &FakeClone, remotes: &[Remote], extras: SyncExtras) -> anyhow::Result<()> {
+        sync(
+            &mut NoRenderer,
+            &clone.git,
+            &clone.user,
+            &clone.host,
+            remotes,
+            &BranchPatterns::default(),
+            extras,
+        )
+    }
+
+    fn sync_host(clone: &FakeClone) {
+        sync_with(
+            clone,
+            &[clone.remote.always_borrow()],
+            SyncExtras::default(),
+        )
+        .unwrap();
+    }
+
+    fn purge(clone: &FakeClone, remotes: &[Remote], host: &str) -> anyhow::Result<()> {
+        Workflow::Purge {
+            user: clone.user.always_borrow(),
+            remotes: remotes.iter().map(Remote::always_borrow).collect(),
+            host_filter: Filter::Allow([Host::from(host)].into()),
+            older_than: None,
+            dry_run: false,
+        }
+        .execute(&mut NoRenderer, &clone.git)
+    }
+
+    fn hosts(nomad_refs: HashSet<NomadRef<'_, GitCommitId>>) -> Vec<String> {
+        let mut hosts = nomad_refs
+            .into_iter()
+            .map(|nomad_ref| nomad_ref.host.0.into_owned())
+            .collect::<Vec<_>>();
+        hosts.sort();
+        hosts.dedup();
+        hosts
+    }
+
+    /// Every host that pushed should be listed once fetched, no matter how many there are.
+    #[test]
+    fn ls_many_hosts() {
+        let remote = FakeRemote::init();
+        let names = (0..50).map(|i| format!("host{:02}", i)).collect::<Vec<_>>();
+        for name in &names {
+            let clone = remote.clone("user0", "unused");
+            let clone = FakeClone {
+                host: Host::from(name.clone()),
+                ..clone
+            };
+            clone.commit(name);
+            clone.push();
+        }
+
+        let host0 = remote.clone("user0", "host0");
+        host0.fetch();
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::Ls {
+            printer: LsPrinter::Ref,
+            user: host0.user.always_borrow(),
+            fetch_remotes: Vec::new(),
+            host_filter: Filter::Deny([Host::from("host00")].into()),
+            branch_filter: Filter::All,
+        }
+        .execute(&mut renderer, &host0.git)
+        .unwrap();
+
+        let expected = names[1..]
+            .iter()
+            .map(|name| format!("refs/nomad/{}/{}\n", name, INITIAL_BRANCH))
+            .collect::<String>();
+        assert_eq!(renderer.as_str(), expected);
+    }
+
+    /// A remote that cannot be reached should fail the sync without losing track of what the
+    /// other remotes have.
+    #[test]
+    fn sync_unreachable_mirror() {
+        let origin = FakeRemote::init();
+        let mirror = FakeRemote::init();
+        let host0 = origin.clone("user0", "host0");
+        let host1 = origin.clone("user0", "host1");
+        let remotes0 = [
+            host0.remote.always_borrow(),
+            host0.add_remote("mirror", &mirror),
+        ];
+        let remotes1 = [
+            host1.remote.always_borrow(),
+            host1.add_remote("mirror", &mirror),
+        ];
+
+        host0.checkout_new_branch("feature");
+        sync_with(&host0, &remotes0, SyncExtras::default()).unwrap();
+        sync_with(&host1, &remotes1, SyncExtras::default()).unwrap();
+
+        mirror.fail_with(Some(RemoteFailure::Unreachable));
+        host0.checkout_new_branch("another");
+        let error = sync_with(&host0, &remotes0, SyncExtras::default()).unwrap_err();
+        assert_eq!(exit_code(&error), 6);
+        assert_eq!(origin.nomad_refs().len(), 4);
+
+        // Nothing that the mirror might still have is pruned in the meantime.
+        let error = sync_with(&host1, &remotes1, SyncExtras::default()).unwrap_err();
+        assert_eq!(exit_code(&error), 6);
+        assert_eq!(hosts(host1.nomad_refs()), vec!["host0", "host1"]);
+
+        mirror.fail_with(None);
+        sync_with(&host0, &remotes0, SyncExtras::default()).unwrap();
+        assert_eq!(mirror.nomad_refs(), origin.nomad_refs());
+
+        // A deleted branch is pruned from both remotes once both are reachable again.
+        host0.delete_branch("feature");
+        sync_with(&host0, &remotes0, SyncExtras::default()).unwrap();
+        for remote in [&origin, &mirror] {
+            assert!(remote
+                .nomad_refs()
+                .iter()
+                .all(|nomad_ref| nomad_ref.branch.0 != "feature"));
+        }
+    }
+
+    /// A purge that cannot reach the remote should not delete anything locally either.
+    #[test]
+    fn purge_unreachable_remote() {
+        let remote = FakeRemote::init();
+        let host0 = remote.clone("user0", "host0");
+        let host1 = remote.clone("user0", "host1");
+        sync_host(&host1);
+        sync_host(&host0);
+        let remotes = [host0.remote.always_borrow()];
+
+        remote.fail_with(Some(RemoteFailure::Unreachable));
+        assert_eq!(exit_code(&purge(&host0, &remotes, "host1").unwrap_err()), 6);
+        assert_eq!(hosts(host0.nomad_refs()), vec!["host0", "host1"]);
+
+        remote.fail_with(Some(RemoteFailure::RejectPushes));
+        assert_eq!(exit_code(&purge(&host0, &remotes, "host1").unwrap_err()), 8);
+        assert_eq!(hosts(host0.nomad_refs()), vec!["host0", "host1"]);
+        assert_eq!(hosts(remote.nomad_refs()), vec!["host0", "host1"]);
+
+        remote.fail_with(None);
+        purge(&host0, &remotes, "host1").unwrap();
+        assert_eq!(hosts(host0.nomad_refs()), vec!["host0"]);
+        assert_eq!(hosts(remote.nomad_refs()), vec!["host0"]);
+
+        // The host name is free to be taken over by another clone.
+        let impostor = remote.clone("user0", "impostor");
+        let impostor = FakeClone {
+            host: Host::from("host1"),
+            ..impostor
+        };
+        sync_host(&impostor);
+    }
+
+    /// `purge` with `older_than` should only delete refs for hosts that have been inactive.
+    #[test]
+    fn purge_older_than() {
+        let remote = FakeRemote::init();
+        let host0 = remote.clone("user0", "host0");
+        let host1 = remote.clone("user0", "host1");
+        let host2 = remote.clone("user0", "host2");
+
+        let days_ago = |days: u64| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+                - days * 24 * 60 * 60
+        };
+        host1.commit_at("Ancient history", days_ago(400));
+        host2.commit_at("Recent history", days_ago(20));
+        sync_host(&host1);
+        sync_host(&host2);
+        sync_host(&host0);
+
+        let purge = |renderer: &mut MemoryRenderer, dry_run: bool| {
+            Workflow::Purge {
+                user: host0.user.always_borrow(),
+                remotes: vec![host0.remote.always_borrow()],
+                host_filter: Filter::All,
+                older_than: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+                dry_run,
+            }
+            .execute(renderer, &host0.git)
+            .unwrap();
+        };
+
+        let mut renderer = MemoryRenderer::new();
+        purge(&mut renderer, true);
+        let output = renderer.as_str();
+        assert!(output.contains("Would expire host1, last active 400 days ago\n"));
+        assert!(!output.contains("host2"));
+
+        purge(&mut MemoryRenderer::new(), false);
+        assert_eq!(hosts(remote.nomad_refs()), vec!["host0", "host2"]);
+        assert_eq!(hosts(host0.nomad_refs()), vec!["host0", "host2"]);
+    }
+
+    /// Stash entries and tags should be published alongside branches and pruned once dropped.
+    #[test]
+    fn sync_stash_and_tags() {
+        let remote = FakeRemote::init();
+        let host0 = remote.clone("user0", "host0");
+        let host1 = remote.clone("user0", "host1");
+        let extras = SyncExtras {
+            stash: true,
+            tags: true,
+            ..SyncExtras::default()
+        };
+        let branches = |clone: &FakeClone| {
+            let mut branches = clone
+                .nomad_refs()
+                .into_iter()
+                .filter(|nomad_ref| nomad_ref.host.0 == "host0")
+                .map(|nomad_ref| (nomad_ref.kind, nomad_ref.branch.0.into_owned()))
+                .collect::<Vec<_>>();
+            branches.sort();
+            branches
+        };
+        let branch = |kind, name: &str| (kind, name.to_string());
+
+        host0.stash("older changes");
+        host0.stash("newer changes");
+        host0.tag("v1");
+        sync_with(&host0, &[host0.remote.always_borrow()], extras).unwrap();
+        sync_with(&host1, &[host1.remote.always_borrow()], extras).unwrap();
+        assert_eq!(
+            branches(&host1),
+            vec![
+                branch(RefKind::Branch, "master"),
+                branch(RefKind::Stash, "0"),
+                branch(RefKind::Stash, "1"),
+                branch(RefKind::Tag, "v1"),
+            ]
+        );
+
+        Workflow::StashApply {
+            user: host1.user.always_borrow(),
+            host: host0.host.always_borrow(),
+            index: 1,
+            dry_run: false,
+        }
+        .execute(&mut NoRenderer, &host1.git)
+        .unwrap();
+        assert_eq!(host1.changes().as_deref(), Some("older changes"));
+
+        host0.clear_stash();
+        host0.delete_tag("v1");
+        sync_with(&host0, &[host0.remote.always_borrow()], extras).unwrap();
+        sync_with(&host1, &[host1.remote.always_borrow()], extras).unwrap();
+        assert_eq!(branches(&host1), vec![branch(RefKind::Branch, "master")]);
+    }
+
+    /// Uncommitted changes should follow the current branch, which requires there to be one.
+    #[test]
+    fn sync_worktree() {
+        let remote = FakeRemote::init();
+        let host0 = remote.clone("user0", "host0");
+        let host1 = remote.clone("user0", "host1");
+        let extras = SyncExtras {
+            worktree: true,
+            ..SyncExtras::default()
+        };
+
+        host0.write_changes("work in progress");
+        sync_with(&host0, &[host0.remote.always_borrow()], extras).unwrap();
+        sync_host(&host1);
+
+        Workflow::WorktreeApply {
+            user: host1.user.always_borrow(),
+            host: host0.host.always_borrow(),
+            branch: Branch::from(INITIAL_BRANCH),
+            dry_run: false,
+        }
+        .execute(&mut NoRenderer, &host1.git)
+        .unwrap();
+        assert_eq!(host1.changes().as_deref(), Some("work in progress"));
+
+        host0.detach_head();
+        let error = sync_with(&host0, &[host0.remote.always_borrow()], extras).unwrap_err();
+        assert_eq!(exit_code(&error), 5);
+    }
+
+    /// Snapshots live apart from branches, so a branch under `worktree/` should be synced
+    /// alongside the snapshot of its own uncommitted changes.
+    #[test]
+    fn sync_worktree_alongside_worktree_branch() {
+        let remote = FakeRemote::init();
+        let host0 = remote.clone("user0", "host0");
+        host0.checkout_new_branch("worktree/feature");
+        host0.write_changes("work in progress");
+        sync_with(
+            &host0,
+            &[host0.remote.always_borrow()],
+            SyncExtras {
+                worktree: true,
+                ..SyncExtras::default()
+            },
+        )
+        .unwrap();
+
+        let mut synced = remote
+            .nomad_refs()
+            .into_iter()
+            .map(|nomad_ref| (nomad_ref.kind, nomad_ref.branch.0.into_owned()))
+            .collect::<Vec<_>>();
+        synced.sort();
+        assert_eq!(
+            synced,
+            vec![
+                (RefKind::Branch, INITIAL_BRANCH.to_string()),
+                (RefKind::Branch, "worktree/feature".to_string()),
+                (RefKind::Worktree, "worktree/feature".to_string()),
+            ]
+        );
+    }
+
+    /// Hosts with extra commits should be reported as ahead, and local commits as behind.
+    #[test]
+    fn status_ahead_and_behind() {
+        let remote = FakeRemote::init();
+        let host0 = remote.clone("user0", "host0");
+        let host1 = remote.clone("user0", "host1");
+        let host2 = remote.clone("user0", "host2");
+        let initial_commit = host2.current_commit();
+
+        host0.commit("host0 work");
+        let host0_commit = host0.commit("more host0 work");
+        sync_host(&host0);
+        sync_host(&host1);
+        let host2_commit = host2.commit("host2 work");
+
+        let mut renderer = MemoryRenderer::new();
+        Workflow::Status {
+            user: host2.user.always_borrow(),
+            fetch_remotes: vec![host2.remote.always_borrow()],
+            host_filter: Filter::Deny([host2.host.always_borrow()].into()),
+            branch_filter: Filter::All,
+        }
+        .execute(&mut renderer, &host2.git)
+        .unwrap();
+
+        assert_eq!(
+            renderer.as_str(),
+            format!(
+                concat!(
+                    "master (diverged)\n",
+                    "  refs/heads/master -> {2} (compared against)\n",
+                    "  refs/nomad/host0/master -> {1} (ahead 2, behind 1, diverged from {0})\n",
+                    "  refs/nomad/host1/master -> {0} (behind 1)\n",
+                ),
+                initial_commit.0, host0_commit.0, host2_commit.0
+            )
+        );
+    }
+
+    /// Checking out a branch that has diverged should require `force`.
+    #[test]
+    fn checkout_diverged() {
+        let remote = FakeRemote::init();
+        let host0 = remote.clone("user0", "host0");
+        let host1 = remote.clone("user0", "host1");
+        let host0_commit = host0.commit("host0 work");
+        sync_host(&host0);
+        let host1_commit = host1.commit("host1 work");
+
+        let checkout = |force| {
+            Workflow::Checkout {
+                user: host1.user.always_borrow(),
+                fetch_remotes: vec![host1.remote.always_borrow()],
+                host_filter: Filter::Allow([host0.host.always_borrow()].into()),
+                branch: Branch::from(INITIAL_BRANCH),
+                force,
+                dry_run: false,
+            }
+            .execute(&mut NoRenderer, &host1.git)
+        };
+
+        assert!(checkout(false).is_err());
+        assert_eq!(host1.current_commit(), host1_commit);
+
+        checkout(true).unwrap();
+        assert_eq!(host1.current_commit(), host0_commit);
     }
 }
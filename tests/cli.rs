@@ -0,0 +1,97 @@
+//! Runs the real `git-nomad` binary for behaviour that depends on the whole process, like exit
+//! codes and what ends up on stdout.
+
+mod common;
+
+use git_nomad::NomadError;
+use tempfile::tempdir;
+
+use common::{another_clone, command, remote_and_clone, run, run_ok, GIT_NOMAD};
+
+/// Commands that need a branch should fail distinctly when `HEAD` is detached.
+#[test]
+fn detached_head() {
+    let root = tempdir().unwrap();
+    let (_, clone) = remote_and_clone(root.path());
+    run_ok("git", &clone, &["commit", "--allow-empty", "-m", "Initial"]);
+    run_ok("git", &clone, &["checkout", "--detach"]);
+
+    let output = run(GIT_NOMAD, &clone, &["ls", "--head"]);
+    assert_eq!(
+        output.status.code(),
+        Some(i32::from(NomadError::DetachedHead.exit_code()))
+    );
+}
+
+/// Verbose output should describe what is being run.
+#[test]
+fn ls_verbose() {
+    let root = tempdir().unwrap();
+    let (_, clone) = remote_and_clone(root.path());
+    run_ok("git", &clone, &["commit", "--allow-empty", "-m", "Initial"]);
+
+    assert!(!run_ok(GIT_NOMAD, &clone, &["ls", "-vv"]).is_empty());
+}
+
+/// Expiring inactive hosts should leave the current host alone however old its commits are,
+/// unless every host is asked for.
+#[test]
+fn purge_older_than_keeps_current_host() {
+    let root = tempdir().unwrap();
+    let (remote, clone) = remote_and_clone(root.path());
+
+    let output = command("git", &clone)
+        .env("GIT_AUTHOR_DATE", "2000-01-01T00:00:00Z")
+        .env("GIT_COMMITTER_DATE", "2000-01-01T00:00:00Z")
+        .args(["commit", "--allow-empty", "-m", "Ancient history"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    run_ok(GIT_NOMAD, &clone, &["sync"]);
+
+    let remote_refs = || run_ok("git", &remote, &["for-each-ref", "refs/nomad"]);
+    let before = remote_refs();
+    assert!(!before.is_empty());
+
+    run_ok(GIT_NOMAD, &clone, &["purge", "--older-than", "1s"]);
+    assert_eq!(remote_refs(), before);
+
+    run_ok(GIT_NOMAD, &clone, &["purge", "--all", "--older-than", "1s"]);
+    assert_eq!(remote_refs(), "");
+}
+
+/// A host that another clone purged should be able to sync again, rather than mistaking what it
+/// last saw of the remote for another clone taking over its name.
+#[test]
+fn sync_after_purge_elsewhere() {
+    let root = tempdir().unwrap();
+    let (remote, host0) = remote_and_clone(root.path());
+    let host1 = another_clone(root.path(), &remote, "host1", "host1");
+
+    for clone in [&host0, &host1] {
+        run_ok("git", clone, &["commit", "--allow-empty", "-m", "Initial"]);
+        run_ok(GIT_NOMAD, clone, &["sync"]);
+    }
+
+    let host1_ref = || {
+        let output = run(
+            "git",
+            &remote,
+            &[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                "refs/nomad/user0/host1/master",
+            ],
+        );
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    };
+    let head = run_ok("git", &host1, &["rev-parse", "HEAD"]);
+    assert_eq!(host1_ref(), head);
+
+    run_ok(GIT_NOMAD, &host0, &["purge", "--all"]);
+    assert_eq!(host1_ref(), "");
+
+    run_ok(GIT_NOMAD, &host1, &["sync"]);
+    assert_eq!(host1_ref(), head);
+}
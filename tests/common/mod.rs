@@ -0,0 +1,72 @@
+//! Helpers for running the real `git-nomad` binary, which only integration tests can do since they
+//! are the only ones that cargo tells where the binary is.
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+pub const GIT_NOMAD: &str = env!("CARGO_BIN_EXE_git-nomad");
+
+/// A command for `program` in `dir` with `git-nomad` on `$PATH`, so that git can find it for
+/// `git nomad`.
+pub fn command(program: &str, dir: &Path) -> Command {
+    let bin_dir = Path::new(GIT_NOMAD).parent().unwrap();
+    let path = std::env::join_paths(std::iter::once(bin_dir.to_path_buf()).chain(
+        std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default()),
+    ))
+    .unwrap();
+
+    let mut command = Command::new(program);
+    command
+        .current_dir(dir)
+        .env("PATH", path)
+        .env("GIT_AUTHOR_NAME", "git-nomad")
+        .env("GIT_AUTHOR_EMAIL", "git-nomad@invalid")
+        .env("GIT_COMMITTER_NAME", "git-nomad")
+        .env("GIT_COMMITTER_EMAIL", "git-nomad@invalid");
+    command
+}
+
+/// Run `program` in `dir`, see [`command`].
+pub fn run(program: &str, dir: &Path, args: &[&str]) -> Output {
+    command(program, dir).args(args).output().unwrap()
+}
+
+/// Like [`run`], but fails the test if `program` does and returns its trimmed stdout.
+pub fn run_ok(program: &str, dir: &Path, args: &[&str]) -> String {
+    let output = run(program, dir, args);
+    assert!(
+        output.status.success(),
+        "{} {:?} failed: {}",
+        program,
+        args,
+        String::from_utf8_lossy(&output.stderr),
+    );
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// A bare `remote` and a `clone` of it in `root`, pushing as `user0` from `host0`.
+pub fn remote_and_clone(root: &Path) -> (PathBuf, PathBuf) {
+    let remote = root.join("remote");
+
+    run_ok(
+        "git",
+        root,
+        &["init", "--bare", "--initial-branch", "master", "remote"],
+    );
+    let clone = another_clone(root, &remote, "clone", "host0");
+
+    (remote, clone)
+}
+
+/// Another clone of `remote` in `root` named `name`, pushing as `user0` from `host`.
+pub fn another_clone(root: &Path, remote: &Path, name: &str, host: &str) -> PathBuf {
+    let clone = root.join(name);
+
+    run_ok("git", root, &["clone", remote.to_str().unwrap(), name]);
+    run_ok("git", &clone, &["config", "nomad.user", "user0"]);
+    run_ok("git", &clone, &["config", "nomad.host", host]);
+
+    clone
+}
@@ -0,0 +1,62 @@
+//! Runs the real `git-nomad` binary from the hook it installs.
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use tempfile::tempdir;
+
+use common::{remote_and_clone, run, run_ok, GIT_NOMAD};
+
+/// Commits in quick succession each start a background push, which should all succeed and leave
+/// the remote at the final commit rather than racing each other.
+#[test]
+fn hook_pushes_quick_commits() {
+    let root = tempdir().unwrap();
+    let (remote, clone) = remote_and_clone(root.path());
+
+    run_ok(
+        "git",
+        &clone,
+        &["commit", "--allow-empty", "-m", "Before hooks"],
+    );
+    run_ok(GIT_NOMAD, &clone, &["hooks", "install"]);
+
+    for i in 0..10 {
+        run_ok(
+            "git",
+            &clone,
+            &["commit", "--allow-empty", "-m", &format!("Commit {}", i)],
+        );
+    }
+    let head = run_ok("git", &clone, &["rev-parse", "HEAD"]);
+
+    let pushed = || {
+        let output = run(
+            "git",
+            &remote,
+            &[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                "refs/nomad/user0/host0/master",
+            ],
+        );
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    };
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while pushed() != head && Instant::now() < deadline {
+        sleep(Duration::from_millis(100));
+    }
+    assert_eq!(pushed(), head);
+
+    // The pushes should have left consistent leases behind, rather than making this clone look
+    // like a different one pushing as the same host.
+    run_ok(GIT_NOMAD, &clone, &["sync"]);
+    assert_eq!(pushed(), head);
+}